@@ -1,35 +1,135 @@
 use crossbeam_channel::unbounded;
-use std::sync::Arc;
 use std::{thread, time::Duration};
 
-use crate::audio::engine::{AudioEngine, PlaybackState};
+use crate::audio::{analyzer::Analyzer, engine::AudioEngine};
+use crate::config::Config;
+use crate::controller::lyrics::{self, CurrentLyrics};
 use crate::controller::metadata::Metadata;
-use crate::controller::player::{AudioCommand, AudioEvent, Controller, PlayerState, ResHandler};
+use crate::controller::player::{AudioCommand, AudioEvent, Controller, ResHandler};
+use crate::integrations::{notifications, tray};
+use crate::library::{
+    self, Library,
+    playlist::Playlists,
+    stats::{self, Stats},
+};
+use crate::session::{self, Session};
+use crate::ui::accent::{self, AlbumPalette};
 use crate::ui::assets::Assets;
+use crate::ui::components::notifications::{NotificationLevel, report_error};
+use crate::ui::components::{
+    LibraryView, lyrics_editor, palette, search,
+    waveform::{self, CurrentWaveform},
+};
+use crate::ui::keymap::{self, Keymap};
+use crate::ui::theme::ThemeRegistry;
 use crate::ui::wiremann::Wiremann;
 use gpui::*;
 use gpui_component::*;
 
+const SESSION_SAVE_INTERVAL: Duration = Duration::from_secs(15);
+
 pub fn run() {
     let (audio_tx, audio_rx) = unbounded::<AudioCommand>();
-    let (events_tx, event_rx) = unbounded::<AudioEvent>();
+    let (events_tx, event_rx) = async_channel::unbounded::<AudioEvent>();
+
+    // Shown once the main window is up to hold notifications.
+    let mut startup_errors: Vec<(&'static str, anyhow::Error)> = Vec::new();
+
+    let config = Config::load().unwrap_or_else(|err| {
+        startup_errors.push(("Could not load config", err));
+        Config::default()
+    });
+    let audio_config = config.audio.clone();
+
+    let (themes, theme_errors) = ThemeRegistry::load();
+    startup_errors.extend(
+        theme_errors
+            .into_iter()
+            .map(|err| ("Could not load theme", err)),
+    );
 
+    let (keymap, keymap_errors) = Keymap::load();
+    startup_errors.extend(
+        keymap_errors
+            .into_iter()
+            .map(|err| ("Could not load keymap", err)),
+    );
+
+    let analyzer = Analyzer::new();
+    let engine_analyzer = analyzer.clone();
     thread::spawn(move || {
-        AudioEngine::run(audio_rx, events_tx);
+        AudioEngine::run(audio_rx, events_tx, audio_config, engine_analyzer);
+    });
+
+    let session = Session::load().unwrap_or_else(|err| {
+        startup_errors.push(("Could not restore the session", err));
+        Session::default()
     });
 
-    let controller = Controller::new(audio_tx, event_rx, PlayerState::default());
+    let controller = Controller::new(audio_tx, event_rx, session.player_state(), analyzer);
+    controller.refresh_output_devices();
+    session.restore(&controller);
 
     let app = Application::new().with_assets(Assets);
 
     app.run(move |cx| {
         gpui_component::init(cx);
-        let bounds = Bounds::centered(None, size(px(1280.0), px(760.0)), cx);
+        keymap::init(cx);
+        palette::init(cx);
+        search::init(cx);
+        lyrics_editor::init(cx);
+        keymap.bind(None, cx);
+        cx.set_global(keymap);
+
+        let controller_evt_clone = controller.clone();
+        cx.set_global(controller);
+
+        // Whatever changes the config, be it the settings page or an edit on
+        // disk, the engine and library follow along from here.
+        let mut applied = config.clone();
+        cx.set_global(config);
+        cx.set_global(themes);
+        cx.set_global(AlbumPalette::default());
+        cx.set_global(CurrentLyrics::default());
+        cx.set_global(CurrentWaveform::default());
+        cx.observe_global::<Config>(move |cx| {
+            let config = cx.global::<Config>().clone();
+            cx.global::<Controller>()
+                .apply_audio_config(&applied.audio, &config.audio);
+
+            if applied.library.roots != config.library.roots {
+                library::rescan(cx);
+            }
+
+            applied = config;
+        })
+        .detach();
+
+        cx.set_global(Library::default());
+        cx.set_global(LibraryView::default());
+        library::rescan(cx);
+
+        cx.set_global(Playlists::load().unwrap_or_else(|err| {
+            startup_errors.push(("Could not load playlists", err));
+            Playlists::default()
+        }));
+        cx.set_global(Stats::load().unwrap_or_else(|err| {
+            startup_errors.push(("Could not load play counts", err));
+            Stats::default()
+        }));
+        stats::init(cx);
+
+        notifications::init(cx);
+        let tray_rx = tray::init(cx);
+
+        let bounds = session.window.map(WindowBounds::from).unwrap_or_else(|| {
+            WindowBounds::Windowed(Bounds::centered(None, size(px(1280.0), px(760.0)), cx))
+        });
 
         cx.spawn(async move |cx| {
             cx.open_window(
                 WindowOptions {
-                    window_bounds: Some(WindowBounds::Windowed(bounds)),
+                    window_bounds: Some(bounds),
                     app_id: Some(String::from("wiremann")),
                     focus: true,
                     titlebar: Some(TitlebarOptions {
@@ -44,76 +144,193 @@ pub fn run() {
                     ..Default::default()
                 },
                 |window, cx| {
-                    let controller_evt_clone = controller.clone();
-
-                    cx.set_global(controller);
-
-                    let view = cx.new(|cx| Wiremann::new(cx));
+                    let view = cx.new(|cx| Wiremann::new(window, cx));
 
                     cx.new(|cx| {
                         let res_handler = cx.new(|_| ResHandler {});
-                        let arc_res = Arc::new(res_handler.clone());
+                        let event_handler = res_handler.clone();
                         cx.spawn(async move |_, cx| {
-                            let res_handler = arc_res.clone();
-                            loop {
-                                while let Ok(event) = controller_evt_clone.event_rx.try_recv() {
-                                    res_handler.update(&mut cx.clone(), |res_handler, cx| {
-                                        res_handler.handle(cx, event);
-                                    });
+                            // Wakes only when the engine has something to say.
+                            while let Ok(event) = controller_evt_clone.event_rx.recv().await {
+                                let result = event_handler.update(cx, |res_handler, cx| {
+                                    res_handler.handle(cx, event);
+                                });
+
+                                if result.is_err() {
+                                    break;
                                 }
-                                cx.background_executor()
-                                    .timer(Duration::from_millis(100))
-                                    .await;
                             }
                         })
                         .detach();
 
-                        let playbar_view = view.clone();
-
-                        cx.subscribe(
-                            &res_handler,
-                            move |_, _, event: &AudioEvent, cx| match event {
-                                AudioEvent::StateChanged(state) => {
-                                    cx.global_mut::<Controller>().state = state.clone();
-
-                                    if state.state == PlaybackState::Playing {
-                                        playbar_view.update(cx, |this, cx| {
-                                            this.controlbar.update(cx, |this, cx| {
-                                                this.playback_slider_state.update(
-                                                    cx,
-                                                    |this, cx| {
-                                                        if let Some(meta) = cx
-                                                            .global::<Controller>()
-                                                            .state
-                                                            .meta
-                                                            .clone()
-                                                        {
-                                                            this.set_value(
-                                                                secs_to_slider(
-                                                                    state.position,
-                                                                    meta.duration,
-                                                                ),
-                                                                cx,
-                                                            );
-                                                        }
-                                                        cx.notify();
-                                                    },
-                                                );
-                                            })
-                                        })
+                        let notify_view = view.clone();
+
+                        cx.subscribe(&res_handler, move |_, _, event: &AudioEvent, cx| {
+                            cx.global_mut::<Controller>().apply(event);
+
+                            match event {
+                                AudioEvent::TrackLoaded(path) => {
+                                    if let Ok(meta) = Metadata::read(path.clone()) {
+                                        cx.global_mut::<Controller>().set_meta(meta);
                                     }
-                                    cx.notify();
+
+                                    notifications::track_loaded(path.clone(), cx);
+                                    accent::load(path.clone(), cx);
+                                    lyrics::load(path.clone(), cx);
+                                    waveform::load(path.clone(), cx);
                                 }
-                                AudioEvent::TrackLoaded(path) => {
-                                    let meta = Metadata::read(path.clone()).expect("No metadata");
-                                    cx.global_mut::<Controller>().set_meta_in_engine(meta);
-                                    cx.notify();
+                                AudioEvent::TrackEnded => {
+                                    if let Some(path) =
+                                        cx.global::<Controller>().state.current.clone()
+                                    {
+                                        stats::record_play(&path, cx);
+                                    }
+                                }
+                                AudioEvent::Error(err) => {
+                                    let message = match &err.path {
+                                        Some(path) => {
+                                            format!("{} ({})", err.message, path.display())
+                                        }
+                                        None => err.message.clone(),
+                                    };
+
+                                    notify_view.update(cx, |this, cx| {
+                                        this.notifications.update(cx, |this, cx| {
+                                            this.push(
+                                                NotificationLevel::Error,
+                                                err.title(),
+                                                message,
+                                                cx,
+                                            );
+                                        })
+                                    });
                                 }
                                 _ => (),
-                            },
-                        )
+                            }
+
+                            cx.notify();
+                        })
                         .detach();
 
+                        // Saved periodically as well as on close, so a crash
+                        // loses at most a few seconds.
+                        cx.spawn_in(window, async move |_, cx| {
+                            loop {
+                                cx.background_executor().timer(SESSION_SAVE_INTERVAL).await;
+
+                                if cx.update(|window, cx| session::save(window, cx)).is_err() {
+                                    break;
+                                }
+                            }
+                        })
+                        .detach();
+
+                        for (title, err) in startup_errors {
+                            report_error(title, err, cx);
+                        }
+
+                        let (config_tx, config_rx) = async_channel::unbounded();
+                        match Config::watch(config_tx) {
+                            Ok(watcher) => {
+                                let config_view = view.clone();
+                                cx.spawn(async move |_, cx| {
+                                    // Owning the watcher here keeps it alive as long as
+                                    // reloads are being handled.
+                                    let _watcher = watcher;
+
+                                    while let Ok(config) = config_rx.recv().await {
+                                        let result = config_view.update(cx, |this, cx| {
+                                            reload_config(config, this, cx);
+                                        });
+
+                                        if result.is_err() {
+                                            break;
+                                        }
+                                    }
+                                })
+                                .detach();
+                            }
+                            Err(err) => report_error("Could not watch the config file", err, cx),
+                        }
+
+                        let (themes_tx, themes_rx) = async_channel::unbounded();
+                        match ThemeRegistry::watch(themes_tx) {
+                            Ok(watcher) => {
+                                let themes_view = view.clone();
+                                cx.spawn(async move |_, cx| {
+                                    let _watcher = watcher;
+
+                                    while themes_rx.recv().await.is_ok() {
+                                        let result = themes_view.update(cx, |this, cx| {
+                                            let (themes, errors) = ThemeRegistry::load();
+                                            report_errors("Could not load theme", errors, this, cx);
+
+                                            if cx.global::<ThemeRegistry>() != &themes {
+                                                cx.set_global(themes);
+                                            }
+                                        });
+
+                                        if result.is_err() {
+                                            break;
+                                        }
+                                    }
+                                })
+                                .detach();
+                            }
+                            Err(err) => {
+                                report_error("Could not watch the themes directory", err, cx)
+                            }
+                        }
+
+                        let (keymap_tx, keymap_rx) = async_channel::unbounded();
+                        match Keymap::watch(keymap_tx) {
+                            Ok(watcher) => {
+                                let keymap_view = view.clone();
+                                cx.spawn(async move |_, cx| {
+                                    let _watcher = watcher;
+
+                                    while keymap_rx.recv().await.is_ok() {
+                                        let result = keymap_view.update(cx, |this, cx| {
+                                            let (keymap, errors) = Keymap::load();
+                                            report_errors(
+                                                "Could not load keymap",
+                                                errors,
+                                                this,
+                                                cx,
+                                            );
+
+                                            let previous = cx.global::<Keymap>().clone();
+                                            if previous != keymap {
+                                                keymap.bind(Some(&previous), cx);
+                                                cx.set_global(keymap);
+                                            }
+                                        });
+
+                                        if result.is_err() {
+                                            break;
+                                        }
+                                    }
+                                })
+                                .detach();
+                            }
+                            Err(err) => report_error("Could not watch the keymap file", err, cx),
+                        }
+
+                        cx.spawn_in(window, async move |_, cx| {
+                            while let Ok(command) = tray_rx.recv().await {
+                                let result = cx.update(|window, cx| {
+                                    tray::handle_command(command, window, cx);
+                                });
+
+                                if result.is_err() {
+                                    break;
+                                }
+                            }
+                        })
+                        .detach();
+
+                        window.on_window_should_close(cx, tray::should_close);
+
                         Root::new(view, window, cx)
                     })
                 },
@@ -125,10 +342,40 @@ pub fn run() {
     });
 }
 
-fn secs_to_slider(pos: u64, duration: u64) -> f32 {
-    if duration == 0 {
-        0.0
-    } else {
-        (pos as f32 / duration as f32) * 100.0
+fn reload_config(config: anyhow::Result<Config>, view: &mut Wiremann, cx: &mut Context<Wiremann>) {
+    let config = match config {
+        Ok(config) => config,
+        Err(err) => {
+            // Keep running with the last good config until the file is fixed.
+            view.notifications.update(cx, |this, cx| {
+                this.push(
+                    NotificationLevel::Error,
+                    "Could not reload config",
+                    format!("{err:#}"),
+                    cx,
+                );
+            });
+            return;
+        }
+    };
+
+    if cx.global::<Config>() == &config {
+        return;
+    }
+
+    cx.set_global(config);
+    cx.notify();
+}
+
+fn report_errors(
+    title: &'static str,
+    errors: Vec<anyhow::Error>,
+    view: &mut Wiremann,
+    cx: &mut Context<Wiremann>,
+) {
+    for err in errors {
+        view.notifications.update(cx, |this, cx| {
+            this.push(NotificationLevel::Error, title, format!("{err:#}"), cx);
+        });
     }
 }
@@ -0,0 +1,329 @@
+use rodio::{Source, source::SeekError};
+use std::{
+    f32::consts::PI,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Frames kept for analysis, and the FFT size. About 46 ms at 44.1 kHz.
+pub const WINDOW: usize = 2048;
+
+/// Frames the tap collects before handing them over, so the lock is taken a
+/// few times per buffer rather than for every sample.
+const BATCH: usize = 256;
+
+/// Spectrum range shown, in Hz.
+const MIN_FREQUENCY: f32 = 30.0;
+const MAX_FREQUENCY: f32 = 16_000.0;
+
+/// Levels below this many dB show as empty bars.
+const FLOOR_DB: f32 = -70.0;
+
+/// The latest frames played, shared between the tap on the audio thread
+/// and whoever draws them. Cloning shares the same buffer.
+#[derive(Debug, Clone, Default)]
+pub struct Analyzer {
+    capture: Arc<Mutex<Capture>>,
+}
+
+#[derive(Debug, Default)]
+struct Capture {
+    /// Stereo frames, oldest first. Mono is duplicated to both sides.
+    frames: Vec<[f32; 2]>,
+    sample_rate: u32,
+}
+
+/// A copy of the captured frames to analyze.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub frames: Vec<[f32; 2]>,
+    pub sample_rate: u32,
+}
+
+impl Analyzer {
+    pub fn new() -> Self {
+        Analyzer::default()
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let capture = self.capture.lock().unwrap_or_else(|err| err.into_inner());
+
+        Snapshot {
+            frames: capture.frames.clone(),
+            sample_rate: capture.sample_rate,
+        }
+    }
+
+    /// Forgets the captured frames, so a stopped or new track doesn't show
+    /// the old one's.
+    pub fn clear(&self) {
+        if let Ok(mut capture) = self.capture.lock() {
+            capture.frames.clear();
+        }
+    }
+
+    /// Never blocks: the audio thread gives up on a batch rather than wait
+    /// for the UI to finish reading.
+    fn try_push(&self, frames: &[[f32; 2]], sample_rate: u32) -> bool {
+        let Ok(mut capture) = self.capture.try_lock() else {
+            return false;
+        };
+
+        capture.sample_rate = sample_rate;
+        capture.frames.extend_from_slice(frames);
+        let excess = capture.frames.len().saturating_sub(WINDOW);
+        capture.frames.drain(..excess);
+        true
+    }
+}
+
+impl Snapshot {
+    /// Average of both channels.
+    pub fn mono(&self) -> Vec<f32> {
+        self.frames
+            .iter()
+            .map(|[left, right]| (left + right) * 0.5)
+            .collect()
+    }
+
+    pub fn spectrum(&self, bands: usize) -> Vec<f32> {
+        spectrum(&self.mono(), self.sample_rate, bands)
+    }
+}
+
+/// Passes a source through unchanged while copying what it plays to an
+/// [`Analyzer`].
+pub struct Tap<S> {
+    source: S,
+    analyzer: Analyzer,
+    pending: Vec<[f32; 2]>,
+    frame: [f32; 2],
+    channel: usize,
+}
+
+impl<S: Source> Tap<S> {
+    pub fn new(source: S, analyzer: Analyzer) -> Self {
+        Tap {
+            source,
+            analyzer,
+            pending: Vec::with_capacity(BATCH),
+            frame: [0.0; 2],
+            channel: 0,
+        }
+    }
+
+    fn flush(&mut self) {
+        if self
+            .analyzer
+            .try_push(&self.pending, self.source.sample_rate())
+        {
+            self.pending.clear();
+        } else if self.pending.len() > WINDOW {
+            let excess = self.pending.len() - WINDOW;
+            self.pending.drain(..excess);
+        }
+    }
+}
+
+impl<S: Source> Iterator for Tap<S> {
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.source.next()?;
+        let channels = (self.source.channels() as usize).max(1);
+
+        if self.channel < 2 {
+            self.frame[self.channel] = sample;
+        }
+        self.channel += 1;
+
+        if self.channel >= channels {
+            if channels == 1 {
+                self.frame[1] = self.frame[0];
+            }
+            self.pending.push(self.frame);
+            self.channel = 0;
+
+            if self.pending.len() >= BATCH {
+                self.flush();
+            }
+        }
+
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.source.size_hint()
+    }
+}
+
+impl<S: Source> Source for Tap<S> {
+    fn current_span_len(&self) -> Option<usize> {
+        self.source.current_span_len()
+    }
+
+    fn channels(&self) -> rodio::ChannelCount {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> rodio::SampleRate {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.pending.clear();
+        self.channel = 0;
+        self.source.try_seek(pos)
+    }
+}
+
+/// Levels of `bands` logarithmically spaced frequency bands over the last
+/// [`WINDOW`] samples, from 0 (at or below [`FLOOR_DB`]) to 1 (full scale).
+pub fn spectrum(samples: &[f32], sample_rate: u32, bands: usize) -> Vec<f32> {
+    if bands == 0 || sample_rate == 0 {
+        return vec![0.0; bands];
+    }
+
+    let start = samples.len().saturating_sub(WINDOW);
+    let samples = &samples[start..];
+
+    let mut re = vec![0.0; WINDOW];
+    let mut im = vec![0.0; WINDOW];
+    for (ix, sample) in samples.iter().enumerate() {
+        re[ix] = sample * hann(ix, WINDOW);
+    }
+    fft(&mut re, &mut im);
+
+    // A full-scale sine peaks at N/4 through a Hann window.
+    let scale = 4.0 / WINDOW as f32;
+    let magnitudes: Vec<f32> = re[..WINDOW / 2]
+        .iter()
+        .zip(&im[..WINDOW / 2])
+        .map(|(re, im)| (re * re + im * im).sqrt() * scale)
+        .collect();
+
+    let bin_width = sample_rate as f32 / WINDOW as f32;
+    let edge = |band: usize| band_edge(band, bands, sample_rate);
+
+    (0..bands)
+        .map(|band| {
+            let low = (edge(band) / bin_width).round() as usize;
+            let high = ((edge(band + 1) / bin_width).round() as usize).max(low + 1);
+            let peak = magnitudes[low.min(magnitudes.len() - 1)..high.min(magnitudes.len())]
+                .iter()
+                .fold(0.0f32, |peak, magnitude| peak.max(*magnitude));
+
+            let db = 20.0 * peak.max(f32::MIN_POSITIVE).log10();
+            ((db - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0)
+        })
+        .collect()
+}
+
+/// Lower frequency of `band`, or the upper one of the band before it.
+fn band_edge(band: usize, bands: usize, sample_rate: u32) -> f32 {
+    let max = MAX_FREQUENCY.min(sample_rate as f32 / 2.0);
+    MIN_FREQUENCY * (max / MIN_FREQUENCY).powf(band as f32 / bands as f32)
+}
+
+fn hann(ix: usize, len: usize) -> f32 {
+    0.5 - 0.5 * (2.0 * PI * ix as f32 / (len - 1) as f32).cos()
+}
+
+/// In-place iterative radix-2 FFT. The length must be a power of two.
+pub fn fft(re: &mut [f32], im: &mut [f32]) {
+    let len = re.len();
+    debug_assert!(len.is_power_of_two() && im.len() == len);
+    if len < 2 {
+        return;
+    }
+
+    let bits = len.trailing_zeros();
+    for ix in 0..len {
+        let reversed = ix.reverse_bits() >> (usize::BITS - bits);
+        if ix < reversed {
+            re.swap(ix, reversed);
+            im.swap(ix, reversed);
+        }
+    }
+
+    let mut size = 2;
+    while size <= len {
+        let angle = -2.0 * PI / size as f32;
+        let (step_re, step_im) = (angle.cos(), angle.sin());
+
+        for start in (0..len).step_by(size) {
+            let (mut w_re, mut w_im) = (1.0f32, 0.0f32);
+
+            for k in 0..size / 2 {
+                let even = start + k;
+                let odd = even + size / 2;
+                let t_re = re[odd] * w_re - im[odd] * w_im;
+                let t_im = re[odd] * w_im + im[odd] * w_re;
+
+                re[odd] = re[even] - t_re;
+                im[odd] = im[even] - t_im;
+                re[even] += t_re;
+                im[even] += t_im;
+
+                (w_re, w_im) = (
+                    w_re * step_re - w_im * step_im,
+                    w_re * step_im + w_im * step_re,
+                );
+            }
+        }
+
+        size *= 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    const SAMPLE_RATE: u32 = 44_100;
+    const BANDS: usize = 24;
+
+    /// A half-scale sine of `frequency` Hz, `len` samples long.
+    fn sine(frequency: f32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|ix| (2.0 * PI * frequency * ix as f32 / SAMPLE_RATE as f32).sin() * 0.5)
+            .collect()
+    }
+
+    #[test]
+    fn tap_passes_samples_through() {
+        let samples = sine(1000.0, WINDOW * 2);
+        let analyzer = Analyzer::new();
+        let tap = Tap::new(
+            SamplesBuffer::new(1, SAMPLE_RATE, samples.clone()),
+            analyzer.clone(),
+        );
+
+        assert_eq!(tap.collect::<Vec<_>>(), samples);
+
+        // Only the latest window is kept, with mono on both sides.
+        let snapshot = analyzer.snapshot();
+        assert_eq!(snapshot.sample_rate, SAMPLE_RATE);
+        assert!(snapshot.frames.iter().all(|[left, right]| left == right));
+        assert_eq!(snapshot.mono(), samples[WINDOW..]);
+    }
+
+    #[test]
+    fn sine_peaks_in_its_band() {
+        let levels = spectrum(&sine(1000.0, WINDOW), SAMPLE_RATE, BANDS);
+        let peak = (0..BANDS)
+            .max_by(|a, b| levels[*a].total_cmp(&levels[*b]))
+            .unwrap();
+
+        let band = band_edge(peak, BANDS, SAMPLE_RATE)..band_edge(peak + 1, BANDS, SAMPLE_RATE);
+        assert!(band.contains(&1000.0), "peak in {band:?}");
+        // Half scale is about -6 dB.
+        assert!(levels[peak] > 0.85, "peak level {}", levels[peak]);
+        assert!(levels[0] < 0.1 && levels[BANDS - 1] < 0.1, "{levels:?}");
+    }
+}
@@ -1,17 +1,51 @@
+use super::{
+    analyzer::{Analyzer, Tap},
+    error::{AudioError, AudioErrorKind},
+    output::{self, Output, OutputBackend},
+    replaygain,
+};
+use crate::config::{AudioConfig, ReplayGainMode};
 use crate::controller::{
     metadata::Metadata,
     player::{AudioCommand, AudioEvent, PlayerState},
 };
-use crossbeam_channel::{Receiver, Sender, select, tick};
-use rodio::{Decoder, OutputStream, OutputStreamBuilder, Sink, Source, decoder::DecoderBuilder};
-use std::{fs::File, io::BufReader, path::PathBuf, time::Duration};
+use crossbeam_channel::{Receiver, Sender, never, select, tick, unbounded};
+use rodio::{Sink, Source, cpal, decoder::DecoderBuilder};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 pub struct AudioEngine {
     sink: Sink,
-    stream_handle: OutputStream,
+    output: Option<Output>,
+    backend: OutputBackend,
+    device_error_tx: Sender<cpal::StreamError>,
+    device_error_rx: Receiver<cpal::StreamError>,
     player_state: PlayerState,
+    /// Indices played since shuffle was turned on, most recent last.
+    history: Vec<usize>,
+    rng: u64,
+    replaygain: ReplayGainMode,
+    preamp: f32,
+    /// ReplayGain factor of the current track.
+    track_gain: f32,
+    crossfade: Duration,
+    /// The previous track while it fades out under the current one.
+    fading: Option<Fade>,
+    /// Receives what the current track plays, for the visualizer.
+    analyzer: Analyzer,
     audio_rx: Receiver<AudioCommand>,
-    event_tx: Sender<AudioEvent>,
+    event_tx: async_channel::Sender<AudioEvent>,
+}
+
+struct Fade {
+    sink: Sink,
+    volume: f32,
+    started: Instant,
+    duration: Duration,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
@@ -22,132 +56,1122 @@ pub enum PlaybackState {
     Paused,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+pub enum RepeatMode {
+    #[default]
+    Off,
+    All,
+    One,
+}
+
 impl AudioEngine {
-    pub fn run(audio_rx: Receiver<AudioCommand>, event_tx: Sender<AudioEvent>) {
-        let stream_handle = OutputStreamBuilder::open_default_stream().unwrap();
-        let sink = Sink::connect_new(&stream_handle.mixer());
+    pub fn run(
+        audio_rx: Receiver<AudioCommand>,
+        event_tx: async_channel::Sender<AudioEvent>,
+        config: AudioConfig,
+        analyzer: Analyzer,
+    ) {
+        let mut engine = AudioEngine::new(
+            audio_rx,
+            event_tx,
+            OutputBackend::Device(config.device.clone()),
+        );
+        engine.analyzer = analyzer;
+        engine.replaygain = config.replaygain;
+        engine.preamp = config.preamp;
+        engine.crossfade = config.crossfade();
+        engine.event_loop();
+    }
+
+    pub fn new(
+        audio_rx: Receiver<AudioCommand>,
+        event_tx: async_channel::Sender<AudioEvent>,
+        backend: OutputBackend,
+    ) -> Self {
+        let (device_error_tx, device_error_rx) = unbounded();
 
         let mut engine = AudioEngine {
-            sink,
-            stream_handle,
+            sink: Sink::new().0,
+            output: None,
+            backend,
+            device_error_tx,
+            device_error_rx,
             player_state: PlayerState::default(),
+            history: Vec::new(),
+            rng: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0x2545_f491_4f6c_dd1d, |time| time.as_nanos() as u64)
+                | 1,
+            replaygain: ReplayGainMode::Off,
+            preamp: 0.0,
+            track_gain: 1.0,
+            crossfade: Duration::ZERO,
+            fading: None,
+            analyzer: Analyzer::new(),
             audio_rx,
             event_tx,
         };
 
-        engine.event_loop();
+        // Without an output device the engine still runs, so it can report
+        // errors and retry once a track is loaded.
+        if let Err(err) = engine.open_stream() {
+            engine.send_error(err);
+        }
+
+        engine
+    }
+
+    #[cfg(test)]
+    pub fn player_state(&self) -> &PlayerState {
+        &self.player_state
     }
 
-    fn event_loop(&mut self) {
-        let ticker = tick(Duration::from_millis(500));
+    /// Processes commands until every command sender has been dropped.
+    pub fn event_loop(&mut self) {
+        let position_ticker = tick(Duration::from_millis(250));
+        let fade_ticker = tick(Duration::from_millis(20));
+        let idle = never();
 
         loop {
+            // Position updates are only needed while playing, so a paused or
+            // stopped engine sleeps until the next command.
+            let ticker = if self.player_state.state == PlaybackState::Playing {
+                &position_ticker
+            } else {
+                &idle
+            };
+            let fade_ticker = if self.fading.is_some() {
+                &fade_ticker
+            } else {
+                &idle
+            };
+
             select! {
                 recv(self.audio_rx) -> msg => {
-                    let cmd = match msg {
-                        Ok(c) => c,
+                    match msg {
+                        Ok(cmd) => self.handle_command(cmd),
                         Err(_) => break,
-                    };
-
-                    match cmd {
-                        AudioCommand::Load(path) => self.load(PathBuf::from(path)),
-                        AudioCommand::Play => self.play(),
-                        AudioCommand::Pause => self.pause(),
-                        AudioCommand::Stop => self.stop(),
-                        AudioCommand::Volume(vol) => self.set_volume(vol),
-                        AudioCommand::Seek(pos) => self.seek(pos),
-                        AudioCommand::Meta(meta) => self.meta(meta)
                     }
                 }
 
-                recv(ticker) -> _ => {
-                    self.emit_position();
+                recv(self.device_error_rx) -> msg => {
+                    if let Ok(err) = msg {
+                        self.handle_device_error(err);
+                    }
                 }
+
+                recv(ticker) -> _ => self.tick(),
+
+                recv(fade_ticker) -> _ => self.step_fade(),
             }
         }
     }
 
+    pub fn handle_command(&mut self, cmd: AudioCommand) {
+        match cmd {
+            AudioCommand::Load(path) => self.load(PathBuf::from(path)),
+            AudioCommand::Enqueue(paths) => self.enqueue(paths),
+            AudioCommand::PlayNext(paths) => self.play_next(paths),
+            AudioCommand::ClearQueue => self.clear_queue(),
+            AudioCommand::PlayIndex(index) => self.jump_to(index),
+            AudioCommand::MoveInQueue { from, to } => self.move_in_queue(from, to),
+            AudioCommand::RemoveFromQueue(indices) => self.remove_from_queue(indices),
+            AudioCommand::ReplaceQueue { queue, index } => self.replace_queue(queue, index),
+            AudioCommand::Play => self.play(),
+            AudioCommand::Pause => self.pause(),
+            AudioCommand::Stop => self.stop(),
+            AudioCommand::Next => self.next(),
+            AudioCommand::Previous => self.previous(),
+            AudioCommand::Volume(vol) => self.set_volume(vol),
+            AudioCommand::Mute(muted) => self.set_muted(muted),
+            AudioCommand::Seek(pos) => self.seek(pos),
+            AudioCommand::SetOutputDevice(device) => self.set_output_device(device),
+            AudioCommand::ListOutputDevices => self.list_output_devices(),
+            AudioCommand::Meta(meta) => self.meta(meta),
+            AudioCommand::SetRepeat(repeat) => self.set_repeat(repeat),
+            AudioCommand::SetShuffle(shuffle) => self.set_shuffle(shuffle),
+            AudioCommand::SetReplayGain { mode, preamp } => self.set_replaygain(mode, preamp),
+            AudioCommand::SetCrossfade(crossfade) => self.crossfade = crossfade,
+            AudioCommand::Restore {
+                queue,
+                index,
+                position,
+            } => self.restore(queue, index, position),
+        }
+    }
+
+    /// Publishes the position and advances the queue when a track ends.
+    pub fn tick(&mut self) {
+        self.emit_position();
+        self.check_crossfade();
+        self.check_track_end();
+    }
+
     fn load(&mut self, path: PathBuf) {
+        self.finish_fade();
+
+        let index = match self.player_state.queue.iter().position(|p| p == &path) {
+            Some(index) => index,
+            None => {
+                self.player_state.queue.push(path);
+                self.player_state.queue.len() - 1
+            }
+        };
+
+        self.play_index(index);
+    }
+
+    fn enqueue(&mut self, paths: Vec<PathBuf>) {
+        self.player_state.queue.extend(paths);
+        self.emit_queue();
+    }
+
+    /// Inserts tracks right after the current one, or at the end when
+    /// nothing is playing.
+    fn play_next(&mut self, paths: Vec<PathBuf>) {
+        let at = match self.player_state.index {
+            Some(index) => index + 1,
+            None => self.player_state.queue.len(),
+        };
+
+        // Shuffle history refers to queue positions, which have moved.
+        let inserted = paths.len();
+        for index in &mut self.history {
+            if *index >= at {
+                *index += inserted;
+            }
+        }
+
+        self.player_state.queue.splice(at..at, paths);
+        self.emit_queue();
+    }
+
+    fn replace_queue(&mut self, queue: Vec<PathBuf>, index: usize) {
+        self.finish_fade();
+        self.history.clear();
+        self.player_state.queue = queue;
+
+        if index < self.player_state.queue.len() {
+            self.play_index(index);
+        } else {
+            self.stop();
+            self.player_state.index = None;
+            self.emit_queue();
+        }
+    }
+
+    fn jump_to(&mut self, index: usize) {
+        if index >= self.player_state.queue.len() {
+            return;
+        }
+
+        self.finish_fade();
+        if let Some(current) = self.player_state.index {
+            self.history.push(current);
+        }
+        self.play_index(index);
+    }
+
+    /// Moves a queue entry, keeping the current index and shuffle history
+    /// pointing at the same tracks.
+    fn move_in_queue(&mut self, from: usize, to: usize) {
+        let len = self.player_state.queue.len();
+        if from >= len || to >= len || from == to {
+            return;
+        }
+
+        let path = self.player_state.queue.remove(from);
+        self.player_state.queue.insert(to, path);
+
+        let remap = |index: usize| {
+            if index == from {
+                to
+            } else if from < index && index <= to {
+                index - 1
+            } else if to <= index && index < from {
+                index + 1
+            } else {
+                index
+            }
+        };
+
+        self.player_state.index = self.player_state.index.map(remap);
+        for index in &mut self.history {
+            *index = remap(*index);
+        }
+
+        self.emit_queue();
+    }
+
+    /// Removes queue entries. The current track is kept, as it's still
+    /// playing.
+    fn remove_from_queue(&mut self, mut indices: Vec<usize>) {
+        let len = self.player_state.queue.len();
+        indices.sort_unstable();
+        indices.dedup();
+        indices.retain(|ix| *ix < len && Some(*ix) != self.player_state.index);
+
+        if indices.is_empty() {
+            return;
+        }
+
+        for ix in indices.iter().rev() {
+            self.player_state.queue.remove(*ix);
+        }
+
+        let remap = |index: usize| index - indices.partition_point(|removed| *removed < index);
+
+        self.player_state.index = self.player_state.index.map(remap);
+        self.history
+            .retain(|index| indices.binary_search(index).is_err());
+        for index in &mut self.history {
+            *index = remap(*index);
+        }
+
+        self.emit_queue();
+    }
+
+    fn clear_queue(&mut self) {
+        self.stop();
+        self.player_state.queue.clear();
+        self.player_state.index = None;
+        self.player_state.current = None;
+        self.player_state.meta = None;
+        self.history.clear();
+        self.emit_queue();
+    }
+
+    fn open_stream(&mut self) -> Result<(), AudioError> {
+        let output = match &self.backend {
+            OutputBackend::Device(device) => {
+                let opened = output::open_stream(device.as_deref(), self.device_error_tx.clone())?;
+
+                if let Some(err) = opened.fallback {
+                    self.send_error(err);
+                }
+
+                Output::Device(opened.stream)
+            }
+            #[cfg(test)]
+            OutputBackend::Null(null) => Output::Null(null.clone()),
+        };
+
+        self.output = Some(output);
+        Ok(())
+    }
+
+    fn list_output_devices(&self) {
+        self.emit(AudioEvent::OutputDevices(output::output_devices()));
+    }
+
+    fn set_output_device(&mut self, device: Option<String>) {
+        match &self.backend {
+            OutputBackend::Device(_) => self.backend = OutputBackend::Device(device),
+            // A null output has no devices to pick, so it is only reopened.
+            #[cfg(test)]
+            OutputBackend::Null(_) => {}
+        }
+
+        self.reopen_output();
+    }
+
+    fn handle_device_error(&mut self, err: cpal::StreamError) {
+        self.send_error(AudioError::new(
+            AudioErrorKind::OutputDevice,
+            err.to_string(),
+        ));
+
+        if matches!(err, cpal::StreamError::DeviceNotAvailable) {
+            self.reopen_output();
+            self.list_output_devices();
+        }
+    }
+
+    /// Reopens the output, picking up a changed or vanished device, and
+    /// resumes the current track where it left off.
+    fn reopen_output(&mut self) {
+        let position = self.sink.get_pos();
+        let state = self.player_state.state;
+
+        self.finish_fade();
         self.sink.stop();
-        self.sink = Sink::connect_new(self.stream_handle.mixer());
-        self.player_state.current = Some(path.clone());
+        self.output = None;
+
+        if let Err(err) = self.open_stream() {
+            self.send_error(err);
+            self.stop();
+            return;
+        }
+
+        let Some(index) = self.player_state.index else {
+            return;
+        };
+
+        if state == PlaybackState::Stopped {
+            return;
+        }
+
+        if let Err(err) = self.open_track(index, state == PlaybackState::Playing) {
+            self.send_error(err);
+            self.stop();
+            return;
+        }
+
+        if !position.is_zero() {
+            self.seek(position);
+        }
+    }
+
+    /// Plays the track at `index`. Tracks that fail to open are skipped in
+    /// play order, shuffled or not, until one plays or none are left.
+    fn play_index(&mut self, index: usize) {
+        // Failed tracks are only history while looking for one that plays.
+        let history = self.history.len();
+        let mut next = Some(index);
+
+        for _ in 0..self.player_state.queue.len() {
+            let Some(index) = next else {
+                break;
+            };
+
+            match self.open_track(index, true) {
+                Ok(()) => {
+                    self.history.truncate(history);
+                    return;
+                }
+                Err(err) => {
+                    let output_failed = err.kind == AudioErrorKind::OutputDevice;
+                    self.send_error(err);
+
+                    if output_failed {
+                        break;
+                    }
+                }
+            }
+
+            next = if self.player_state.shuffle {
+                self.history.push(index);
+                self.next_index()
+            } else {
+                Some(index + 1).filter(|next| *next < self.player_state.queue.len())
+            };
+        }
+
+        self.history.truncate(history);
+        self.stop();
+    }
 
-        let file = File::open(path.clone()).unwrap();
-        let len = file.metadata().unwrap().len();
+    fn open_track(&mut self, index: usize, autoplay: bool) -> Result<(), AudioError> {
+        let Some(path) = self.player_state.queue.get(index).cloned() else {
+            return Ok(());
+        };
+
+        if self.output.is_none() {
+            self.open_stream()?;
+        }
+
+        let file = File::open(&path).map_err(|err| AudioError::from_io(err, path.clone()))?;
+        let len = file
+            .metadata()
+            .map_err(|err| AudioError::from_io(err, path.clone()))?
+            .len();
         let source = DecoderBuilder::new()
             .with_data(file)
             .with_byte_len(len)
             .with_seekable(true)
             .build()
-            .unwrap();
+            .map_err(|err| AudioError::from_decoder(err, path.clone()))?;
+
+        // The previous track plays on until this one is known to open.
+        self.sink.stop();
+        self.player_state.current = Some(path.clone());
+        self.player_state.index = Some(index);
+        self.player_state.meta = None;
+
+        if let Some(output) = &self.output {
+            self.sink = Sink::connect_new(output.mixer());
+        }
+        self.player_state.duration = source.total_duration().unwrap_or_default();
+        self.track_gain = replaygain::factor(&path, self.replaygain, self.preamp);
+
+        self.player_state.state = if autoplay {
+            PlaybackState::Playing
+        } else {
+            self.sink.pause();
+            PlaybackState::Paused
+        };
+        // A track fading in starts silent and is ramped up by `step_fade`.
+        self.sink.set_volume(if self.fading.is_some() {
+            0.0
+        } else {
+            self.effective_volume()
+        });
+        self.analyzer.clear();
+        self.sink.append(Tap::new(source, self.analyzer.clone()));
+        self.player_state.position = Duration::ZERO;
+
+        self.emit(AudioEvent::TrackLoaded(path));
+        self.emit_queue();
+        self.emit(AudioEvent::DurationChanged(self.player_state.duration));
+        self.emit(AudioEvent::PositionChanged(Duration::ZERO));
+        self.emit(AudioEvent::StateChanged(self.player_state.state));
+
+        Ok(())
+    }
+
+    fn next(&mut self) {
+        self.finish_fade();
+
+        match self.next_index() {
+            Some(next) => {
+                if let Some(index) = self.player_state.index {
+                    self.history.push(index);
+                }
+                self.play_index(next);
+            }
+            None => self.stop(),
+        }
+    }
+
+    fn next_index(&mut self) -> Option<usize> {
+        let len = self.player_state.queue.len();
+        if len == 0 {
+            return None;
+        }
+
+        if self.player_state.shuffle {
+            let mut unplayed: Vec<usize> = (0..len)
+                .filter(|index| {
+                    Some(*index) != self.player_state.index && !self.history.contains(index)
+                })
+                .collect();
+
+            if unplayed.is_empty() {
+                if self.player_state.repeat != RepeatMode::All {
+                    return None;
+                }
+
+                self.history.clear();
+                unplayed = (0..len)
+                    .filter(|index| len == 1 || Some(*index) != self.player_state.index)
+                    .collect();
+            }
+
+            let pick = self.random() as usize % unplayed.len();
+            return Some(unplayed[pick]);
+        }
+
+        let next = self.player_state.index.map_or(0, |index| index + 1);
+        if next < len {
+            Some(next)
+        } else if self.player_state.repeat == RepeatMode::All {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    fn previous(&mut self) {
+        self.finish_fade();
+
+        // Like most players, "previous" restarts the current track unless we
+        // are right at its beginning.
+        if self.sink.get_pos() > Duration::from_secs(3) {
+            self.seek(Duration::ZERO);
+            return;
+        }
+
+        if self.player_state.shuffle {
+            if let Some(index) = self.history.pop() {
+                self.play_index(index);
+            } else {
+                self.seek(Duration::ZERO);
+            }
+            return;
+        }
+
+        match self.player_state.index {
+            Some(index) if index > 0 => self.play_index(index - 1),
+            Some(_) => self.seek(Duration::ZERO),
+            None => {}
+        }
+    }
+
+    fn check_track_end(&mut self) {
+        if self.player_state.state == PlaybackState::Playing && self.sink.empty() {
+            self.emit(AudioEvent::TrackEnded);
+
+            match (self.player_state.repeat, self.player_state.index) {
+                (RepeatMode::One, Some(index)) => self.play_index(index),
+                _ => self.next(),
+            }
+        }
+    }
+
+    /// Starts the next track under the end of the current one once the
+    /// remaining time drops below the crossfade duration.
+    fn check_crossfade(&mut self) {
+        if self.crossfade.is_zero()
+            || self.fading.is_some()
+            || self.player_state.state != PlaybackState::Playing
+            || self.player_state.duration.is_zero()
+        {
+            return;
+        }
+
+        let remaining = self
+            .player_state
+            .duration
+            .saturating_sub(self.sink.get_pos());
+        if remaining > self.crossfade || remaining.is_zero() {
+            return;
+        }
+
+        let next = match (self.player_state.repeat, self.player_state.index) {
+            (RepeatMode::One, Some(index)) => Some(index),
+            _ => self.next_index(),
+        };
+        let Some(next) = next else {
+            return;
+        };
+
+        self.emit(AudioEvent::TrackEnded);
+
+        if let Some(index) = self.player_state.index.filter(|index| *index != next) {
+            self.history.push(index);
+        }
+
+        let sink = std::mem::replace(&mut self.sink, Sink::new().0);
+        self.fading = Some(Fade {
+            sink,
+            volume: self.effective_volume(),
+            started: Instant::now(),
+            duration: remaining,
+        });
+
+        self.play_index(next);
+    }
+
+    fn step_fade(&mut self) {
+        let Some(fade) = &self.fading else {
+            return;
+        };
 
-        self.sink.set_volume(self.player_state.volume);
-        self.sink.append(source);
+        let progress =
+            (fade.started.elapsed().as_secs_f32() / fade.duration.as_secs_f32()).min(1.0);
+        fade.sink.set_volume(fade.volume * (1.0 - progress));
+        self.sink.set_volume(self.effective_volume() * progress);
 
-        let _ = self.event_tx.send(AudioEvent::TrackLoaded(path));
+        if progress >= 1.0 {
+            self.finish_fade();
+        }
+    }
+
+    fn finish_fade(&mut self) {
+        if let Some(fade) = self.fading.take() {
+            fade.sink.stop();
+            self.sink.set_volume(self.effective_volume());
+        }
+    }
+
+    fn set_replaygain(&mut self, mode: ReplayGainMode, preamp: f32) {
+        self.replaygain = mode;
+        self.preamp = preamp;
+        self.track_gain = match &self.player_state.current {
+            Some(path) => replaygain::factor(path, mode, preamp),
+            None => 1.0,
+        };
+
+        if self.fading.is_none() {
+            self.sink.set_volume(self.effective_volume());
+        }
+    }
+
+    fn set_repeat(&mut self, repeat: RepeatMode) {
+        self.player_state.repeat = repeat;
+        self.emit_mode();
+    }
+
+    fn set_shuffle(&mut self, shuffle: bool) {
+        self.player_state.shuffle = shuffle;
+        self.history.clear();
+        self.emit_mode();
+    }
+
+    /// Puts back a saved queue, paused at `position` in the current track.
+    fn restore(&mut self, queue: Vec<PathBuf>, index: Option<usize>, position: Duration) {
+        self.sink.stop();
+        self.history.clear();
+        self.player_state.queue = queue;
+        self.player_state.index = index.filter(|index| *index < self.player_state.queue.len());
+        self.emit_queue();
+
+        let Some(index) = self.player_state.index else {
+            return;
+        };
 
-        self.player_state.state = PlaybackState::Playing;
+        if let Err(err) = self.open_track(index, false) {
+            self.send_error(err);
+            self.stop();
+            return;
+        }
 
-        let _ = self
-            .event_tx
-            .send(AudioEvent::StateChanged(self.player_state.clone()));
+        if !position.is_zero() {
+            self.seek(position);
+        }
+    }
+
+    /// xorshift64, plenty for picking shuffle order.
+    fn random(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
     }
 
     fn meta(&mut self, meta: Metadata) {
+        // Some decoders can't tell the duration up front, so fall back to the
+        // one read from the tags.
+        if self.player_state.duration.is_zero() && !meta.duration.is_zero() {
+            self.player_state.duration = meta.duration;
+            self.emit(AudioEvent::DurationChanged(meta.duration));
+        }
         self.player_state.meta = Some(meta);
-        self.send_player_state();
     }
 
     fn play(&mut self) {
-        if self.player_state.state != PlaybackState::Playing {
+        if self.player_state.state == PlaybackState::Stopped {
+            // The sink is emptied on stop, so the track has to be reloaded.
+            if !self.player_state.queue.is_empty() {
+                self.play_index(self.player_state.index.unwrap_or(0));
+            }
+        } else if self.player_state.state == PlaybackState::Paused {
             self.sink.play();
             self.player_state.state = PlaybackState::Playing;
-            let _ = self
-                .event_tx
-                .send(AudioEvent::StateChanged(self.player_state.clone()));
+            self.emit(AudioEvent::StateChanged(PlaybackState::Playing));
         }
     }
 
     fn pause(&mut self) {
         if self.player_state.state == PlaybackState::Playing {
+            self.finish_fade();
             self.sink.pause();
             self.player_state.state = PlaybackState::Paused;
-            let _ = self
-                .event_tx
-                .send(AudioEvent::StateChanged(self.player_state.clone()));
+            self.player_state.position = self.sink.get_pos();
+            self.emit(AudioEvent::PositionChanged(self.player_state.position));
+            self.emit(AudioEvent::StateChanged(PlaybackState::Paused));
         }
     }
 
     fn stop(&mut self) {
+        self.finish_fade();
         self.sink.stop();
+        self.analyzer.clear();
         self.player_state.state = PlaybackState::Stopped;
-        let _ = self
-            .event_tx
-            .send(AudioEvent::StateChanged(self.player_state.clone()));
+        self.player_state.position = Duration::ZERO;
+        self.emit(AudioEvent::PositionChanged(Duration::ZERO));
+        self.emit(AudioEvent::StateChanged(PlaybackState::Stopped));
     }
 
     fn set_volume(&mut self, volume: f32) {
         self.player_state.volume = volume.clamp(0.0, 1.0);
-        self.sink.set_volume(self.player_state.volume);
-        let _ = self
-            .event_tx
-            .send(AudioEvent::StateChanged(self.player_state.clone()));
+        self.finish_fade();
+        self.sink.set_volume(self.effective_volume());
+        self.emit_volume();
+    }
+
+    fn set_muted(&mut self, muted: bool) {
+        self.player_state.muted = muted;
+        self.finish_fade();
+        self.sink.set_volume(self.effective_volume());
+        self.emit_volume();
+    }
+
+    fn effective_volume(&self) -> f32 {
+        if self.player_state.muted {
+            0.0
+        } else {
+            self.player_state.volume * self.track_gain
+        }
+    }
+
+    fn emit(&self, event: AudioEvent) {
+        // The channel is unbounded, so this only fails once the UI is gone.
+        let _ = self.event_tx.try_send(event);
+    }
+
+    fn send_error(&self, err: AudioError) {
+        self.emit(AudioEvent::Error(err));
+    }
+
+    fn emit_volume(&self) {
+        self.emit(AudioEvent::VolumeChanged {
+            volume: self.player_state.volume,
+            muted: self.player_state.muted,
+        });
+    }
+
+    fn emit_mode(&self) {
+        self.emit(AudioEvent::ModeChanged {
+            repeat: self.player_state.repeat,
+            shuffle: self.player_state.shuffle,
+        });
     }
 
-    fn send_player_state(&mut self) {
-        let _ = self
-            .event_tx
-            .send(AudioEvent::StateChanged(self.player_state.clone()));
+    fn emit_queue(&self) {
+        self.emit(AudioEvent::QueueChanged {
+            queue: self.player_state.queue.clone(),
+            index: self.player_state.index,
+        });
     }
 
     fn emit_position(&mut self) {
         if self.player_state.state == PlaybackState::Playing {
-            self.player_state.position = self.sink.get_pos().as_secs();
-            self.send_player_state();
+            self.player_state.position = self.sink.get_pos();
+            self.emit(AudioEvent::PositionChanged(self.player_state.position));
+        }
+    }
+
+    fn seek(&mut self, pos: Duration) {
+        self.finish_fade();
+
+        if let Err(err) = self.sink.try_seek(pos) {
+            let err = AudioError::new(AudioErrorKind::Seek, err.to_string());
+            self.send_error(match self.player_state.current.clone() {
+                Some(path) => err.with_path(path),
+                None => err,
+            });
+            return;
+        }
+
+        self.player_state.position = self.sink.get_pos();
+        self.emit(AudioEvent::PositionChanged(self.player_state.position));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{
+        fixtures::{SAMPLE_RATE, write_wav},
+        null::NullOutput,
+    };
+    use std::{fs, iter};
+
+    const TRACK: Duration = Duration::from_secs(1);
+
+    /// An engine on a capturing null output, driven by hand.
+    struct Harness {
+        engine: AudioEngine,
+        output: NullOutput,
+        events: async_channel::Receiver<AudioEvent>,
+        tracks: Vec<PathBuf>,
+        _commands: Sender<AudioCommand>,
+    }
+
+    impl Harness {
+        /// Sets up an engine with `count` one second tracks to play.
+        fn new(test: &str, count: usize) -> Self {
+            let (commands, audio_rx) = unbounded();
+            let (event_tx, events) = async_channel::unbounded();
+            let output = NullOutput::capturing(1, SAMPLE_RATE);
+            let engine = AudioEngine::new(audio_rx, event_tx, OutputBackend::Null(output.clone()));
+            let tracks = (0..count)
+                .map(|index| write_wav(&format!("{test}-{index}"), TRACK))
+                .collect();
+
+            Harness {
+                engine,
+                output,
+                events,
+                tracks,
+                _commands: commands,
+            }
+        }
+
+        fn track(&self, index: usize) -> PathBuf {
+            self.tracks[index].clone()
+        }
+
+        fn send(&mut self, command: AudioCommand) {
+            self.engine.handle_command(command);
+        }
+
+        /// Plays `duration` worth of audio, then ticks the engine as its
+        /// position ticker would.
+        fn advance(&mut self, duration: Duration) {
+            self.output.advance(duration);
+            self.engine.tick();
+        }
+
+        fn events(&self) -> Vec<AudioEvent> {
+            iter::from_fn(|| self.events.try_recv().ok()).collect()
+        }
+
+        /// The events `open_track` emits for a track starting to play.
+        fn loaded(&self, index: usize) -> Vec<AudioEvent> {
+            vec![
+                AudioEvent::TrackLoaded(self.track(index)),
+                AudioEvent::QueueChanged {
+                    queue: self.tracks.clone(),
+                    index: Some(index),
+                },
+                AudioEvent::DurationChanged(TRACK),
+                AudioEvent::PositionChanged(Duration::ZERO),
+                AudioEvent::StateChanged(PlaybackState::Playing),
+            ]
+        }
+    }
+
+    impl Drop for Harness {
+        fn drop(&mut self) {
+            for track in &self.tracks {
+                let _ = fs::remove_file(track);
+            }
+        }
+    }
+
+    fn is_silent(samples: &[f32]) -> bool {
+        samples.iter().all(|sample| *sample == 0.0)
+    }
+
+    #[test]
+    fn load_plays_the_track() {
+        let mut harness = Harness::new("load", 1);
+
+        harness.send(AudioCommand::Load(harness.track(0).display().to_string()));
+        assert_eq!(harness.events(), harness.loaded(0));
+
+        harness.advance(Duration::from_millis(250));
+        let events = harness.events();
+        let [AudioEvent::PositionChanged(position)] = events.as_slice() else {
+            panic!("expected a position update, got {events:?}");
+        };
+        assert!(
+            (Duration::from_millis(200)..=Duration::from_millis(250)).contains(position),
+            "position {position:?}"
+        );
+        assert!(!is_silent(&harness.output.captured()));
+    }
+
+    #[test]
+    fn pause_holds_the_position() {
+        let mut harness = Harness::new("pause", 1);
+        harness.send(AudioCommand::Load(harness.track(0).display().to_string()));
+        harness.advance(Duration::from_millis(250));
+        harness.events();
+
+        harness.send(AudioCommand::Pause);
+        let events = harness.events();
+        let [
+            AudioEvent::PositionChanged(position),
+            AudioEvent::StateChanged(PlaybackState::Paused),
+        ] = events.as_slice()
+        else {
+            panic!("expected a pause, got {events:?}");
+        };
+        let position = *position;
+
+        // The sink checks for the pause every few milliseconds.
+        harness.advance(Duration::from_millis(10));
+        harness.output.clear_captured();
+        harness.advance(Duration::from_millis(250));
+        assert!(harness.events().is_empty());
+        assert!(is_silent(&harness.output.captured()));
+        assert_eq!(harness.engine.player_state().position, position);
+
+        harness.send(AudioCommand::Play);
+        assert_eq!(
+            harness.events(),
+            vec![AudioEvent::StateChanged(PlaybackState::Playing)]
+        );
+        harness.output.clear_captured();
+        harness.advance(Duration::from_millis(100));
+        assert!(!is_silent(&harness.output.captured()));
+    }
+
+    #[test]
+    fn seek_moves_the_position() {
+        let mut harness = Harness::new("seek", 1);
+        harness.send(AudioCommand::Load(harness.track(0).display().to_string()));
+        harness.events();
+
+        // The seek waits for the mixer to pull the track, so the clock has to
+        // keep running meanwhile.
+        harness.output.spawn_realtime();
+        harness.send(AudioCommand::Seek(Duration::from_millis(500)));
+
+        let events = harness.events();
+        let [AudioEvent::PositionChanged(position)] = events.as_slice() else {
+            panic!("expected a position update, got {events:?}");
+        };
+        assert!(
+            (Duration::from_millis(500)..Duration::from_millis(600)).contains(position),
+            "position {position:?}"
+        );
+    }
+
+    #[test]
+    fn switching_output_resumes_the_track() {
+        let mut harness = Harness::new("switch", 1);
+        harness.send(AudioCommand::Load(harness.track(0).display().to_string()));
+        harness.advance(Duration::from_millis(500));
+        harness.events();
+        let before = harness.engine.sink.get_pos();
+
+        // Resuming seeks the reopened track, which needs a running clock.
+        harness.output.spawn_realtime();
+        harness.send(AudioCommand::SetOutputDevice(Some(String::from("other"))));
+
+        let events = harness.events();
+        assert_eq!(events[..5], harness.loaded(0));
+        let [AudioEvent::PositionChanged(position)] = &events[5..] else {
+            panic!("expected the position to be restored, got {events:?}");
+        };
+        assert!(
+            (before..before + Duration::from_millis(100)).contains(position),
+            "position {position:?}, was {before:?}"
+        );
+        assert_eq!(harness.engine.player_state().state, PlaybackState::Playing);
+    }
+
+    #[test]
+    fn track_end_advances_the_queue() {
+        let mut harness = Harness::new("end", 2);
+
+        harness.send(AudioCommand::Enqueue(harness.tracks.clone()));
+        assert_eq!(
+            harness.events(),
+            vec![AudioEvent::QueueChanged {
+                queue: harness.tracks.clone(),
+                index: None,
+            }]
+        );
+
+        harness.send(AudioCommand::PlayIndex(0));
+        assert_eq!(harness.events(), harness.loaded(0));
+
+        harness.advance(TRACK + Duration::from_millis(100));
+        let events = harness.events();
+        assert!(matches!(events[0], AudioEvent::PositionChanged(_)));
+        assert_eq!(events[1], AudioEvent::TrackEnded);
+        assert_eq!(events[2..], harness.loaded(1));
+
+        // Nothing follows the last track, so playback stops.
+        harness.advance(TRACK + Duration::from_millis(100));
+        let events = harness.events();
+        assert_eq!(
+            events[1..],
+            [
+                AudioEvent::TrackEnded,
+                AudioEvent::PositionChanged(Duration::ZERO),
+                AudioEvent::StateChanged(PlaybackState::Stopped),
+            ]
+        );
+        assert_eq!(harness.engine.player_state().index, Some(1));
+    }
+
+    #[test]
+    fn failed_tracks_are_skipped() {
+        let mut harness = Harness::new("skip", 2);
+        let missing = write_wav("skip-missing", TRACK);
+        fs::remove_file(&missing).unwrap();
+        harness.tracks.insert(0, missing.clone());
+
+        harness.send(AudioCommand::ReplaceQueue {
+            queue: harness.tracks.clone(),
+            index: 0,
+        });
+        let events = harness.events();
+        assert!(
+            matches!(&events[0], AudioEvent::Error(err)
+                if err.kind == AudioErrorKind::FileNotFound && err.path == Some(missing.clone())),
+            "{events:?}"
+        );
+        assert_eq!(events[1..], harness.loaded(1));
+        assert_eq!(
+            harness.engine.player_state().current,
+            Some(harness.track(1))
+        );
+    }
+
+    #[test]
+    fn failed_tracks_are_skipped_in_shuffle_order() {
+        let mut harness = Harness::new("shuffle", 2);
+        let missing = write_wav("shuffle-missing", TRACK);
+        fs::remove_file(&missing).unwrap();
+        harness.tracks.insert(1, missing);
+        harness.send(AudioCommand::SetShuffle(true));
+
+        // Skipping the missing track in queue order would land back on the
+        // current one, whichever way the shuffle goes.
+        for seed in 1..=8 {
+            harness.send(AudioCommand::ReplaceQueue {
+                queue: harness.tracks.clone(),
+                index: 2,
+            });
+            harness.events();
+
+            harness.engine.rng = seed;
+            harness.send(AudioCommand::Next);
+            let loaded: Vec<PathBuf> = harness
+                .events()
+                .into_iter()
+                .filter_map(|event| match event {
+                    AudioEvent::TrackLoaded(path) => Some(path),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(loaded, vec![harness.track(0)], "seed {seed}");
+            assert_eq!(harness.engine.history, vec![2]);
         }
     }
 
-    fn seek(&mut self, pos: u64) {
-        self.sink.try_seek(Duration::from_secs(pos)).unwrap();
+    #[test]
+    fn queue_edits_follow_the_current_track() {
+        let mut harness = Harness::new("queue", 3);
+        let (a, b, c) = (harness.track(0), harness.track(1), harness.track(2));
+        harness.send(AudioCommand::Enqueue(harness.tracks.clone()));
+        harness.send(AudioCommand::PlayIndex(1));
+        harness.events();
+
+        harness.send(AudioCommand::MoveInQueue { from: 1, to: 0 });
+        assert_eq!(
+            harness.events(),
+            vec![AudioEvent::QueueChanged {
+                queue: vec![b.clone(), a.clone(), c.clone()],
+                index: Some(0),
+            }]
+        );
+
+        // The current track stays even when asked to be removed.
+        harness.send(AudioCommand::RemoveFromQueue(vec![0, 2]));
+        assert_eq!(
+            harness.events(),
+            vec![AudioEvent::QueueChanged {
+                queue: vec![b.clone(), a.clone()],
+                index: Some(0),
+            }]
+        );
+
+        harness.send(AudioCommand::Next);
+        let events = harness.events();
+        assert_eq!(events[0], AudioEvent::TrackLoaded(a.clone()));
+        assert_eq!(
+            events[1],
+            AudioEvent::QueueChanged {
+                queue: vec![b, a],
+                index: Some(1),
+            }
+        );
+
+        harness.send(AudioCommand::ClearQueue);
+        assert_eq!(
+            harness.events().last(),
+            Some(&AudioEvent::QueueChanged {
+                queue: Vec::new(),
+                index: None,
+            })
+        );
     }
 }
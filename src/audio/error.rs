@@ -0,0 +1,73 @@
+use std::{fmt, path::PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AudioErrorKind {
+    FileNotFound,
+    Io,
+    UnsupportedFormat,
+    Decode,
+    OutputDevice,
+    Seek,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioError {
+    pub kind: AudioErrorKind,
+    pub path: Option<PathBuf>,
+    pub message: String,
+}
+
+impl AudioError {
+    pub fn new(kind: AudioErrorKind, message: impl Into<String>) -> Self {
+        AudioError {
+            kind,
+            path: None,
+            message: message.into(),
+        }
+    }
+
+    pub fn with_path(mut self, path: PathBuf) -> Self {
+        self.path = Some(path);
+        self
+    }
+
+    pub fn from_io(err: std::io::Error, path: PathBuf) -> Self {
+        let kind = match err.kind() {
+            std::io::ErrorKind::NotFound => AudioErrorKind::FileNotFound,
+            _ => AudioErrorKind::Io,
+        };
+
+        AudioError::new(kind, err.to_string()).with_path(path)
+    }
+
+    pub fn from_decoder(err: rodio::decoder::DecoderError, path: PathBuf) -> Self {
+        let kind = match err {
+            rodio::decoder::DecoderError::UnrecognizedFormat => AudioErrorKind::UnsupportedFormat,
+            _ => AudioErrorKind::Decode,
+        };
+
+        AudioError::new(kind, err.to_string()).with_path(path)
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self.kind {
+            AudioErrorKind::FileNotFound => "File not found",
+            AudioErrorKind::Io => "Could not read file",
+            AudioErrorKind::UnsupportedFormat => "Unsupported format",
+            AudioErrorKind::Decode => "Could not decode track",
+            AudioErrorKind::OutputDevice => "Audio output unavailable",
+            AudioErrorKind::Seek => "Seek failed",
+        }
+    }
+}
+
+impl fmt::Display for AudioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}: {} ({})", self.title(), self.message, path.display()),
+            None => write!(f, "{}: {}", self.title(), self.message),
+        }
+    }
+}
+
+impl std::error::Error for AudioError {}
@@ -0,0 +1,32 @@
+use std::{f32::consts::TAU, fs, path::PathBuf, time::Duration};
+
+pub const SAMPLE_RATE: u32 = 44_100;
+
+/// Writes a mono 16-bit WAV of a 440 Hz tone.
+pub fn write_wav(name: &str, duration: Duration) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("wiremann-{}-{name}.wav", std::process::id()));
+    let frames = (duration.as_secs_f64() * SAMPLE_RATE as f64) as u32;
+    let data_len = frames * 2;
+
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // mono
+    bytes.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    bytes.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    for frame in 0..frames {
+        let t = frame as f32 / SAMPLE_RATE as f32;
+        let sample = ((TAU * 440.0 * t).sin() * i16::MAX as f32 * 0.5) as i16;
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    fs::write(&path, bytes).unwrap();
+    path
+}
@@ -1 +1,10 @@
+pub mod analyzer;
 pub mod engine;
+pub mod error;
+#[cfg(test)]
+pub mod fixtures;
+#[cfg(test)]
+pub mod null;
+pub mod output;
+pub mod replaygain;
+pub mod waveform;
@@ -0,0 +1,102 @@
+use rodio::{
+    ChannelCount, SampleRate, Source,
+    mixer::{Mixer, MixerSource},
+};
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+struct NullState {
+    source: MixerSource,
+    captured: Option<Vec<f32>>,
+}
+
+/// An output without a sound device, for running the engine in tests.
+/// Nothing plays until the clock is advanced, either manually with
+/// [`NullOutput::advance`] or in real time with [`NullOutput::spawn_realtime`].
+///
+/// Clones share the same mixer and clock, so a test can keep a handle while
+/// the engine owns another.
+///
+/// Seeking a sink blocks until its source has processed the seek, so with a
+/// manual clock the engine must run on its own thread while the caller keeps
+/// advancing.
+#[derive(Clone)]
+pub struct NullOutput {
+    mixer: Mixer,
+    state: Arc<Mutex<NullState>>,
+}
+
+impl NullOutput {
+    pub fn new(channels: ChannelCount, sample_rate: SampleRate) -> Self {
+        let (mixer, source) = rodio::mixer::mixer(channels, sample_rate);
+
+        NullOutput {
+            mixer,
+            state: Arc::new(Mutex::new(NullState {
+                source,
+                captured: None,
+            })),
+        }
+    }
+
+    /// Like [`NullOutput::new`], but every sample pulled is kept and can be
+    /// read back with [`NullOutput::captured`].
+    pub fn capturing(channels: ChannelCount, sample_rate: SampleRate) -> Self {
+        let output = Self::new(channels, sample_rate);
+        output.state.lock().unwrap().captured = Some(Vec::new());
+        output
+    }
+
+    pub fn mixer(&self) -> &Mixer {
+        &self.mixer
+    }
+
+    /// Pulls `duration` worth of samples through the mixer, as a sound
+    /// device would.
+    pub fn advance(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap();
+        let samples_per_sec = state.source.sample_rate() as f64 * state.source.channels() as f64;
+        let count = (duration.as_secs_f64() * samples_per_sec).round() as usize;
+
+        let NullState { source, captured } = &mut *state;
+        for _ in 0..count {
+            let sample = source.next().unwrap_or(0.0);
+            if let Some(captured) = captured {
+                captured.push(sample);
+            }
+        }
+    }
+
+    /// Samples pulled so far, if this output is capturing.
+    pub fn captured(&self) -> Vec<f32> {
+        self.state
+            .lock()
+            .unwrap()
+            .captured
+            .clone()
+            .unwrap_or_default()
+    }
+
+    pub fn clear_captured(&self) {
+        if let Some(captured) = &mut self.state.lock().unwrap().captured {
+            captured.clear();
+        }
+    }
+
+    /// Drives the clock from a background thread at roughly real time,
+    /// until every other handle to this output has been dropped.
+    pub fn spawn_realtime(&self) {
+        let output = self.clone();
+        let interval = Duration::from_millis(10);
+
+        thread::spawn(move || {
+            while Arc::strong_count(&output.state) > 1 {
+                thread::sleep(interval);
+                output.advance(interval);
+            }
+        });
+    }
+}
@@ -0,0 +1,104 @@
+use super::error::{AudioError, AudioErrorKind};
+#[cfg(test)]
+use super::null::NullOutput;
+use crossbeam_channel::Sender;
+use rodio::{
+    OutputStream, OutputStreamBuilder, StreamError,
+    cpal::{
+        self,
+        traits::{DeviceTrait, HostTrait},
+    },
+    mixer::Mixer,
+};
+
+/// Where the engine sends its audio.
+#[derive(Clone)]
+pub enum OutputBackend {
+    /// A sound device by name, or the system default for `None`.
+    Device(Option<String>),
+    /// No sound device; samples are pulled by a [`NullOutput`].
+    #[cfg(test)]
+    Null(NullOutput),
+}
+
+/// An opened output the engine's sinks connect to.
+pub enum Output {
+    Device(OutputStream),
+    #[cfg(test)]
+    Null(NullOutput),
+}
+
+impl Output {
+    pub fn mixer(&self) -> &Mixer {
+        match self {
+            Output::Device(stream) => stream.mixer(),
+            #[cfg(test)]
+            Output::Null(null) => null.mixer(),
+        }
+    }
+}
+
+/// Names of the output devices of the default host.
+pub fn output_devices() -> Vec<String> {
+    cpal::default_host()
+        .output_devices()
+        .map(|devices| devices.filter_map(|device| device.name().ok()).collect())
+        .unwrap_or_default()
+}
+
+fn find_device(name: &str) -> Option<cpal::Device> {
+    cpal::default_host()
+        .output_devices()
+        .ok()?
+        .find(|device| device.name().is_ok_and(|n| n == name))
+}
+
+pub struct OpenedStream {
+    pub stream: OutputStream,
+    /// Set when the requested device could not be used and the default
+    /// device was opened instead.
+    pub fallback: Option<AudioError>,
+}
+
+/// Opens `device`, or the default device when `device` is `None` or cannot
+/// be opened. Stream errors, such as the device being unplugged, are
+/// forwarded to `error_tx`.
+pub fn open_stream(
+    device: Option<&str>,
+    error_tx: Sender<cpal::StreamError>,
+) -> Result<OpenedStream, AudioError> {
+    let callback = move |err| {
+        let _ = error_tx.send(err);
+    };
+
+    let mut fallback = None;
+
+    if let Some(name) = device {
+        let opened = match find_device(name) {
+            Some(device) => OutputStreamBuilder::from_device(device)
+                .and_then(|builder| builder.with_error_callback(callback.clone()).open_stream()),
+            None => Err(StreamError::NoDevice),
+        };
+
+        match opened {
+            Ok(stream) => {
+                return Ok(OpenedStream {
+                    stream,
+                    fallback: None,
+                });
+            }
+            Err(err) => {
+                fallback = Some(AudioError::new(
+                    AudioErrorKind::OutputDevice,
+                    format!("\"{name}\" is unavailable ({err}), using the default device"),
+                ));
+            }
+        }
+    }
+
+    let stream = OutputStreamBuilder::from_default_device()
+        .and_then(|builder| builder.with_error_callback(callback).open_stream())
+        .map_err(|err| AudioError::new(AudioErrorKind::OutputDevice, err.to_string()))?;
+
+    Ok(OpenedStream { stream, fallback })
+}
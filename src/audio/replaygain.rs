@@ -0,0 +1,66 @@
+use crate::config::ReplayGainMode;
+use lofty::{prelude::*, probe::Probe};
+use std::path::Path;
+
+/// Linear volume factor for `path` under `mode`, including `preamp` (dB).
+/// Tracks without ReplayGain tags only get the preamp. The result is capped
+/// by the stored peak so the gain never pushes the track into clipping.
+pub fn factor(path: &Path, mode: ReplayGainMode, preamp: f32) -> f32 {
+    if mode == ReplayGainMode::Off {
+        return 1.0;
+    }
+
+    let (gain, peak) = read_tags(path, mode).unwrap_or((None, None));
+    let factor = db_to_factor(gain.unwrap_or(0.0) + preamp);
+
+    match peak {
+        Some(peak) if peak > 0.0 => factor.min(1.0 / peak),
+        _ => factor,
+    }
+}
+
+fn read_tags(path: &Path, mode: ReplayGainMode) -> Option<(Option<f32>, Option<f32>)> {
+    let tagged_file = Probe::open(path)
+        .ok()?
+        .guess_file_type()
+        .ok()?
+        .read()
+        .ok()?;
+    let tag = tagged_file
+        .primary_tag()
+        .or_else(|| tagged_file.first_tag())?;
+
+    let (gain_key, peak_key, fallback_gain_key, fallback_peak_key) = match mode {
+        ReplayGainMode::Album => (
+            ItemKey::ReplayGainAlbumGain,
+            ItemKey::ReplayGainAlbumPeak,
+            ItemKey::ReplayGainTrackGain,
+            ItemKey::ReplayGainTrackPeak,
+        ),
+        _ => (
+            ItemKey::ReplayGainTrackGain,
+            ItemKey::ReplayGainTrackPeak,
+            ItemKey::ReplayGainAlbumGain,
+            ItemKey::ReplayGainAlbumPeak,
+        ),
+    };
+
+    let parse = |key: &ItemKey| tag.get_string(key).and_then(parse_number);
+    let gain = parse(&gain_key).or_else(|| parse(&fallback_gain_key));
+    let peak = parse(&peak_key).or_else(|| parse(&fallback_peak_key));
+
+    Some((gain, peak))
+}
+
+/// Parses values like `-6.54 dB` or `0.988547`.
+fn parse_number(value: &str) -> Option<f32> {
+    value
+        .trim()
+        .trim_end_matches(|c: char| c.is_alphabetic() || c.is_whitespace())
+        .parse()
+        .ok()
+}
+
+fn db_to_factor(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
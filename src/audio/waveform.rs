@@ -0,0 +1,360 @@
+use crate::fs_util::write_atomic;
+use anyhow::{Context, Result, bail};
+use rodio::{Source, decoder::DecoderBuilder};
+use std::{
+    fs::{self, File},
+    io::Read,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+    time::UNIX_EPOCH,
+};
+
+/// Columns in a waveform, whatever the track's length.
+pub const RESOLUTION: usize = 1000;
+
+/// Frames summarized together while decoding, before they're spread over
+/// the columns.
+const BLOCK: usize = 1024;
+
+const MAGIC: &[u8; 4] = b"WMWF";
+const VERSION: u8 = 1;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Overview of a track's loudness, from 0 to 1 per column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Waveform {
+    pub peaks: Vec<f32>,
+    pub rms: Vec<f32>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Block {
+    peak: f32,
+    sum_squares: f32,
+    samples: usize,
+}
+
+impl Waveform {
+    /// Reads the waveform from the cache, or decodes the track and caches
+    /// it. Failing to write the cache doesn't fail the load, those errors
+    /// come back along with the waveform.
+    ///
+    /// The cache is looked up by the file's path, size and modification
+    /// time first, and only when that misses by a hash of its contents,
+    /// which means reading the whole file.
+    ///
+    /// Setting `cancel` stops hashing or decoding early with an error, for
+    /// when the track is no longer needed.
+    pub fn load(path: &Path, cancel: &AtomicBool) -> Result<(Self, Vec<anyhow::Error>)> {
+        let cache_dir = dirs::cache_dir().map(|dir| dir.join("wiremann").join("waveforms"));
+        Self::load_cached(path, cache_dir.as_deref(), cancel)
+    }
+
+    fn load_cached(
+        path: &Path,
+        cache_dir: Option<&Path>,
+        cancel: &AtomicBool,
+    ) -> Result<(Self, Vec<anyhow::Error>)> {
+        let cache_path = |name: String| cache_dir.map(|dir| dir.join(name));
+
+        let key = file_key(path).and_then(|key| cache_path(format!("{key:016x}.key")));
+        if let Some(waveform) = key
+            .as_deref()
+            .and_then(read_key)
+            .and_then(|hash| cache_path(format!("{hash:016x}")))
+            .and_then(|cache| Self::read_cache(&cache))
+        {
+            return Ok((waveform, Vec::new()));
+        }
+
+        let mut errors = Vec::new();
+        let hash = hash_file(path, cancel)?;
+        let cache = cache_path(format!("{hash:016x}"));
+
+        let waveform = match cache.as_deref().and_then(Self::read_cache) {
+            Some(waveform) => waveform,
+            None => {
+                let waveform = Self::generate(path, cancel)?;
+                if let Some(cache) = &cache
+                    && let Err(err) = write_atomic(cache, waveform.to_bytes())
+                {
+                    errors.push(err);
+                }
+                waveform
+            }
+        };
+
+        if let Some(key) = key
+            && let Err(err) = write_atomic(&key, hash.to_le_bytes())
+        {
+            errors.push(err);
+        }
+
+        Ok((waveform, errors))
+    }
+
+    /// Decodes the whole track. Decoding stops quietly at the first broken
+    /// frame, like playback would, and with an error once `cancel` is set.
+    pub fn generate(path: &Path, cancel: &AtomicBool) -> Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("could not open {}", path.display()))?;
+        let len = file.metadata()?.len();
+        let decoder = DecoderBuilder::new()
+            .with_data(file)
+            .with_byte_len(len)
+            .build()
+            .with_context(|| format!("could not decode {}", path.display()))?;
+
+        let channels = (decoder.channels() as usize).max(1);
+        let per_block = BLOCK * channels;
+        let mut blocks = Vec::new();
+        let mut block = Block::default();
+
+        for sample in decoder {
+            block.peak = block.peak.max(sample.abs());
+            block.sum_squares += sample * sample;
+            block.samples += 1;
+
+            if block.samples == per_block {
+                blocks.push(std::mem::take(&mut block));
+
+                if cancel.load(Ordering::Relaxed) {
+                    bail!("decoding {} was cancelled", path.display());
+                }
+            }
+        }
+        if block.samples > 0 {
+            blocks.push(block);
+        }
+
+        if blocks.is_empty() {
+            bail!("{} has no audio", path.display());
+        }
+
+        Ok(Self::from_blocks(&blocks))
+    }
+
+    /// Spreads the blocks evenly over [`RESOLUTION`] columns.
+    fn from_blocks(blocks: &[Block]) -> Self {
+        let mut peaks = Vec::with_capacity(RESOLUTION);
+        let mut rms = Vec::with_capacity(RESOLUTION);
+
+        for column in 0..RESOLUTION {
+            let start = column * blocks.len() / RESOLUTION;
+            let end = ((column + 1) * blocks.len() / RESOLUTION).max(start + 1);
+            let blocks = &blocks[start.min(blocks.len() - 1)..end.min(blocks.len())];
+
+            let samples: usize = blocks.iter().map(|block| block.samples).sum();
+            let sum_squares: f32 = blocks.iter().map(|block| block.sum_squares).sum();
+
+            peaks.push(
+                blocks
+                    .iter()
+                    .fold(0.0f32, |peak, block| peak.max(block.peak)),
+            );
+            rms.push((sum_squares / samples.max(1) as f32).sqrt());
+        }
+
+        Waveform { peaks, rms }
+    }
+
+    /// `None` for a missing, outdated or damaged cache entry, which is then
+    /// regenerated.
+    fn read_cache(path: &Path) -> Option<Self> {
+        let bytes = fs::read(path).ok()?;
+        let body = bytes.strip_prefix(MAGIC)?.strip_prefix(&[VERSION])?;
+        if body.len() != RESOLUTION * 2 {
+            return None;
+        }
+
+        let level = |byte: u8| byte as f32 / 255.0;
+        Some(Waveform {
+            peaks: body.iter().step_by(2).copied().map(level).collect(),
+            rms: body.iter().skip(1).step_by(2).copied().map(level).collect(),
+        })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let byte = |level: f32| (level.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let mut bytes = Vec::with_capacity(MAGIC.len() + 1 + RESOLUTION * 2);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        for (peak, rms) in self.peaks.iter().zip(&self.rms) {
+            bytes.extend([byte(*peak), byte(*rms)]);
+        }
+        bytes
+    }
+}
+
+/// Content hash a key entry points to.
+fn read_key(path: &Path) -> Option<u64> {
+    let bytes = fs::read(path).ok()?;
+    Some(u64::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// FNV-1a over the file's path, size and modification time. Cheap to work
+/// out, and changes whenever the file is edited or replaced. `None` where
+/// modification times aren't available.
+fn file_key(path: &Path) -> Option<u64> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+    let hash = fnv(FNV_OFFSET, path.as_os_str().as_encoded_bytes());
+    let hash = fnv(hash, &metadata.len().to_le_bytes());
+    Some(fnv(hash, &modified.as_nanos().to_le_bytes()))
+}
+
+/// FNV-1a over the file's contents, so a cached waveform follows the audio
+/// rather than the path, and is redone if the file changes.
+fn hash_file(path: &Path, cancel: &AtomicBool) -> Result<u64> {
+    let mut file =
+        File::open(path).with_context(|| format!("could not open {}", path.display()))?;
+    let mut buffer = vec![0; 64 * 1024];
+    let mut hash = FNV_OFFSET;
+
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .with_context(|| format!("could not read {}", path.display()))?;
+        if read == 0 {
+            return Ok(hash);
+        }
+        if cancel.load(Ordering::Relaxed) {
+            bail!("reading {} was cancelled", path.display());
+        }
+
+        hash = fnv(hash, &buffer[..read]);
+    }
+}
+
+fn fnv(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::fixtures::write_wav;
+    use std::{path::PathBuf, time::Duration};
+
+    fn block(peak: f32, samples: usize) -> Block {
+        Block {
+            peak,
+            sum_squares: peak * peak * samples as f32,
+            samples,
+        }
+    }
+
+    fn flat(level: f32) -> Waveform {
+        Waveform {
+            peaks: vec![level; RESOLUTION],
+            rms: vec![level; RESOLUTION],
+        }
+    }
+
+    fn cache_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wiremann-{}-{test}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn blocks_spread_over_the_columns() {
+        // Fewer blocks than columns: each block covers a run of columns.
+        let waveform = Waveform::from_blocks(&[block(0.5, 4), block(1.0, 4)]);
+        assert_eq!(waveform.peaks.len(), RESOLUTION);
+        assert!(waveform.peaks[..RESOLUTION / 2].iter().all(|p| *p == 0.5));
+        assert!(waveform.peaks[RESOLUTION / 2..].iter().all(|p| *p == 1.0));
+        assert_eq!(waveform.rms[0], 0.5);
+
+        // More blocks than columns: each column keeps the loudest peak and
+        // the level over all of its blocks.
+        let blocks: Vec<Block> = (0..RESOLUTION * 2)
+            .map(|ix| block(if ix % 2 == 0 { 0.2 } else { 0.8 }, 4))
+            .collect();
+        let waveform = Waveform::from_blocks(&blocks);
+        let rms = ((0.2f32 * 0.2 + 0.8 * 0.8) / 2.0).sqrt();
+        assert!(waveform.peaks.iter().all(|p| *p == 0.8));
+        assert!(waveform.rms.iter().all(|r| (r - rms).abs() < 1e-6));
+    }
+
+    #[test]
+    fn cache_entries_round_trip() {
+        let dir = cache_dir("waveform-entries");
+        let entry = dir.join("entry");
+
+        write_atomic(&entry, flat(1.0).to_bytes()).unwrap();
+        assert_eq!(Waveform::read_cache(&entry), Some(flat(1.0)));
+
+        write_atomic(&entry, &flat(1.0).to_bytes()[..100]).unwrap();
+        assert_eq!(Waveform::read_cache(&entry), None);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cached_waveforms_are_found_by_key_then_by_content() {
+        let dir = cache_dir("waveform-lookup");
+        let track = dir.join("track.flac");
+        fs::write(&track, "not audio").unwrap();
+        let running = AtomicBool::new(false);
+
+        let hash = hash_file(&track, &running).unwrap();
+        write_atomic(&dir.join(format!("{hash:016x}")), flat(1.0).to_bytes()).unwrap();
+
+        // Without a key the contents are hashed, and the key written.
+        let (loaded, errors) = Waveform::load_cached(&track, Some(&dir), &running).unwrap();
+        assert_eq!(loaded, flat(1.0));
+        assert!(errors.is_empty());
+        let key = dir.join(format!("{:016x}.key", file_key(&track).unwrap()));
+        assert_eq!(read_key(&key), Some(hash));
+
+        // A key leads straight to its entry: hashing would fail, cancelled.
+        write_atomic(&dir.join(format!("{:016x}", 1)), flat(0.0).to_bytes()).unwrap();
+        write_atomic(&key, 1u64.to_le_bytes()).unwrap();
+        let (loaded, _) =
+            Waveform::load_cached(&track, Some(&dir), &AtomicBool::new(true)).unwrap();
+        assert_eq!(loaded, flat(0.0));
+
+        // A key to a missing entry falls back to the content hash.
+        write_atomic(&key, 2u64.to_le_bytes()).unwrap();
+        let (loaded, _) = Waveform::load_cached(&track, Some(&dir), &running).unwrap();
+        assert_eq!(loaded, flat(1.0));
+        assert_eq!(read_key(&key), Some(hash));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn tracks_are_decoded_and_cached() {
+        let dir = cache_dir("waveform-decode");
+        let track = write_wav("waveform-decode", Duration::from_secs(1));
+        let running = AtomicBool::new(false);
+
+        let (waveform, errors) = Waveform::load_cached(&track, Some(&dir), &running).unwrap();
+        assert!(errors.is_empty());
+        // A steady tone at half scale.
+        assert!(waveform.peaks.iter().all(|peak| (peak - 0.5).abs() < 0.01));
+
+        let hash = hash_file(&track, &running).unwrap();
+        assert!(
+            Waveform::read_cache(&dir.join(format!("{hash:016x}"))).is_some(),
+            "the decoded waveform is cached"
+        );
+        fs::remove_file(track).unwrap();
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cancelled_decodes_stop() {
+        let track = write_wav("waveform-cancel", Duration::from_secs(1));
+
+        assert!(Waveform::generate(&track, &AtomicBool::new(true)).is_err());
+        assert!(Waveform::generate(&track, &AtomicBool::new(false)).is_ok());
+        fs::remove_file(track).unwrap();
+    }
+}
@@ -0,0 +1,507 @@
+use crate::fs_util;
+use anyhow::{Context, Result, bail};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub library: LibraryConfig,
+    pub audio: AudioConfig,
+    pub ui: UiConfig,
+    pub integrations: IntegrationsConfig,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LibraryConfig {
+    /// Folders scanned for music.
+    pub roots: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AudioConfig {
+    /// Output device name, or `None` for the system default.
+    pub device: Option<String>,
+    pub replaygain: ReplayGainMode,
+    /// Extra gain in dB applied on top of ReplayGain.
+    pub preamp: f32,
+    /// Crossfade between tracks in seconds, `0` to disable.
+    pub crossfade: f32,
+    /// What dropping files onto the window does.
+    pub drop_action: DropAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DropAction {
+    /// Add the tracks to the end of the queue.
+    #[default]
+    Enqueue,
+    /// Replace the queue with the tracks and start playing.
+    Play,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplayGainMode {
+    #[default]
+    Off,
+    Track,
+    Album,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UiConfig {
+    /// Theme name, or `"system"` to follow the system light/dark preference.
+    pub theme: String,
+    /// Theme used for a light system appearance when following the system.
+    pub light_theme: String,
+    /// Theme used for a dark system appearance when following the system.
+    pub dark_theme: String,
+    /// Font family, or `None` for the system UI font.
+    pub font: Option<String>,
+    /// Base font size, or `None` for the theme's own.
+    pub font_size: Option<f32>,
+    /// Take the accent color from the current track's cover art.
+    pub dynamic_accent: bool,
+    /// What the visualizer beside the current track shows.
+    pub visualizer: VisualizerMode,
+    /// Keep the mini player above other windows.
+    pub mini_player_on_top: bool,
+    pub layout: LayoutConfig,
+    /// Track table columns, in display order.
+    pub columns: Vec<TrackColumn>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutConfig {
+    pub navbar: bool,
+    pub controlbar: ControlBarPosition,
+    /// Show the queue panel beside the page.
+    pub queue: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VisualizerMode {
+    Off,
+    /// Levels of frequency bands.
+    #[default]
+    Spectrum,
+    /// The waveform as it plays.
+    Oscilloscope,
+    /// Left against right channel, showing the stereo image.
+    Vectorscope,
+}
+
+impl VisualizerMode {
+    pub fn next(self) -> Self {
+        match self {
+            VisualizerMode::Off => VisualizerMode::Spectrum,
+            VisualizerMode::Spectrum => VisualizerMode::Oscilloscope,
+            VisualizerMode::Oscilloscope => VisualizerMode::Vectorscope,
+            VisualizerMode::Vectorscope => VisualizerMode::Off,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ControlBarPosition {
+    Top,
+    #[default]
+    Bottom,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrackColumn {
+    pub column: Column,
+    /// Width in pixels.
+    pub width: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Column {
+    Title,
+    Artist,
+    Album,
+    Duration,
+    Year,
+    Plays,
+    Rating,
+    Bitrate,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IntegrationsConfig {
+    pub notifications: bool,
+    /// Previous and next buttons on notifications.
+    pub notification_actions: bool,
+    /// Skip notifications while one of the app's windows has focus.
+    pub quiet_when_focused: bool,
+    pub tray: bool,
+    pub close_to_tray: bool,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        AudioConfig {
+            device: None,
+            replaygain: ReplayGainMode::Off,
+            preamp: 0.0,
+            crossfade: 0.0,
+            drop_action: DropAction::Enqueue,
+        }
+    }
+}
+
+impl AudioConfig {
+    pub fn crossfade(&self) -> Duration {
+        Duration::from_secs_f32(self.crossfade.max(0.0))
+    }
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        UiConfig {
+            theme: String::from("system"),
+            light_theme: String::from("light"),
+            dark_theme: String::from("dark"),
+            font: None,
+            font_size: None,
+            dynamic_accent: false,
+            visualizer: VisualizerMode::Spectrum,
+            mini_player_on_top: true,
+            layout: LayoutConfig::default(),
+            columns: TrackColumn::defaults(),
+        }
+    }
+}
+
+impl TrackColumn {
+    pub const MIN_WIDTH: f32 = 40.0;
+    pub const MAX_WIDTH: f32 = 1000.0;
+
+    pub fn defaults() -> Vec<TrackColumn> {
+        [
+            (Column::Title, 280.0),
+            (Column::Artist, 200.0),
+            (Column::Album, 200.0),
+            (Column::Year, 64.0),
+            (Column::Duration, 80.0),
+            (Column::Plays, 64.0),
+            (Column::Rating, 96.0),
+            (Column::Bitrate, 96.0),
+        ]
+        .into_iter()
+        .map(|(column, width)| TrackColumn { column, width })
+        .collect()
+    }
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        LayoutConfig {
+            navbar: true,
+            controlbar: ControlBarPosition::Bottom,
+            queue: false,
+        }
+    }
+}
+
+impl Default for IntegrationsConfig {
+    fn default() -> Self {
+        IntegrationsConfig {
+            notifications: true,
+            notification_actions: true,
+            quiet_when_focused: true,
+            tray: false,
+            close_to_tray: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn dir() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("wiremann"))
+    }
+
+    pub fn path() -> Option<PathBuf> {
+        Self::dir().map(|dir| dir.join("config.toml"))
+    }
+
+    /// Loads the config file, falling back to defaults when it doesn't exist.
+    pub fn load() -> Result<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Config::default());
+        };
+
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+
+        Self::load_from(&path)
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("could not read {}", path.display()))?;
+        let config: Config = toml::from_str(&contents)
+            .with_context(|| format!("invalid config in {}", path.display()))?;
+
+        config
+            .validate()
+            .with_context(|| format!("invalid config in {}", path.display()))?;
+
+        Ok(config)
+    }
+
+    /// Checks values the type system can't, reporting every problem at once.
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+
+        if !(0.0..=12.0).contains(&self.audio.crossfade) {
+            problems.push(format!(
+                "audio.crossfade must be between 0 and 12 seconds, got {}",
+                self.audio.crossfade
+            ));
+        }
+
+        if !(-15.0..=15.0).contains(&self.audio.preamp) {
+            problems.push(format!(
+                "audio.preamp must be between -15 and 15 dB, got {}",
+                self.audio.preamp
+            ));
+        }
+
+        if self.audio.device.as_deref().is_some_and(str::is_empty) {
+            problems.push(String::from(
+                "audio.device must not be empty, remove it to use the default device",
+            ));
+        }
+
+        for (key, theme) in [
+            ("ui.theme", &self.ui.theme),
+            ("ui.light_theme", &self.ui.light_theme),
+            ("ui.dark_theme", &self.ui.dark_theme),
+        ] {
+            if theme.trim().is_empty() {
+                problems.push(format!("{key} must not be empty"));
+            }
+        }
+
+        if let Some(font_size) = self.ui.font_size
+            && !(8.0..=32.0).contains(&font_size)
+        {
+            problems.push(format!(
+                "ui.font_size must be between 8 and 32, got {font_size}"
+            ));
+        }
+
+        if self.ui.columns.is_empty() {
+            problems.push(String::from("ui.columns must list at least one column"));
+        }
+
+        for (ix, column) in self.ui.columns.iter().enumerate() {
+            if self.ui.columns[..ix]
+                .iter()
+                .any(|other| other.column == column.column)
+            {
+                problems.push(format!(
+                    "ui.columns lists {:?} more than once",
+                    column.column
+                ));
+            }
+
+            if !(TrackColumn::MIN_WIDTH..=TrackColumn::MAX_WIDTH).contains(&column.width) {
+                problems.push(format!(
+                    "ui.columns widths must be between {} and {}, got {}",
+                    TrackColumn::MIN_WIDTH,
+                    TrackColumn::MAX_WIDTH,
+                    column.width
+                ));
+            }
+        }
+
+        for root in &self.library.roots {
+            if !root.is_absolute() {
+                problems.push(format!(
+                    "library.roots entries must be absolute paths, got \"{}\"",
+                    root.display()
+                ));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            bail!("{}", problems.join("\n"))
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path().context("could not determine the config directory")?;
+        fs_util::write_atomic(&path, toml::to_string_pretty(self)?)
+    }
+
+    /// Applies `f` to the global config and writes it to disk. Changes that
+    /// fail validation are dropped; ones that fail to save stay in effect
+    /// until the app quits.
+    pub fn update(cx: &mut gpui::App, f: impl FnOnce(&mut Config)) -> Result<()> {
+        Self::apply(cx, f)?;
+        cx.global::<Config>().save()
+    }
+
+    /// Applies `f` to the global config without writing it to disk, for
+    /// changes made many times a second. Changes that fail validation are
+    /// dropped.
+    pub fn apply(cx: &mut gpui::App, f: impl FnOnce(&mut Config)) -> Result<()> {
+        let mut config = cx.global::<Config>().clone();
+        f(&mut config);
+        config.validate()?;
+
+        cx.set_global(config);
+        Ok(())
+    }
+
+    /// Watches the config file and sends the reloaded config, or the reason
+    /// it couldn't be loaded, whenever it changes on disk. The returned
+    /// watcher stops watching when dropped.
+    pub fn watch(tx: async_channel::Sender<Result<Config>>) -> Result<RecommendedWatcher> {
+        let dir = Self::dir().context("could not determine the config directory")?;
+        let path = dir.join("config.toml");
+
+        watch_dir(
+            &dir,
+            {
+                let path = path.clone();
+                move |changed| changed == path
+            },
+            move || {
+                let config = if path.exists() {
+                    Config::load_from(&path)
+                } else {
+                    Ok(Config::default())
+                };
+                let _ = tx.try_send(config);
+            },
+        )
+    }
+}
+
+/// Calls `on_change` whenever a file in `dir` accepted by `matches` is
+/// written, created or removed, creating `dir` first if needed. The returned
+/// watcher stops watching when dropped.
+pub fn watch_dir(
+    dir: &Path,
+    matches: impl Fn(&Path) -> bool + Send + 'static,
+    on_change: impl Fn() + Send + 'static,
+) -> Result<RecommendedWatcher> {
+    fs::create_dir_all(dir)?;
+
+    // Editors often save by writing a temporary file and renaming it over
+    // the original, so watch the directory rather than the file itself.
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else {
+            return;
+        };
+
+        if event.kind.is_access() || !event.paths.iter().any(|p| matches(p)) {
+            return;
+        }
+
+        on_change();
+    })?;
+
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    Ok(watcher)
+}
+
+impl gpui::Global for Config {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(toml: &str) -> Result<Config> {
+        let config: Config = toml::from_str(toml)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    #[test]
+    fn default_config_round_trips() {
+        let path = std::env::temp_dir()
+            .join(format!("wiremann-{}-config", std::process::id()))
+            .join("config.toml");
+        fs_util::write_atomic(&path, toml::to_string_pretty(&Config::default()).unwrap()).unwrap();
+
+        assert_eq!(Config::load_from(&path).unwrap(), Config::default());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn missing_keys_take_their_defaults() {
+        let config = parse("[audio]\ncrossfade = 3.5\n").unwrap();
+
+        assert_eq!(config.audio.crossfade(), Duration::from_millis(3500));
+        assert_eq!(
+            Config {
+                audio: AudioConfig::default(),
+                ..config
+            },
+            Config::default()
+        );
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let err = parse("[audio]\ncrosfade = 2.0\n").unwrap_err();
+        assert!(
+            format!("{err:#}").contains("unknown field `crosfade`"),
+            "{err:#}"
+        );
+
+        let err = parse("[equalizer]\nbass = 1\n").unwrap_err();
+        assert!(
+            format!("{err:#}").contains("unknown field `equalizer`"),
+            "{err:#}"
+        );
+    }
+
+    #[test]
+    fn out_of_range_values_are_all_reported() {
+        let err = parse("[audio]\ncrossfade = 20.0\npreamp = -30.0\n").unwrap_err();
+        let message = format!("{err:#}");
+
+        assert!(
+            message.contains("audio.crossfade must be between 0 and 12 seconds, got 20"),
+            "{message}"
+        );
+        assert!(
+            message.contains("audio.preamp must be between -15 and 15 dB, got -30"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn range_limits_are_inclusive() {
+        parse("[audio]\ncrossfade = 0.0\npreamp = -15.0\n").unwrap();
+        parse("[audio]\ncrossfade = 12.0\npreamp = 15.0\n").unwrap();
+
+        assert!(parse("[audio]\ncrossfade = -0.5\n").is_err());
+        assert!(parse("[audio]\npreamp = 15.5\n").is_err());
+    }
+}
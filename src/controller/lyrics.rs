@@ -0,0 +1,523 @@
+use crate::fs_util;
+use anyhow::{Result, bail};
+use gpui::{App, Global};
+use lofty::{
+    TextEncoding,
+    config::{ParseOptions, WriteOptions},
+    file::{AudioFile, FileType},
+    id3::v2::{
+        BinaryFrame, Frame, FrameId, Id3v2Tag, SyncTextContentType, SynchronizedTextFrame,
+        TimestampFormat, UnsynchronizedTextFrame,
+    },
+    mpeg::MpegFile,
+    prelude::*,
+    probe::Probe,
+    tag::Tag,
+};
+use std::{
+    borrow::Cow,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LyricLine {
+    /// When the line starts, `None` for unsynchronized lyrics.
+    pub time: Option<Duration>,
+    pub text: String,
+    /// Word timings from enhanced LRC, empty when the line isn't timed by
+    /// word.
+    pub words: Vec<(Duration, String)>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Lyrics {
+    pub lines: Vec<LyricLine>,
+}
+
+impl Lyrics {
+    /// Finds lyrics for a track, preferring synchronized ones: a sidecar
+    /// `.lrc` file, then an ID3v2 SYLT frame, then the lyrics tag, which may
+    /// itself hold LRC.
+    pub fn read(path: &Path) -> Option<Self> {
+        if let Ok(contents) = fs::read_to_string(Self::sidecar(path))
+            && let Some(lyrics) = Self::parse_lrc(&contents)
+        {
+            return Some(lyrics);
+        }
+
+        if let Some(lyrics) = Self::read_sylt(path) {
+            return Some(lyrics);
+        }
+
+        let tagged_file = Probe::open(path)
+            .ok()?
+            .guess_file_type()
+            .ok()?
+            .read()
+            .ok()?;
+        let text = tagged_file
+            .primary_tag()
+            .or_else(|| tagged_file.first_tag())?
+            .get_string(&ItemKey::Lyrics)?;
+
+        Self::parse_lrc(text).or_else(|| Self::plain(text))
+    }
+
+    /// Where the LRC file of a track lives: next to it, with the same name.
+    pub fn sidecar(path: &Path) -> PathBuf {
+        path.with_extension("lrc")
+    }
+
+    /// Parses LRC, including several timestamps per line, the `offset` tag
+    /// and enhanced `<mm:ss.xx>` word timings. `None` if nothing is timed.
+    pub fn parse_lrc(text: &str) -> Option<Self> {
+        let mut offset_ms: i64 = 0;
+        let mut lines = Vec::new();
+
+        for line in text.lines().map(str::trim) {
+            let mut rest = line;
+            let mut times = Vec::new();
+
+            while let Some(tag) = rest.strip_prefix('[')
+                && let Some(end) = tag.find(']')
+            {
+                let (content, after) = (&tag[..end], &tag[end + 1..]);
+
+                match parse_time(content) {
+                    Some(time) => times.push(time),
+                    None => {
+                        if let Some(offset) = content.strip_prefix("offset:") {
+                            offset_ms = offset.trim().parse().unwrap_or(0);
+                        }
+                        break;
+                    }
+                }
+
+                rest = after;
+            }
+
+            if times.is_empty() {
+                continue;
+            }
+
+            let (text, words) = parse_words(rest.trim());
+            for time in times {
+                lines.push(LyricLine {
+                    time: Some(time),
+                    text: text.clone(),
+                    words: words.clone(),
+                });
+            }
+        }
+
+        if lines.is_empty() {
+            return None;
+        }
+
+        // A positive offset shows the lyrics sooner.
+        let shift = |time: Duration| {
+            let ms = time.as_millis() as i64 - offset_ms;
+            Duration::from_millis(ms.max(0) as u64)
+        };
+        for line in &mut lines {
+            line.time = line.time.map(shift);
+            for (time, _) in &mut line.words {
+                *time = shift(*time);
+            }
+        }
+
+        lines.sort_by_key(|line| line.time);
+        Some(Lyrics { lines })
+    }
+
+    pub fn plain(text: &str) -> Option<Self> {
+        let lines: Vec<LyricLine> = text
+            .lines()
+            .map(|line| LyricLine {
+                time: None,
+                text: line.trim().to_string(),
+                words: Vec::new(),
+            })
+            .collect();
+
+        lines
+            .iter()
+            .any(|line| !line.text.is_empty())
+            .then_some(Lyrics { lines })
+    }
+
+    /// Writes the synchronized lines as LRC, keeping enhanced word timings.
+    pub fn to_lrc(&self) -> String {
+        let mut lrc = String::new();
+
+        for line in &self.lines {
+            let Some(time) = line.time else {
+                continue;
+            };
+
+            lrc.push_str(&format!("[{}]", format_time(time)));
+            if line.words.is_empty() {
+                lrc.push_str(&line.text);
+            } else {
+                // The words make up the end of the text, anything before
+                // the first one isn't timed.
+                let words: String = line.words.iter().map(|(_, word)| word.as_str()).collect();
+                lrc.push_str(line.text.strip_suffix(&words).unwrap_or_default());
+
+                for (time, word) in &line.words {
+                    lrc.push_str(&format!("<{}>{word}", format_time(*time)));
+                }
+            }
+            lrc.push('\n');
+        }
+
+        lrc
+    }
+
+    pub fn to_plain(&self) -> String {
+        self.lines
+            .iter()
+            .map(|line| line.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn write_sidecar(&self, path: &Path) -> Result<()> {
+        if !self.is_synced() {
+            bail!("no line has a timestamp");
+        }
+
+        fs_util::write_atomic(&Self::sidecar(path), self.to_lrc())
+    }
+
+    /// Writes the lyrics into the track's tags: USLT and SYLT frames for
+    /// MP3, and the lyrics tag, as LRC when synchronized, for other formats.
+    pub fn write_tags(&self, path: &Path) -> Result<()> {
+        let mut tagged_file = Probe::open(path)?.guess_file_type()?.read()?;
+
+        if tagged_file.file_type() == FileType::Mpeg {
+            return self.write_id3v2(path);
+        }
+
+        if tagged_file.primary_tag().is_none() {
+            let tag_type = tagged_file.primary_tag_type();
+            tagged_file.insert_tag(Tag::new(tag_type));
+        }
+
+        let Some(tag) = tagged_file.primary_tag_mut() else {
+            bail!("{} can't hold tags", path.display());
+        };
+
+        let text = if self.is_synced() {
+            self.to_lrc()
+        } else {
+            self.to_plain()
+        };
+        tag.insert_text(ItemKey::Lyrics, text);
+        tag.save_to_path(path, WriteOptions::default())?;
+        Ok(())
+    }
+
+    /// Goes through the ID3v2 tag directly, as SYLT has no generic
+    /// equivalent and would be lost converting from [`Tag`].
+    fn write_id3v2(&self, path: &Path) -> Result<()> {
+        let mut file = fs::File::open(path)?;
+        let mpeg = MpegFile::read_from(&mut file, ParseOptions::new())?;
+        drop(file);
+
+        let mut tag: Id3v2Tag = mpeg.id3v2().cloned().unwrap_or_default();
+        tag.insert(Frame::UnsynchronizedText(UnsynchronizedTextFrame::new(
+            TextEncoding::UTF8,
+            *b"XXX",
+            String::new(),
+            self.to_plain(),
+        )));
+
+        let sylt_id = FrameId::Valid(Cow::Borrowed("SYLT"));
+        if self.is_synced() {
+            let content = self
+                .lines
+                .iter()
+                .filter_map(|line| Some((line.time?.as_millis() as u32, line.text.clone())))
+                .collect();
+            let sylt = SynchronizedTextFrame::new(
+                TextEncoding::UTF8,
+                *b"XXX",
+                TimestampFormat::MS,
+                SyncTextContentType::Lyrics,
+                None,
+                content,
+            );
+            tag.insert(Frame::Binary(BinaryFrame::new(
+                sylt_id,
+                sylt.as_bytes(false)?,
+            )));
+        } else {
+            tag.remove(&sylt_id).for_each(drop);
+        }
+
+        tag.save_to_path(path, WriteOptions::default())?;
+        Ok(())
+    }
+
+    /// SYLT frames only exist in ID3v2, which in practice means MP3.
+    fn read_sylt(path: &Path) -> Option<Self> {
+        let mut file = fs::File::open(path).ok()?;
+        let mpeg = MpegFile::read_from(&mut file, ParseOptions::new()).ok()?;
+        let Frame::Binary(frame) = mpeg.id3v2()?.get(&FrameId::Valid(Cow::Borrowed("SYLT")))?
+        else {
+            return None;
+        };
+
+        let sylt = SynchronizedTextFrame::parse(&frame.data, frame.flags()).ok()?;
+        if sylt.timestamp_format != TimestampFormat::MS {
+            return None;
+        }
+
+        let mut lines: Vec<LyricLine> = sylt
+            .content
+            .into_iter()
+            .map(|(ms, text)| LyricLine {
+                time: Some(Duration::from_millis(ms.into())),
+                text: text.trim_matches(['\n', '\r']).to_string(),
+                words: Vec::new(),
+            })
+            .collect();
+        // Nothing requires the frame to be in order.
+        lines.sort_by_key(|line| line.time);
+
+        (!lines.is_empty()).then_some(Lyrics { lines })
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.lines.first().is_some_and(|line| line.time.is_some())
+    }
+
+    /// The line being sung at `position`, if the lyrics are synchronized
+    /// and it has started.
+    pub fn line_at(&self, position: Duration) -> Option<usize> {
+        if !self.is_synced() {
+            return None;
+        }
+
+        self.lines
+            .partition_point(|line| line.time.is_some_and(|time| time <= position))
+            .checked_sub(1)
+    }
+}
+
+/// Parses `mm:ss`, `mm:ss.xx`, `mm:ss.xxx` or `mm:ss:xx`.
+pub fn parse_time(text: &str) -> Option<Duration> {
+    let (minutes, rest) = text.split_once(':')?;
+    let (seconds, fraction) = match rest.split_once(['.', ':']) {
+        Some((seconds, fraction)) => (seconds, fraction),
+        None => (rest, ""),
+    };
+
+    let minutes: u64 = minutes.trim().parse().ok()?;
+    let seconds: u64 = seconds.parse().ok()?;
+    let millis = match fraction.len() {
+        0 => 0,
+        1..=3 => {
+            let value: u64 = fraction.parse().ok()?;
+            value * 10u64.pow(3 - fraction.len() as u32)
+        }
+        _ => return None,
+    };
+
+    Some(Duration::from_millis(
+        (minutes * 60 + seconds) * 1000 + millis,
+    ))
+}
+
+/// Formats a time the way LRC writes it, `mm:ss.xx`.
+pub fn format_time(time: Duration) -> String {
+    let centis = time.as_millis() / 10;
+    format!(
+        "{:02}:{:02}.{:02}",
+        centis / 6000,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+/// Splits enhanced LRC word timings out of a line's text.
+fn parse_words(text: &str) -> (String, Vec<(Duration, String)>) {
+    if !text.contains('<') {
+        return (text.to_string(), Vec::new());
+    }
+
+    let mut plain = String::new();
+    let mut words: Vec<(Duration, String)> = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('<') {
+        plain.push_str(&rest[..start]);
+        if let Some((_, word)) = words.last_mut() {
+            word.push_str(&rest[..start]);
+        }
+
+        let Some(end) = rest[start..].find('>') else {
+            rest = &rest[start..];
+            break;
+        };
+        let Some(time) = parse_time(&rest[start + 1..start + end]) else {
+            plain.push_str(&rest[start..start + end + 1]);
+            if let Some((_, word)) = words.last_mut() {
+                word.push_str(&rest[start..start + end + 1]);
+            }
+            rest = &rest[start + end + 1..];
+            continue;
+        };
+
+        words.push((time, String::new()));
+        rest = &rest[start + end + 1..];
+    }
+
+    plain.push_str(rest);
+    if let Some((_, word)) = words.last_mut() {
+        word.push_str(rest);
+    }
+    // Drops the end mark of the last word.
+    words.retain(|(_, word)| !word.is_empty());
+
+    (plain.trim().to_string(), words)
+}
+
+/// Lyrics of the current track. `path` is the track they belong to, so
+/// results from a track that has since been skipped are dropped.
+#[derive(Default)]
+pub struct CurrentLyrics {
+    pub path: Option<PathBuf>,
+    pub lyrics: Option<Lyrics>,
+}
+
+impl Global for CurrentLyrics {}
+
+/// Reads the lyrics of a newly loaded track in the background.
+pub fn load(path: PathBuf, cx: &mut App) {
+    cx.set_global(CurrentLyrics {
+        path: Some(path.clone()),
+        lyrics: None,
+    });
+
+    let task = cx.background_executor().spawn({
+        let path = path.clone();
+        async move { Lyrics::read(&path) }
+    });
+
+    cx.spawn(async move |cx| {
+        let lyrics = task.await;
+        let _ = cx.update(|cx| {
+            if cx.global::<CurrentLyrics>().path.as_ref() == Some(&path) {
+                cx.set_global(CurrentLyrics {
+                    path: Some(path),
+                    lyrics,
+                });
+            }
+        });
+    })
+    .detach();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn times(lyrics: &Lyrics) -> Vec<(Option<Duration>, &str)> {
+        lyrics
+            .lines
+            .iter()
+            .map(|line| (line.time, line.text.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn lines_with_several_timestamps_repeat() {
+        let lyrics = Lyrics::parse_lrc(
+            "[ti:Song]\n[00:01.00]Verse\n[00:05.00][00:20.50]Chorus\n[00:10.00]Bridge",
+        )
+        .unwrap();
+
+        assert_eq!(
+            times(&lyrics),
+            [
+                (Some(ms(1000)), "Verse"),
+                (Some(ms(5000)), "Chorus"),
+                (Some(ms(10000)), "Bridge"),
+                (Some(ms(20500)), "Chorus"),
+            ]
+        );
+    }
+
+    #[test]
+    fn offset_shifts_every_time() {
+        let lyrics =
+            Lyrics::parse_lrc("[offset:+500]\n[00:00.20]First\n[00:02.00]<00:02.00>Second")
+                .unwrap();
+
+        assert_eq!(
+            times(&lyrics),
+            [(Some(ms(0)), "First"), (Some(ms(1500)), "Second")]
+        );
+        assert_eq!(lyrics.lines[1].words, [(ms(1500), "Second".to_string())]);
+
+        let later = Lyrics::parse_lrc("[offset:-250]\n[00:01.00]Line").unwrap();
+        assert_eq!(later.lines[0].time, Some(ms(1250)));
+    }
+
+    #[test]
+    fn times_accept_every_fraction() {
+        assert_eq!(parse_time("01:02"), Some(ms(62_000)));
+        assert_eq!(parse_time("01:02.5"), Some(ms(62_500)));
+        assert_eq!(parse_time("01:02.34"), Some(ms(62_340)));
+        assert_eq!(parse_time("01:02.345"), Some(ms(62_345)));
+        assert_eq!(parse_time("01:02:34"), Some(ms(62_340)));
+        assert_eq!(parse_time("01:02.3456"), None);
+        assert_eq!(parse_time("ar:Artist"), None);
+
+        let lyrics = Lyrics::parse_lrc("[00:12:50]Colon").unwrap();
+        assert_eq!(times(&lyrics), [(Some(ms(12_500)), "Colon")]);
+    }
+
+    #[test]
+    fn enhanced_lines_time_each_word() {
+        let lyrics =
+            Lyrics::parse_lrc("[00:01.00]<00:01.00>Hello <00:01.50>there<00:02.00>").unwrap();
+        let line = &lyrics.lines[0];
+
+        assert_eq!(line.text, "Hello there");
+        assert_eq!(
+            line.words,
+            [
+                (ms(1000), "Hello ".to_string()),
+                (ms(1500), "there".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn lrc_round_trips() {
+        let lrc = "[00:01.00]Plain line\n\
+                   [00:03.25]Oh, <00:03.50>timed <00:04.00>words\n\
+                   [00:05.00]\n\
+                   [01:10.05]<01:10.05>Late\n";
+        let lyrics = Lyrics::parse_lrc(lrc).unwrap();
+
+        assert_eq!(lyrics.lines[1].text, "Oh, timed words");
+        assert_eq!(lyrics.to_lrc(), lrc);
+        assert_eq!(Lyrics::parse_lrc(&lyrics.to_lrc()), Some(lyrics));
+    }
+
+    #[test]
+    fn untimed_text_is_not_lrc() {
+        assert_eq!(Lyrics::parse_lrc("Just words\n[ar:Someone]"), None);
+        assert!(!Lyrics::plain("Just words").unwrap().is_synced());
+    }
+}
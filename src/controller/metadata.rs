@@ -1,77 +1,178 @@
 use anyhow::Result;
-use lofty::{prelude::*, probe::Probe};
+use lofty::{
+    config::WriteOptions,
+    picture::PictureType,
+    prelude::*,
+    probe::Probe,
+    tag::{ItemValue, Tag, TagItem},
+};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 #[derive(Debug, Default, PartialEq, Clone, Deserialize, Serialize)]
 pub struct Metadata {
     pub title: String,
     pub artists: Vec<String>,
     pub album: String,
+    pub album_artist: String,
     pub genre: String,
-    pub duration: u64,
+    pub comment: String,
+    pub duration: Duration,
+    pub year: Option<u32>,
+    /// Audio bitrate in kbps.
+    pub bitrate: Option<u32>,
     pub writer: String,
     pub producer: String,
     pub publisher: String,
     pub label: String,
 }
 
+/// Tag changes to write to a file. `None` leaves a field as it is.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TagEdit {
+    pub title: Option<String>,
+    pub artists: Option<Vec<String>>,
+    pub album: Option<String>,
+    pub genre: Option<String>,
+}
+
 impl Metadata {
     pub fn read(path: PathBuf) -> Result<Self> {
         let tagged_file = Probe::open(path.clone())?.guess_file_type()?.read()?;
 
-        let tag = match tagged_file.primary_tag() {
-            Some(primary_tag) => primary_tag,
-            None => tagged_file
-                .first_tag()
-                .expect("ERROR: could not find any tags!"),
+        // Untagged files still get a usable title and duration.
+        let tag = tagged_file
+            .primary_tag()
+            .or_else(|| tagged_file.first_tag());
+        let get = |key: ItemKey| {
+            tag.and_then(|tag| tag.get_string(&key))
+                .unwrap_or("None")
+                .to_string()
         };
 
         let title = tag
-            .get_string(&ItemKey::TrackTitle)
-            .unwrap_or("None")
-            .to_string();
+            .and_then(|tag| tag.get_string(&ItemKey::TrackTitle))
+            .map(str::to_string)
+            .or_else(|| {
+                path.file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+            })
+            .unwrap_or_else(|| String::from("None"));
         let artists: Vec<String> = tag
-            .get_strings(&ItemKey::TrackArtist)
-            .map(|s| s.to_owned())
-            .collect();
-        let album = tag
-            .get_string(&ItemKey::AlbumTitle)
-            .unwrap_or("None")
-            .to_string();
-        let genre = tag
-            .get_string(&ItemKey::Genre)
-            .unwrap_or("None")
-            .to_string();
-        let duration = tagged_file.properties().duration().as_secs();
+            .map(|tag| {
+                tag.get_strings(&ItemKey::TrackArtist)
+                    .map(|s| s.to_owned())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let album = get(ItemKey::AlbumTitle);
+        let album_artist = get(ItemKey::AlbumArtist);
+        let genre = get(ItemKey::Genre);
+        let comment = get(ItemKey::Comment);
+        let duration = tagged_file.properties().duration();
+        let year = tag.and_then(|tag| tag.year());
+        let bitrate = tagged_file.properties().audio_bitrate();
         let writer = tag
-            .get_string(&ItemKey::Writer)
-            .or_else(|| tag.get_string(&ItemKey::Composer))
-            .unwrap_or("None")
-            .to_string();
-        let producer = tag
-            .get_string(&ItemKey::Producer)
-            .unwrap_or("None")
-            .to_string();
-        let publisher = tag
-            .get_string(&ItemKey::Publisher)
-            .unwrap_or("None")
-            .to_string();
-        let label = tag
-            .get_string(&ItemKey::Label)
+            .and_then(|tag| {
+                tag.get_string(&ItemKey::Writer)
+                    .or_else(|| tag.get_string(&ItemKey::Composer))
+            })
             .unwrap_or("None")
             .to_string();
+        let producer = get(ItemKey::Producer);
+        let publisher = get(ItemKey::Publisher);
+        let label = get(ItemKey::Label);
 
         Ok(Metadata {
             title,
             artists,
             album,
+            album_artist,
             genre,
+            comment,
             duration,
+            year,
+            bitrate,
             writer,
             producer,
             publisher,
             label,
         })
     }
+
+    /// Returns the raw image data of the track's front cover, falling back to
+    /// any embedded picture and then to a cover image next to the file.
+    pub fn cover(path: &Path) -> Option<Vec<u8>> {
+        let embedded = Probe::open(path)
+            .and_then(|probe| probe.guess_file_type())
+            .and_then(|probe| probe.read())
+            .ok()
+            .and_then(|tagged_file| {
+                let pictures: Vec<_> = tagged_file
+                    .tags()
+                    .iter()
+                    .flat_map(|tag| tag.pictures())
+                    .collect();
+
+                pictures
+                    .iter()
+                    .find(|picture| picture.pic_type() == PictureType::CoverFront)
+                    .or_else(|| pictures.first())
+                    .map(|picture| picture.data().to_vec())
+            });
+
+        embedded.or_else(|| {
+            let dir = path.parent()?;
+            ["cover", "folder", "front", "Cover", "Folder", "Front"]
+                .iter()
+                .flat_map(|name| {
+                    ["jpg", "jpeg", "png"].map(|ext| dir.join(format!("{name}.{ext}")))
+                })
+                .find_map(|candidate| fs::read(candidate).ok())
+        })
+    }
+
+    /// Writes `edit` to the file's primary tag, creating the tag if the file
+    /// has none.
+    pub fn write(path: &Path, edit: &TagEdit) -> Result<()> {
+        let mut tagged_file = Probe::open(path)?.guess_file_type()?.read()?;
+
+        if tagged_file.primary_tag().is_none() {
+            let tag_type = tagged_file.primary_tag_type();
+            tagged_file.insert_tag(Tag::new(tag_type));
+        }
+
+        let Some(tag) = tagged_file.primary_tag_mut() else {
+            anyhow::bail!("{} can't hold tags", path.display());
+        };
+
+        if let Some(title) = &edit.title {
+            tag.set_title(title.clone());
+        }
+
+        if let Some(artists) = &edit.artists {
+            tag.remove_key(&ItemKey::TrackArtist);
+            for artist in artists {
+                tag.push(TagItem::new(
+                    ItemKey::TrackArtist,
+                    ItemValue::Text(artist.clone()),
+                ));
+            }
+        }
+
+        if let Some(album) = &edit.album {
+            tag.set_album(album.clone());
+        }
+
+        if let Some(genre) = &edit.genre {
+            tag.set_genre(genre.clone());
+        }
+
+        tag.save_to_path(path, WriteOptions::default())?;
+        Ok(())
+    }
 }
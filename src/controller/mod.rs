@@ -1,2 +1,3 @@
-pub mod player;
+pub mod lyrics;
 pub mod metadata;
+pub mod player;
@@ -1,53 +1,190 @@
 use super::metadata::Metadata;
-use crate::audio::engine::PlaybackState;
-use crossbeam_channel::{Receiver, Sender};
+use crate::audio::{
+    analyzer::Analyzer,
+    engine::{PlaybackState, RepeatMode},
+    error::AudioError,
+};
+use crate::config::{AudioConfig, ReplayGainMode};
+use crossbeam_channel::Sender;
 use gpui::*;
-use std::path::PathBuf;
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
 #[derive(Debug, Clone)]
 pub struct Controller {
     pub audio_tx: Sender<AudioCommand>,
-    pub event_rx: Receiver<AudioEvent>,
+    pub event_rx: async_channel::Receiver<AudioEvent>,
     pub state: PlayerState,
+    pub position_updated_at: Instant,
+    pub output_devices: Vec<String>,
+    /// What the engine is playing, for the visualizer.
+    pub analyzer: Analyzer,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct PlayerState {
     pub current: Option<PathBuf>,
     pub state: PlaybackState,
-    pub position: u64,
+    pub position: Duration,
     pub volume: f32,
-    pub duration: u64,
+    pub duration: Duration,
+    pub muted: bool,
     pub meta: Option<Metadata>,
+    pub queue: Vec<PathBuf>,
+    pub index: Option<usize>,
+    pub repeat: RepeatMode,
+    pub shuffle: bool,
 }
 
 pub enum AudioCommand {
     Load(String),
+    Enqueue(Vec<PathBuf>),
+    PlayNext(Vec<PathBuf>),
+    ClearQueue,
+    PlayIndex(usize),
+    MoveInQueue {
+        from: usize,
+        to: usize,
+    },
+    RemoveFromQueue(Vec<usize>),
+    ReplaceQueue {
+        queue: Vec<PathBuf>,
+        index: usize,
+    },
     Play,
     Pause,
+    Next,
+    Previous,
     Volume(f32),
-    Seek(u64),
+    Mute(bool),
+    Seek(Duration),
     Stop,
     Meta(Metadata),
+    SetOutputDevice(Option<String>),
+    ListOutputDevices,
+    SetRepeat(RepeatMode),
+    SetShuffle(bool),
+    SetReplayGain {
+        mode: ReplayGainMode,
+        preamp: f32,
+    },
+    SetCrossfade(Duration),
+    Restore {
+        queue: Vec<PathBuf>,
+        index: Option<usize>,
+        position: Duration,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum AudioEvent {
-    StateChanged(PlayerState),
+    StateChanged(PlaybackState),
+    PositionChanged(Duration),
+    DurationChanged(Duration),
+    VolumeChanged {
+        volume: f32,
+        muted: bool,
+    },
+    QueueChanged {
+        queue: Vec<PathBuf>,
+        index: Option<usize>,
+    },
+    ModeChanged {
+        repeat: RepeatMode,
+        shuffle: bool,
+    },
     TrackLoaded(PathBuf),
     TrackEnded,
+    OutputDevices(Vec<String>),
+    Error(AudioError),
 }
 
 impl Controller {
     pub fn new(
         audio_tx: Sender<AudioCommand>,
-        event_rx: Receiver<AudioEvent>,
+        event_rx: async_channel::Receiver<AudioEvent>,
         state: PlayerState,
+        analyzer: Analyzer,
     ) -> Controller {
         Controller {
             audio_tx,
             event_rx,
             state,
+            position_updated_at: Instant::now(),
+            output_devices: Vec::new(),
+            analyzer,
+        }
+    }
+
+    /// Mirrors an engine event into the UI-side copy of the player state.
+    pub fn apply(&mut self, event: &AudioEvent) {
+        match event {
+            AudioEvent::StateChanged(state) => {
+                // Re-anchor the interpolation so resuming doesn't jump ahead
+                // by the time spent paused.
+                self.state.position = self.position();
+                self.position_updated_at = Instant::now();
+                self.state.state = *state;
+            }
+            AudioEvent::PositionChanged(position) => {
+                self.state.position = *position;
+                self.position_updated_at = Instant::now();
+            }
+            AudioEvent::DurationChanged(duration) => self.state.duration = *duration,
+            AudioEvent::VolumeChanged { volume, muted } => {
+                self.state.volume = *volume;
+                self.state.muted = *muted;
+            }
+            AudioEvent::QueueChanged { queue, index } => {
+                self.state.queue = queue.clone();
+                self.state.index = *index;
+
+                if index.is_none() {
+                    self.state.current = None;
+                    self.state.meta = None;
+                }
+            }
+            AudioEvent::ModeChanged { repeat, shuffle } => {
+                self.state.repeat = *repeat;
+                self.state.shuffle = *shuffle;
+            }
+            AudioEvent::TrackLoaded(path) => {
+                self.state.current = Some(path.clone());
+                self.state.meta = None;
+            }
+            AudioEvent::OutputDevices(devices) => self.output_devices = devices.clone(),
+            AudioEvent::TrackEnded | AudioEvent::Error(_) => {}
+        }
+    }
+
+    /// Position interpolated from the last engine tick, so the UI can move
+    /// smoothly between the engine's coarser position updates.
+    pub fn position(&self) -> Duration {
+        let position = if self.state.state == PlaybackState::Playing {
+            self.state.position + self.position_updated_at.elapsed()
+        } else {
+            self.state.position
+        };
+
+        match self.duration() {
+            Some(duration) => position.min(duration),
+            None => position,
+        }
+    }
+
+    pub fn duration(&self) -> Option<Duration> {
+        Some(self.state.duration).filter(|duration| !duration.is_zero())
+    }
+
+    /// Playback progress in the range `0.0..=1.0`.
+    pub fn progress(&self) -> f32 {
+        match self.duration() {
+            Some(duration) => {
+                (self.position().as_secs_f64() / duration.as_secs_f64()).clamp(0.0, 1.0) as f32
+            }
+            None => 0.0,
         }
     }
 
@@ -59,6 +196,58 @@ impl Controller {
         let _ = self.audio_tx.send(AudioCommand::Pause);
     }
 
+    pub fn play_pause(&self) {
+        if self.state.state == PlaybackState::Playing {
+            self.pause();
+        } else {
+            self.play();
+        }
+    }
+
+    pub fn stop(&self) {
+        let _ = self.audio_tx.send(AudioCommand::Stop);
+    }
+
+    pub fn next(&self) {
+        let _ = self.audio_tx.send(AudioCommand::Next);
+    }
+
+    pub fn previous(&self) {
+        let _ = self.audio_tx.send(AudioCommand::Previous);
+    }
+
+    pub fn enqueue(&self, paths: Vec<PathBuf>) {
+        let _ = self.audio_tx.send(AudioCommand::Enqueue(paths));
+    }
+
+    pub fn play_next(&self, paths: Vec<PathBuf>) {
+        let _ = self.audio_tx.send(AudioCommand::PlayNext(paths));
+    }
+
+    /// Replaces the queue and starts playing it from `index`.
+    pub fn play_tracks(&self, queue: Vec<PathBuf>, index: usize) {
+        let _ = self
+            .audio_tx
+            .send(AudioCommand::ReplaceQueue { queue, index });
+    }
+
+    pub fn clear_queue(&self) {
+        let _ = self.audio_tx.send(AudioCommand::ClearQueue);
+    }
+
+    /// Plays the queue entry at `index`.
+    pub fn play_index(&self, index: usize) {
+        let _ = self.audio_tx.send(AudioCommand::PlayIndex(index));
+    }
+
+    pub fn move_in_queue(&self, from: usize, to: usize) {
+        let _ = self.audio_tx.send(AudioCommand::MoveInQueue { from, to });
+    }
+
+    pub fn remove_from_queue(&self, indices: Vec<usize>) {
+        let _ = self.audio_tx.send(AudioCommand::RemoveFromQueue(indices));
+    }
+
     pub fn load(&self, path: String) {
         let _ = self.audio_tx.send(AudioCommand::Load(path));
     }
@@ -67,11 +256,85 @@ impl Controller {
         let _ = self.audio_tx.send(AudioCommand::Volume(volume / 100.0));
     }
 
-    pub fn seek(&self, secs: u64) {
-        let _ = self.audio_tx.send(AudioCommand::Seek(secs));
+    /// Raises or lowers the volume by `step` percentage points.
+    pub fn step_volume(&self, step: f32) {
+        self.volume((self.state.volume * 100.0 + step).clamp(0.0, 100.0));
+    }
+
+    pub fn cycle_repeat(&self) {
+        let repeat = match self.state.repeat {
+            RepeatMode::Off => RepeatMode::All,
+            RepeatMode::All => RepeatMode::One,
+            RepeatMode::One => RepeatMode::Off,
+        };
+        let _ = self.audio_tx.send(AudioCommand::SetRepeat(repeat));
+    }
+
+    pub fn toggle_shuffle(&self) {
+        let _ = self
+            .audio_tx
+            .send(AudioCommand::SetShuffle(!self.state.shuffle));
+    }
+
+    pub fn toggle_mute(&self) {
+        let _ = self.audio_tx.send(AudioCommand::Mute(!self.state.muted));
+    }
+
+    pub fn seek(&self, pos: Duration) {
+        let _ = self.audio_tx.send(AudioCommand::Seek(pos));
+    }
+
+    /// Seeks `offset` seconds forward, or backward when negative, staying
+    /// within the current track.
+    pub fn seek_by(&self, offset: f32) {
+        if self.state.state == PlaybackState::Stopped {
+            return;
+        }
+
+        if let Some(duration) = self.duration() {
+            let target =
+                (self.position().as_secs_f32() + offset).clamp(0.0, duration.as_secs_f32());
+            self.seek(Duration::from_secs_f32(target));
+        }
+    }
+
+    /// Seeks to a fraction (`0.0..=1.0`) of the current track's duration.
+    pub fn seek_fraction(&self, fraction: f32) {
+        if let Some(duration) = self.duration() {
+            self.seek(duration.mul_f64(fraction.clamp(0.0, 1.0) as f64));
+        }
+    }
+
+    pub fn set_output_device(&self, device: Option<String>) {
+        let _ = self.audio_tx.send(AudioCommand::SetOutputDevice(device));
+    }
+
+    /// Sends whatever differs between two audio configs to the engine.
+    pub fn apply_audio_config(&self, old: &AudioConfig, new: &AudioConfig) {
+        if old.device != new.device {
+            self.set_output_device(new.device.clone());
+        }
+
+        if old.replaygain != new.replaygain || old.preamp != new.preamp {
+            let _ = self.audio_tx.send(AudioCommand::SetReplayGain {
+                mode: new.replaygain,
+                preamp: new.preamp,
+            });
+        }
+
+        if old.crossfade != new.crossfade {
+            let _ = self
+                .audio_tx
+                .send(AudioCommand::SetCrossfade(new.crossfade()));
+        }
+    }
+
+    pub fn refresh_output_devices(&self) {
+        let _ = self.audio_tx.send(AudioCommand::ListOutputDevices);
     }
 
-    pub fn set_meta_in_engine(&self, meta: Metadata) {
+    pub fn set_meta(&mut self, meta: Metadata) {
+        self.state.meta = Some(meta.clone());
         let _ = self.audio_tx.send(AudioCommand::Meta(meta));
     }
 }
@@ -83,10 +346,15 @@ impl Default for PlayerState {
         Self {
             current: None,
             state: PlaybackState::Stopped,
-            position: 0,
+            position: Duration::ZERO,
             volume: 1.0,
-            duration: 0,
+            duration: Duration::ZERO,
+            muted: false,
             meta: None,
+            queue: Vec::new(),
+            index: None,
+            repeat: RepeatMode::Off,
+            shuffle: false,
         }
     }
 }
@@ -101,9 +369,4 @@ impl ResHandler {
     }
 }
 
-pub enum PlayerStateEvent {
-    Position(u64),
-}
-
 impl EventEmitter<AudioEvent> for ResHandler {}
-impl EventEmitter<PlayerStateEvent> for PlayerState {}
@@ -0,0 +1,47 @@
+use anyhow::{Context, Result};
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Writes `contents` to a temporary file next to `path` and renames it into
+/// place, creating missing parent directories. Readers, including the config
+/// watcher, see either the old file or the new one, never a half written
+/// file, and a crash mid-write leaves the old file intact.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("could not create {}", parent.display()))?;
+    }
+
+    let tmp = tmp_path(path);
+    fs::write(&tmp, contents).with_context(|| format!("could not write {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("could not write {}", path.display()))
+}
+
+/// `path` with `.tmp` appended, keeping its own extension so temporary files
+/// of different entries never collide.
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_atomic_creates_and_replaces() {
+        let dir = std::env::temp_dir().join(format!("wiremann-{}-fs-util", std::process::id()));
+        let path = dir.join("nested").join("config.toml");
+
+        write_atomic(&path, "old").unwrap();
+        write_atomic(&path, "new").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert!(!tmp_path(&path).exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,288 @@
+use crate::audio::engine::PlaybackState;
+use crate::config::Config;
+use crate::controller::{metadata::Metadata, player::Controller};
+use crate::library::is_known;
+use crate::ui::components::notifications::report_error;
+use anyhow::{Context, Result};
+use crossbeam_channel::{Receiver, Sender};
+use gpui::{App, Global};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    },
+    thread,
+};
+use zbus::{
+    blocking::{Connection, Proxy},
+    zvariant::{Structure, Value},
+};
+
+const DESTINATION: &str = "org.freedesktop.Notifications";
+const PATH: &str = "/org/freedesktop/Notifications";
+const INTERFACE: &str = "org.freedesktop.Notifications";
+
+/// Size the cover is scaled down to before it's sent over D-Bus.
+const COVER_SIZE: u32 = 128;
+
+/// Buttons offered on a notification, by their D-Bus action key.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NotificationAction {
+    Previous,
+    Next,
+}
+
+/// A track to announce.
+struct Notification {
+    path: PathBuf,
+    title: String,
+    body: String,
+    actions: bool,
+}
+
+/// Desktop notifications for the playing track. They're sent from a thread
+/// of their own, since D-Bus calls block.
+pub struct Notifier {
+    tx: Sender<Notification>,
+    /// Track loaded but not announced yet. It is once it plays, so a track
+    /// restored paused at startup stays quiet.
+    pending: Option<PathBuf>,
+}
+
+impl Global for Notifier {}
+
+impl NotificationAction {
+    const ALL: [NotificationAction; 2] = [NotificationAction::Previous, NotificationAction::Next];
+
+    fn key(self) -> &'static str {
+        match self {
+            NotificationAction::Previous => "previous",
+            NotificationAction::Next => "next",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            NotificationAction::Previous => "Previous",
+            NotificationAction::Next => "Next",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.key() == key)
+    }
+}
+
+impl Notification {
+    /// Shows the notification in place of the one with id `replaces`, or as
+    /// a new one for `0`. Returns the id it was given.
+    fn send(&self, proxy: &Proxy, replaces: u32) -> Result<u32> {
+        let actions: Vec<&str> = if self.actions {
+            NotificationAction::ALL
+                .into_iter()
+                .flat_map(|action| [action.key(), action.label()])
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let mut hints: HashMap<&str, Value> = HashMap::new();
+        hints.insert("desktop-entry", Value::from("wiremann"));
+        hints.insert("category", Value::from("x-gnome.music"));
+        if let Some(cover) = cover(&self.path) {
+            hints.insert("image-data", cover);
+        }
+
+        proxy
+            .call(
+                "Notify",
+                &(
+                    "Wiremann",
+                    replaces,
+                    "multimedia-audio-player",
+                    self.title.as_str(),
+                    self.body.as_str(),
+                    actions,
+                    hints,
+                    -1i32,
+                ),
+            )
+            .context("could not send a notification")
+    }
+}
+
+/// Starts the notification thread and announces tracks as they start
+/// playing. Connecting to the session bus happens on that thread, and
+/// without one notifications stay off. Its errors are only reported while
+/// notifications are enabled.
+pub fn init(cx: &mut App) {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let (actions_tx, actions_rx) = async_channel::unbounded();
+    let (errors_tx, errors_rx) = async_channel::unbounded();
+
+    thread::spawn(move || {
+        if let Err(err) = run(rx, actions_tx, &errors_tx) {
+            let _ = errors_tx.send_blocking(err);
+        }
+    });
+
+    cx.spawn(async move |cx| {
+        while let Ok(err) = errors_rx.recv().await {
+            let result = cx.update(|cx| {
+                if cx.global::<Config>().integrations.notifications {
+                    report_error("Could not show a notification", err, cx);
+                }
+            });
+
+            if result.is_err() {
+                break;
+            }
+        }
+    })
+    .detach();
+
+    cx.spawn(async move |cx| {
+        while let Ok(action) = actions_rx.recv().await {
+            let result = cx.update(|cx| {
+                let controller = cx.global::<Controller>();
+                match action {
+                    NotificationAction::Previous => controller.previous(),
+                    NotificationAction::Next => controller.next(),
+                }
+            });
+
+            if result.is_err() {
+                break;
+            }
+        }
+    })
+    .detach();
+
+    cx.set_global(Notifier { tx, pending: None });
+    cx.observe_global::<Controller>(announce).detach();
+}
+
+/// Marks a newly loaded track to be announced once it plays.
+pub fn track_loaded(path: PathBuf, cx: &mut App) {
+    cx.global_mut::<Notifier>().pending = Some(path);
+}
+
+fn announce(cx: &mut App) {
+    let state = &cx.global::<Controller>().state;
+    if state.state != PlaybackState::Playing || cx.global::<Notifier>().pending.is_none() {
+        return;
+    }
+
+    let Some(path) = cx.global_mut::<Notifier>().pending.take() else {
+        return;
+    };
+
+    let state = &cx.global::<Controller>().state;
+    let integrations = &cx.global::<Config>().integrations;
+    if state.current.as_ref() != Some(&path)
+        || !integrations.notifications
+        || (integrations.quiet_when_focused && cx.active_window().is_some())
+    {
+        return;
+    }
+
+    let Some(meta) = &state.meta else {
+        return;
+    };
+
+    let notification = Notification {
+        path,
+        title: meta.title.clone(),
+        body: body(meta),
+        actions: integrations.notification_actions,
+    };
+    let _ = cx.global::<Notifier>().tx.send(notification);
+}
+
+/// Sends notifications one at a time, each replacing the last, and passes
+/// clicked buttons back from a second thread listening for them. Failed
+/// sends go to `errors_tx` without stopping the thread.
+fn run(
+    rx: Receiver<Notification>,
+    actions_tx: async_channel::Sender<NotificationAction>,
+    errors_tx: &async_channel::Sender<anyhow::Error>,
+) -> Result<()> {
+    let connection = Connection::session().context("could not connect to the session bus")?;
+    let proxy = Proxy::new(&connection, DESTINATION, PATH, INTERFACE)
+        .context("could not reach the notification server")?;
+    let signals = proxy
+        .receive_signal("ActionInvoked")
+        .context("could not listen for notification actions")?;
+
+    let id = Arc::new(AtomicU32::new(0));
+    let shown = id.clone();
+    thread::spawn(move || {
+        for message in signals {
+            let Ok((id, key)) = message.body().deserialize::<(u32, String)>() else {
+                continue;
+            };
+
+            // Other apps' notifications raise the same signal.
+            if id != shown.load(Ordering::Relaxed) {
+                continue;
+            }
+
+            if let Some(action) = NotificationAction::from_key(&key)
+                && actions_tx.send_blocking(action).is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    for notification in rx {
+        match notification.send(&proxy, id.load(Ordering::Relaxed)) {
+            Ok(new) => id.store(new, Ordering::Relaxed),
+            Err(err) => {
+                let _ = errors_tx.send_blocking(err);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Artists and album, one per line, leaving out missing tags. The body may
+/// be read as markup, so they're escaped.
+fn body(meta: &Metadata) -> String {
+    let artists: Vec<&str> = meta
+        .artists
+        .iter()
+        .map(String::as_str)
+        .filter(|artist| is_known(artist))
+        .collect();
+
+    [artists.join(", "), meta.album.clone()]
+        .into_iter()
+        .filter(|line| is_known(line))
+        .map(|line| escape(&line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// The track's cover as an `image-data` hint: width, height, row stride,
+/// alpha, bits per sample, channels and the RGBA pixels.
+fn cover(path: &Path) -> Option<Value<'static>> {
+    let bytes = Metadata::cover(path)?;
+    let image = image::load_from_memory(&bytes)
+        .ok()?
+        .thumbnail(COVER_SIZE, COVER_SIZE)
+        .to_rgba8();
+    let (width, height) = (image.width() as i32, image.height() as i32);
+
+    let hint = Structure::from((width, height, width * 4, true, 8i32, 4i32, image.into_raw()));
+    Some(hint.into())
+}
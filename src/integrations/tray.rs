@@ -0,0 +1,219 @@
+use crate::audio::engine::PlaybackState;
+use crate::config::Config;
+use crate::controller::player::Controller;
+use crate::session;
+use crate::ui::components::notifications::report_error;
+use async_channel::{Receiver, Sender};
+use gpui::{App, Global, Window};
+use ksni::{
+    ToolTip,
+    blocking::{Handle, TrayMethods},
+    menu::{MenuItem, StandardItem},
+};
+
+/// What the tray menu asks of the app, handled on the UI thread.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrayCommand {
+    PlayPause,
+    Next,
+    Previous,
+    Show,
+    Quit,
+}
+
+/// What the tray shows of the player.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct TrayState {
+    title: Option<String>,
+    artists: String,
+    playing: bool,
+}
+
+/// The StatusNotifierItem itself. It lives on ksni's thread, so it only
+/// holds a copy of the state and sends commands back.
+struct StatusItem {
+    state: TrayState,
+    tx: Sender<TrayCommand>,
+}
+
+/// The tray icon, running while enabled in the config.
+pub struct Tray {
+    tx: Sender<TrayCommand>,
+    handle: Option<Handle<StatusItem>>,
+    /// Whether the config asked for the icon when last synced, so a failed
+    /// start isn't retried on every player update.
+    enabled: bool,
+    state: TrayState,
+}
+
+impl Global for Tray {}
+
+impl TrayState {
+    fn new(controller: &Controller) -> Self {
+        let meta = controller.state.meta.as_ref();
+
+        TrayState {
+            title: meta.map(|meta| meta.title.clone()),
+            artists: meta.map(|meta| meta.artists.join(", ")).unwrap_or_default(),
+            playing: controller.state.state == PlaybackState::Playing,
+        }
+    }
+}
+
+impl StatusItem {
+    fn send(&self, command: TrayCommand) {
+        let _ = self.tx.send_blocking(command);
+    }
+
+    fn item(label: &str, command: TrayCommand) -> MenuItem<Self> {
+        StandardItem {
+            label: label.into(),
+            activate: Box::new(move |this: &mut Self| this.send(command)),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+impl ksni::Tray for StatusItem {
+    fn id(&self) -> String {
+        String::from("wiremann")
+    }
+
+    fn title(&self) -> String {
+        String::from("Wiremann")
+    }
+
+    fn icon_name(&self) -> String {
+        String::from("multimedia-audio-player")
+    }
+
+    fn tool_tip(&self) -> ToolTip {
+        let (title, description) = match &self.state.title {
+            Some(title) => (title.clone(), self.state.artists.clone()),
+            None => (String::from("Wiremann"), String::from("Nothing playing")),
+        };
+
+        ToolTip {
+            title,
+            description,
+            ..Default::default()
+        }
+    }
+
+    fn activate(&mut self, _x: i32, _y: i32) {
+        self.send(TrayCommand::Show);
+    }
+
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        vec![
+            Self::item(
+                if self.state.playing { "Pause" } else { "Play" },
+                TrayCommand::PlayPause,
+            ),
+            Self::item("Next", TrayCommand::Next),
+            Self::item("Previous", TrayCommand::Previous),
+            MenuItem::Separator,
+            Self::item("Show Wiremann", TrayCommand::Show),
+            Self::item("Quit", TrayCommand::Quit),
+        ]
+    }
+}
+
+/// Sets up the tray global and keeps the icon in step with the config and
+/// the player. Commands from the menu arrive on the returned channel.
+pub fn init(cx: &mut App) -> Receiver<TrayCommand> {
+    let (tx, rx) = async_channel::unbounded();
+
+    cx.set_global(Tray {
+        tx,
+        handle: None,
+        enabled: false,
+        state: TrayState::default(),
+    });
+    sync(cx);
+
+    cx.observe_global::<Config>(sync).detach();
+    cx.observe_global::<Controller>(sync).detach();
+
+    rx
+}
+
+/// Starts or stops the icon as the config asks, and passes player changes
+/// on to it. The D-Bus side is only touched when something it shows
+/// changed, not on every position update.
+fn sync(cx: &mut App) {
+    let enabled = cx.global::<Config>().integrations.tray;
+    let state = TrayState::new(cx.global::<Controller>());
+    let executor = cx.background_executor().clone();
+    let tray = cx.global_mut::<Tray>();
+    let mut error = None;
+
+    if enabled && !tray.enabled {
+        let item = StatusItem {
+            state: state.clone(),
+            tx: tray.tx.clone(),
+        };
+
+        match item.spawn() {
+            Ok(handle) => tray.handle = Some(handle),
+            Err(err) => error = Some(err),
+        }
+    } else if !enabled && let Some(handle) = tray.handle.take() {
+        // Waiting for ksni's thread to let go of the bus would block the UI.
+        executor
+            .spawn(async move { handle.shutdown().wait() })
+            .detach();
+    } else if tray.state != state
+        && let Some(handle) = &tray.handle
+    {
+        let shown = state.clone();
+        handle.update(move |item| item.state = shown);
+    }
+
+    tray.enabled = enabled;
+    tray.state = state;
+
+    if let Some(err) = error {
+        report_error("Could not show the tray icon", err.into(), cx);
+    }
+}
+
+/// Whether closing the window should only hide it, leaving the icon to
+/// bring it back. Never when the icon failed to start.
+pub fn close_to_tray(cx: &App) -> bool {
+    cx.global::<Config>().integrations.close_to_tray && cx.global::<Tray>().handle.is_some()
+}
+
+/// Saves the session and decides whether closing the main window should
+/// close it, or only hide it while the tray icon is up. Playback goes on in
+/// the engine meanwhile.
+///
+/// gpui can't hide a single window, and on Linux dropping the last one ends
+/// the app, so hiding minimizes it for [`TrayCommand::Show`] to bring back.
+pub fn should_close(window: &mut Window, cx: &mut App) -> bool {
+    session::save(window, cx);
+
+    if close_to_tray(cx) {
+        window.minimize_window();
+        return false;
+    }
+
+    true
+}
+
+/// Carries out a command from the tray menu on the main window.
+pub fn handle_command(command: TrayCommand, window: &mut Window, cx: &mut App) {
+    let controller = cx.global::<Controller>();
+
+    match command {
+        TrayCommand::PlayPause => controller.play_pause(),
+        TrayCommand::Next => controller.next(),
+        TrayCommand::Previous => controller.previous(),
+        TrayCommand::Show => window.activate_window(),
+        TrayCommand::Quit => {
+            session::save(window, cx);
+            cx.quit();
+        }
+    }
+}
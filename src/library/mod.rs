@@ -0,0 +1,337 @@
+pub mod playlist;
+pub mod search;
+pub mod stats;
+
+use crate::config::Config;
+use crate::controller::metadata::Metadata;
+use gpui::{App, Global};
+use search::SearchIndex;
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+/// Extensions of the formats the decoder can play.
+pub const AUDIO_EXTENSIONS: &[&str] = &[
+    "mp3", "flac", "ogg", "oga", "opus", "wav", "m4a", "m4b", "mp4", "aac", "alac", "aif", "aiff",
+    "caf", "mka", "webm",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    pub path: PathBuf,
+    pub meta: Metadata,
+}
+
+/// Tracks sharing an artist, album or genre, as indices into
+/// [`Library::tracks`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group {
+    pub name: String,
+    /// Album artist for albums, empty otherwise.
+    pub artist: String,
+    pub tracks: Vec<usize>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Library {
+    pub tracks: Vec<Track>,
+    pub artists: Vec<Group>,
+    pub albums: Vec<Group>,
+    pub genres: Vec<Group>,
+    pub index: SearchIndex,
+    pub scanning: bool,
+    /// Counts rescans, so a scan that finishes after a newer one started
+    /// is dropped instead of replacing its result.
+    scan: u64,
+}
+
+impl Global for Library {}
+
+impl Library {
+    /// Replaces the tracks, regroups them by artist, album and genre and
+    /// rebuilds the search index.
+    pub fn set_tracks(&mut self, tracks: Vec<Track>) {
+        let mut artists: BTreeMap<String, Group> = BTreeMap::new();
+        let mut albums: BTreeMap<(String, String), Group> = BTreeMap::new();
+        let mut genres: BTreeMap<String, Group> = BTreeMap::new();
+
+        for (ix, track) in tracks.iter().enumerate() {
+            let meta = &track.meta;
+
+            for artist in meta.artists.iter().filter(|a| is_known(a)) {
+                group(&mut artists, artist.to_lowercase(), artist, "", ix);
+            }
+
+            if is_known(&meta.album) {
+                let artist = meta.artists.first().cloned().unwrap_or_default();
+                let key = (meta.album.to_lowercase(), artist.to_lowercase());
+                group(&mut albums, key, &meta.album, &artist, ix);
+            }
+
+            if is_known(&meta.genre) {
+                group(&mut genres, meta.genre.to_lowercase(), &meta.genre, "", ix);
+            }
+        }
+
+        self.index = SearchIndex::new(&tracks);
+        self.tracks = tracks;
+        self.artists = artists.into_values().collect();
+        self.albums = albums.into_values().collect();
+        self.genres = genres.into_values().collect();
+    }
+
+    /// Swaps in re-read tracks, e.g. after their tags were edited.
+    pub fn update_tracks(&mut self, updated: Vec<Track>) {
+        let mut tracks = std::mem::take(&mut self.tracks);
+
+        for track in updated {
+            if let Ok(ix) = tracks.binary_search_by(|t| t.path.cmp(&track.path)) {
+                tracks[ix] = track;
+            }
+        }
+
+        self.set_tracks(tracks);
+    }
+
+    /// Looks a track up by path; tracks are kept sorted by path.
+    pub fn track(&self, path: &Path) -> Option<&Track> {
+        self.tracks
+            .binary_search_by(|track| track.path.as_path().cmp(path))
+            .ok()
+            .map(|ix| &self.tracks[ix])
+    }
+
+    pub fn paths(&self, group: &Group) -> Vec<PathBuf> {
+        group
+            .tracks
+            .iter()
+            .filter_map(|ix| self.tracks.get(*ix))
+            .map(|track| track.path.clone())
+            .collect()
+    }
+}
+
+fn group<K: Ord>(groups: &mut BTreeMap<K, Group>, key: K, name: &str, artist: &str, ix: usize) {
+    groups
+        .entry(key)
+        .or_insert_with(|| Group {
+            name: name.to_string(),
+            artist: artist.to_string(),
+            tracks: Vec::new(),
+        })
+        .tracks
+        .push(ix);
+}
+
+/// Missing tags are read as `"None"`.
+pub(crate) fn is_known(value: &str) -> bool {
+    !value.is_empty() && value != "None"
+}
+
+pub fn is_audio(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            AUDIO_EXTENSIONS
+                .iter()
+                .any(|known| known.eq_ignore_ascii_case(ext))
+        })
+}
+
+/// Walks every root and reads the tags of each audio file found. Files whose
+/// tags can't be read are skipped.
+pub fn scan(roots: &[PathBuf]) -> Vec<Track> {
+    let mut tracks: Vec<Track> = roots
+        .iter()
+        .flat_map(|root| WalkDir::new(root).follow_links(true))
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file() && is_audio(entry.path()))
+        .filter_map(|entry| {
+            let path = entry.into_path();
+            Metadata::read(path.clone())
+                .ok()
+                .map(|meta| Track { path, meta })
+        })
+        .collect();
+
+    tracks.sort_by(|a, b| a.path.cmp(&b.path));
+    tracks.dedup_by(|a, b| a.path == b.path);
+    tracks
+}
+
+/// Turns dropped or opened paths into tracks to queue: folders are walked
+/// in natural order, playlists resolved and anything else that isn't audio
+/// skipped. Playlists that can't be read are returned as errors.
+pub fn expand(paths: &[PathBuf]) -> (Vec<PathBuf>, Vec<anyhow::Error>) {
+    let mut tracks = Vec::new();
+    let mut errors = Vec::new();
+
+    for path in paths {
+        if path.is_dir() {
+            tracks.extend(
+                WalkDir::new(path)
+                    .follow_links(true)
+                    .sort_by(|a, b| {
+                        natural_cmp(
+                            &a.file_name().to_string_lossy(),
+                            &b.file_name().to_string_lossy(),
+                        )
+                    })
+                    .into_iter()
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.file_type().is_file() && is_audio(entry.path()))
+                    .map(|entry| entry.into_path()),
+            );
+        } else if playlist::is_playlist(path) {
+            match playlist::read(path) {
+                Ok(entries) => tracks.extend(entries.into_iter().filter(|entry| is_audio(entry))),
+                Err(err) => errors.push(err),
+            }
+        } else if is_audio(path) {
+            tracks.push(path.clone());
+        }
+    }
+
+    (tracks, errors)
+}
+
+/// Compares names the way people expect, so "Track 2" sorts before
+/// "Track 10": runs of digits compare by value, the rest ignoring case.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = digits(&mut a);
+                let y = digits(&mut b);
+                let ordering = x
+                    .trim_start_matches('0')
+                    .len()
+                    .cmp(&y.trim_start_matches('0').len())
+                    .then_with(|| x.trim_start_matches('0').cmp(y.trim_start_matches('0')))
+                    .then_with(|| x.len().cmp(&y.len()));
+
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+fn digits(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        digits.push(c);
+    }
+    digits
+}
+
+/// Rescans the configured roots in the background and replaces the library
+/// once done. Grouping and indexing happen in the background too, as they
+/// take a while on large libraries. Only the latest of overlapping scans
+/// is kept, as earlier ones may have read outdated roots.
+pub fn rescan(cx: &mut App) {
+    let roots = cx.global::<Config>().library.roots.clone();
+    let library = cx.global_mut::<Library>();
+    library.scanning = true;
+    library.scan += 1;
+    let generation = library.scan;
+
+    let scan = cx.background_executor().spawn(async move {
+        let mut library = Library {
+            scan: generation,
+            ..Library::default()
+        };
+        library.set_tracks(scan(&roots));
+        library
+    });
+    cx.spawn(async move |cx| {
+        let library = scan.await;
+        let _ = cx.update(|cx| {
+            if cx.global::<Library>().scan == generation {
+                cx.set_global(library);
+            }
+        });
+    })
+    .detach();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn natural_cmp_compares_numbers_by_value() {
+        assert_eq!(natural_cmp("track2", "track10"), Ordering::Less);
+        assert_eq!(natural_cmp("track10", "track2"), Ordering::Greater);
+        assert_eq!(natural_cmp("Disc 1 - 9", "Disc 1 - 10"), Ordering::Less);
+        // Equal values fall back to the number of leading zeros.
+        assert_eq!(natural_cmp("track02", "track2"), Ordering::Greater);
+        assert_eq!(natural_cmp("track", "track1"), Ordering::Less);
+    }
+
+    #[test]
+    fn natural_cmp_ignores_case() {
+        assert_eq!(natural_cmp("Track 2", "track 2"), Ordering::Equal);
+        assert_eq!(natural_cmp("apple", "Banana"), Ordering::Less);
+
+        let mut names = ["b10.mp3", "B2.mp3", "a1.mp3", "A10.mp3"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(names, ["a1.mp3", "A10.mp3", "B2.mp3", "b10.mp3"]);
+    }
+
+    #[test]
+    fn expand_walks_nested_folders_in_order() {
+        let dir = std::env::temp_dir().join(format!("wiremann-{}-expand", std::process::id()));
+        let album = dir.join("album");
+        for file in [
+            "10 outro.flac",
+            "2 intro.flac",
+            "cover.jpg",
+            "Disc 10/1 last.mp3",
+            "Disc 2/10 b.mp3",
+            "Disc 2/9 a.MP3",
+        ] {
+            let path = album.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        let single = dir.join("single.ogg");
+        fs::write(&single, "").unwrap();
+
+        let (tracks, errors) = expand(&[album.clone(), dir.join("notes.txt"), single.clone()]);
+
+        assert!(errors.is_empty());
+        assert_eq!(
+            tracks,
+            [
+                album.join("2 intro.flac"),
+                album.join("10 outro.flac"),
+                album.join("Disc 2/9 a.MP3"),
+                album.join("Disc 2/10 b.mp3"),
+                album.join("Disc 10/1 last.mp3"),
+                single,
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
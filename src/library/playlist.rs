@@ -0,0 +1,146 @@
+use crate::ui::components::notifications::report_error;
+use anyhow::{Context, Result};
+use gpui::{App, Global};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Playlist {
+    pub name: String,
+    pub tracks: Vec<PathBuf>,
+}
+
+/// The user's playlists, each stored as an M3U8 file in the playlists
+/// directory.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Playlists {
+    pub playlists: Vec<Playlist>,
+}
+
+impl Global for Playlists {}
+
+impl Playlists {
+    pub fn dir() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("wiremann").join("playlists"))
+    }
+
+    pub fn load() -> Result<Self> {
+        let Some(dir) = Self::dir().filter(|dir| dir.exists()) else {
+            return Ok(Playlists::default());
+        };
+
+        let mut playlists = Vec::new();
+        for entry in
+            fs::read_dir(&dir).with_context(|| format!("could not read {}", dir.display()))?
+        {
+            let path = entry?.path();
+
+            if !is_playlist(&path) {
+                continue;
+            }
+
+            let Some(name) = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+            else {
+                continue;
+            };
+
+            playlists.push(Playlist {
+                name,
+                tracks: read(&path)?,
+            });
+        }
+
+        playlists.sort_by_key(|playlist| playlist.name.to_lowercase());
+        Ok(Playlists { playlists })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Playlist> {
+        self.playlists.iter().find(|playlist| playlist.name == name)
+    }
+
+    /// Appends tracks to a playlist, creating it if needed, and saves it.
+    pub fn add(&mut self, name: &str, tracks: Vec<PathBuf>) -> Result<()> {
+        let index = match self.playlists.iter().position(|p| p.name == name) {
+            Some(index) => index,
+            None => {
+                self.playlists.push(Playlist {
+                    name: name.to_string(),
+                    tracks: Vec::new(),
+                });
+                self.playlists
+                    .sort_by_key(|playlist| playlist.name.to_lowercase());
+                self.playlists
+                    .iter()
+                    .position(|p| p.name == name)
+                    .unwrap_or_default()
+            }
+        };
+
+        let playlist = &mut self.playlists[index];
+        playlist.tracks.extend(tracks);
+        playlist.save()
+    }
+}
+
+impl Playlist {
+    pub fn path(&self) -> Option<PathBuf> {
+        Playlists::dir().map(|dir| dir.join(format!("{}.m3u8", self.name)))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = self
+            .path()
+            .context("could not determine the data directory")?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        write(&path, &self.tracks)
+    }
+}
+
+pub fn is_playlist(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("m3u") || ext.eq_ignore_ascii_case("m3u8"))
+}
+
+/// Reads an M3U playlist. Relative entries are resolved against the
+/// playlist's own folder; comments and URLs are skipped.
+pub fn read(path: &Path) -> Result<Vec<PathBuf>> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("could not read {}", path.display()))?;
+    let base = path.parent().unwrap_or(Path::new(""));
+
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.contains("://"))
+        .map(|line| base.join(line))
+        .collect())
+}
+
+pub fn write(path: &Path, tracks: &[PathBuf]) -> Result<()> {
+    let mut contents = String::from("#EXTM3U\n");
+    for track in tracks {
+        contents.push_str(&track.to_string_lossy());
+        contents.push('\n');
+    }
+
+    fs::write(path, contents).with_context(|| format!("could not write {}", path.display()))
+}
+
+/// Adds tracks to a playlist and saves it, reporting failures in the
+/// notifications.
+pub fn add_to_playlist(name: &str, tracks: Vec<PathBuf>, cx: &mut App) {
+    let result = cx.global_mut::<Playlists>().add(name, tracks);
+
+    if let Err(err) = result {
+        report_error("Could not save the playlist", err, cx);
+    }
+}
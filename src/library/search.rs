@@ -0,0 +1,470 @@
+use super::{Group, Library, Track, is_known};
+use std::{cell::RefCell, cmp::Reverse, collections::HashMap, ops::Range};
+
+/// Scores how well `query` matches `candidate` as a subsequence, ignoring
+/// case. Matches at word starts and runs of consecutive characters score
+/// higher, gaps lower. `None` when some character of the query is missing.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    let mut score = 0;
+    let mut next = 0;
+    let mut last_match: Option<usize> = None;
+    let mut prev = ' ';
+
+    for (ix, c) in candidate.chars().enumerate() {
+        if next == query.len() {
+            break;
+        }
+
+        if c.to_lowercase().eq(std::iter::once(query[next])) {
+            score += 1;
+
+            if !prev.is_alphanumeric() {
+                score += 5;
+            }
+
+            match last_match {
+                Some(last) if last + 1 == ix => score += 3,
+                Some(last) => score -= ((ix - last - 1) as i32).min(3),
+                None => score -= (ix as i32).min(3),
+            }
+
+            last_match = Some(ix);
+            next += 1;
+        }
+
+        prev = c;
+    }
+
+    (next == query.len()).then_some(score)
+}
+
+/// Most artists and albums a search returns.
+const MAX_GROUPS: usize = 5;
+/// Most tracks a search returns.
+const MAX_TRACKS: usize = 50;
+
+/// Library matches for a query, best first. Each is an index into the
+/// matching [`Library`] list.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SearchResults {
+    pub artists: Vec<usize>,
+    pub albums: Vec<usize>,
+    pub tracks: Vec<usize>,
+}
+
+impl SearchResults {
+    pub fn is_empty(&self) -> bool {
+        self.artists.is_empty() && self.albums.is_empty() && self.tracks.is_empty()
+    }
+}
+
+/// Inverted index from the words in each track's tags to the tracks, built
+/// once per scan so queries only touch the matching words.
+#[derive(Debug, Default, Clone)]
+pub struct SearchIndex {
+    /// Every distinct word, lowercased and sorted, so the words starting
+    /// with a term form one range.
+    words: Vec<String>,
+    /// For each of `words`, the tracks containing it with the weight of the
+    /// most important field it appears in, ordered by track.
+    postings: Vec<Vec<(u32, u32)>>,
+    /// Indices into `words` by their length in characters, each in sorted
+    /// order, so typo lookups only compare words of a fitting length.
+    by_len: Vec<Vec<u32>>,
+}
+
+impl SearchIndex {
+    pub fn new(tracks: &[Track]) -> Self {
+        let mut index: HashMap<String, Vec<(u32, u32)>> = HashMap::new();
+
+        for (ix, track) in tracks.iter().enumerate() {
+            let meta = &track.meta;
+            let fields = [
+                (meta.title.as_str(), 3),
+                (meta.album.as_str(), 2),
+                (meta.album_artist.as_str(), 2),
+                (meta.genre.as_str(), 1),
+                (meta.writer.as_str(), 1),
+                (meta.comment.as_str(), 1),
+            ]
+            .into_iter()
+            .chain(meta.artists.iter().map(|artist| (artist.as_str(), 2)));
+
+            for (value, weight) in fields.filter(|(value, _)| is_known(value)) {
+                for word in words(value) {
+                    let postings = index.entry(word).or_default();
+                    match postings.last_mut() {
+                        Some(last) if last.0 == ix as u32 => last.1 = last.1.max(weight),
+                        _ => postings.push((ix as u32, weight)),
+                    }
+                }
+            }
+        }
+
+        let mut index: Vec<(String, Vec<(u32, u32)>)> = index.into_iter().collect();
+        index.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        let (words, postings): (Vec<String>, _) = index.into_iter().unzip();
+
+        let mut by_len: Vec<Vec<u32>> = Vec::new();
+        for (ix, word) in words.iter().enumerate() {
+            let len = word.chars().count();
+            if by_len.len() <= len {
+                by_len.resize_with(len + 1, Vec::new);
+            }
+            by_len[len].push(ix as u32);
+        }
+
+        SearchIndex {
+            words,
+            postings,
+            by_len,
+        }
+    }
+
+    /// Indices into `words` of the words starting with `prefix`.
+    fn prefixed(&self, prefix: &str) -> Range<usize> {
+        let start = self.words.partition_point(|word| word.as_str() < prefix);
+        let len = self.words[start..].partition_point(|word| word.starts_with(prefix));
+        start..start + len
+    }
+
+    /// Calls `f` with each word matching the term and the quality of the
+    /// match, as rated by [`Term::quality`].
+    fn matches(&self, term: &Term, mut f: impl FnMut(usize, u32)) {
+        let prefixed = self.prefixed(&term.text);
+        for ix in prefixed.clone() {
+            f(ix, if self.words[ix] == term.text { 3 } else { 2 });
+        }
+
+        if term.max_edits == 0 {
+            return;
+        }
+
+        // Words close in length may be a typo as a whole, longer ones only
+        // in the part typed so far.
+        let len = term.chars.len();
+        let shortest = len.saturating_sub(term.max_edits);
+        let longest = len + term.max_edits;
+
+        for bucket in self.by_len.iter().take(longest + 1).skip(shortest) {
+            for &ix in bucket {
+                let ix = ix as usize;
+                if !prefixed.contains(&ix) && term.close(&self.words[ix]) {
+                    f(ix, 1);
+                }
+            }
+        }
+
+        // Sorted words sharing their first characters come in a row, so
+        // each distinct beginning is only compared once.
+        for bucket in self.by_len.iter().skip(longest + 1) {
+            let mut last: Option<(&str, bool)> = None;
+
+            for &ix in bucket {
+                let ix = ix as usize;
+                let word = self.words[ix].as_str();
+                let end = word.char_indices().nth(len).map_or(word.len(), |(i, _)| i);
+                let typed = &word[..end];
+
+                let close = match last {
+                    Some((prefix, close)) if prefix == typed => close,
+                    _ => term.close(typed),
+                };
+                last = Some((typed, close));
+
+                if close && !prefixed.contains(&ix) {
+                    f(ix, 1);
+                }
+            }
+        }
+    }
+
+    /// Tracks containing every word of the query, best first. Each query
+    /// word matches words it equals, words it's a prefix of and, from four
+    /// characters on, words a typo or two away.
+    fn tracks(&self, terms: &[Term]) -> Vec<usize> {
+        let mut scores: Option<HashMap<u32, u32>> = None;
+
+        for term in terms {
+            let mut term_scores: HashMap<u32, u32> = HashMap::new();
+
+            self.matches(term, |word, quality| {
+                for (track, weight) in &self.postings[word] {
+                    let score = term_scores.entry(*track).or_default();
+                    *score = (*score).max(quality * weight);
+                }
+            });
+
+            scores = Some(match scores {
+                None => term_scores,
+                Some(scores) => scores
+                    .into_iter()
+                    .filter_map(|(track, score)| {
+                        term_scores.get(&track).map(|other| (track, score + other))
+                    })
+                    .collect(),
+            });
+        }
+
+        let mut tracks: Vec<(u32, u32)> = scores.unwrap_or_default().into_iter().collect();
+        tracks.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        tracks
+            .into_iter()
+            .take(MAX_TRACKS)
+            .map(|(track, _)| track as usize)
+            .collect()
+    }
+}
+
+impl Library {
+    pub fn search(&self, query: &str) -> SearchResults {
+        let terms: Vec<Term> = words(query).map(Term::new).collect();
+
+        if terms.is_empty() {
+            return SearchResults::default();
+        }
+
+        SearchResults {
+            artists: groups(&self.artists, &terms),
+            albums: groups(&self.albums, &terms),
+            tracks: self.index.tracks(&terms),
+        }
+    }
+}
+
+/// Groups whose name matches every term, best first.
+fn groups(groups: &[Group], terms: &[Term]) -> Vec<usize> {
+    let mut matches: Vec<(u32, usize)> = groups
+        .iter()
+        .enumerate()
+        .filter_map(|(ix, group)| {
+            let words: Vec<String> = words(&group.name).collect();
+            let score = terms.iter().try_fold(0, |score, term| {
+                let best = words.iter().filter_map(|word| term.quality(word)).max()?;
+                Some(score + best)
+            })?;
+
+            Some((score, ix))
+        })
+        .collect();
+
+    matches.sort_by_key(|&(score, _)| Reverse(score));
+    matches
+        .into_iter()
+        .take(MAX_GROUPS)
+        .map(|(_, ix)| ix)
+        .collect()
+}
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+struct Term {
+    text: String,
+    chars: Vec<char>,
+    /// Edits tolerated when looking for typos.
+    max_edits: usize,
+    /// Rows of the edit distance table, reused across words.
+    rows: RefCell<[Vec<usize>; 3]>,
+}
+
+impl Term {
+    fn new(text: String) -> Self {
+        let chars: Vec<char> = text.chars().collect();
+        let max_edits = match chars.len() {
+            0..4 => 0,
+            4..8 => 1,
+            _ => 2,
+        };
+        let rows = RefCell::new([(); 3].map(|_| vec![0; chars.len() + 1]));
+
+        Term {
+            text,
+            chars,
+            max_edits,
+            rows,
+        }
+    }
+
+    /// 3 for the same word, 2 when the term is a prefix of it and 1 for a
+    /// typo, in the whole word or in the part typed so far.
+    fn quality(&self, word: &str) -> Option<u32> {
+        if word == self.text {
+            return Some(3);
+        }
+
+        if word.starts_with(&self.text) {
+            return Some(2);
+        }
+
+        (self.max_edits > 0 && self.close(word)).then_some(1)
+    }
+
+    /// Whether `word`, or its first characters as many as the term has, are
+    /// at most `max_edits` insertions, deletions, substitutions or
+    /// transpositions away from the term.
+    fn close(&self, word: &str) -> bool {
+        let term = &self.chars;
+        let max = self.max_edits;
+        let mut rows = self.rows.borrow_mut();
+        let [before, previous, current] = &mut *rows;
+
+        for (j, distance) in previous.iter_mut().enumerate() {
+            *distance = j;
+        }
+
+        let mut last = None;
+        for (i, c) in word.chars().enumerate() {
+            let i = i + 1;
+            current[0] = i;
+            let mut row_min = i;
+
+            for j in 1..=term.len() {
+                let cost = usize::from(c != term[j - 1]);
+                let mut distance = (previous[j] + 1)
+                    .min(current[j - 1] + 1)
+                    .min(previous[j - 1] + cost);
+
+                if j > 1 && last == Some(term[j - 1]) && c == term[j - 2] {
+                    distance = distance.min(before[j - 2] + 1);
+                }
+
+                current[j] = distance;
+                row_min = row_min.min(distance);
+            }
+
+            // Every later row only grows from here.
+            if row_min > max {
+                return false;
+            }
+
+            if i == term.len() && current[term.len()] <= max {
+                return true;
+            }
+
+            std::mem::swap(before, previous);
+            std::mem::swap(previous, current);
+            last = Some(c);
+        }
+
+        previous[term.len()] <= max
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::metadata::Metadata;
+    use std::{path::PathBuf, time::Instant};
+
+    fn track(title: &str, artist: &str, album: &str) -> Track {
+        Track {
+            path: PathBuf::from(format!("/music/{title}.flac")),
+            meta: Metadata {
+                title: title.to_string(),
+                artists: vec![artist.to_string()],
+                album: album.to_string(),
+                ..Metadata::default()
+            },
+        }
+    }
+
+    fn search(index: &SearchIndex, query: &str) -> Vec<usize> {
+        let terms: Vec<Term> = words(query).map(Term::new).collect();
+        index.tracks(&terms)
+    }
+
+    fn index() -> SearchIndex {
+        SearchIndex::new(&[
+            track("Come Together", "The Beatles", "Abbey Road"),
+            track("Something", "The Beatles", "Abbey Road"),
+            track("Road to Nowhere", "Talking Heads", "Little Creatures"),
+            track("Beat It", "Michael Jackson", "Thriller"),
+            track("Comeback Kid", "Sleigh Bells", "Reign of Terror"),
+        ])
+    }
+
+    #[test]
+    fn prefixes_match_and_rank_below_whole_words() {
+        let index = index();
+
+        assert_eq!(search(&index, "beat"), [3, 0, 1]);
+        // "Something" starts with a typo of "come".
+        assert_eq!(search(&index, "come"), [0, 4, 1]);
+        assert_eq!(search(&index, "thr"), [3]);
+        assert!(search(&index, "xyz").is_empty());
+    }
+
+    #[test]
+    fn typos_match_whole_words_and_the_part_typed() {
+        let index = index();
+
+        assert_eq!(search(&index, "beatels"), [0, 1]);
+        assert_eq!(search(&index, "someting"), [1]);
+        assert_eq!(search(&index, "creatuers"), [2]);
+        // "talkin" with a swap, as a prefix of "talking".
+        assert_eq!(search(&index, "tlakin"), [2]);
+        // Too short for typos.
+        assert!(search(&index, "rdo").is_empty());
+    }
+
+    #[test]
+    fn every_term_must_match() {
+        let index = index();
+
+        assert_eq!(search(&index, "road"), [2, 0, 1]);
+        assert_eq!(search(&index, "abbey road"), [0, 1]);
+        assert_eq!(search(&index, "road talking"), [2]);
+        assert!(search(&index, "road thriller").is_empty());
+    }
+
+    #[test]
+    #[cfg_attr(debug_assertions, ignore = "timed, run with --release")]
+    fn large_libraries_search_quickly() {
+        // A deterministic mix of made-up words, so most share prefixes
+        // and typo candidates like a real library.
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        let mut word = || {
+            let syllables = [
+                "ka", "lo", "mi", "ren", "su", "tor", "va", "bel", "dri", "on",
+            ];
+            (0..2 + seed % 3)
+                .map(|_| {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 7;
+                    seed ^= seed << 17;
+                    syllables[(seed % syllables.len() as u64) as usize]
+                })
+                .collect::<String>()
+        };
+
+        let tracks: Vec<Track> = (0..100_000)
+            .map(|_| {
+                let title = format!("{} {} {}", word(), word(), word());
+                track(&title, &word(), &format!("{} {}", word(), word()))
+            })
+            .collect();
+        let index = SearchIndex::new(&tracks);
+
+        for query in ["ka", "renvasu", "lomitor bel", "drionka", "sutorvabel"] {
+            let start = Instant::now();
+            search(&index, query);
+            let elapsed = start.elapsed();
+
+            assert!(elapsed.as_millis() < 50, "{query:?} took {elapsed:?}");
+        }
+    }
+}
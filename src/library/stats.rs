@@ -0,0 +1,133 @@
+use crate::fs_util;
+use crate::ui::components::notifications::report_error;
+use anyhow::{Context, Result};
+use gpui::{App, Global, Task};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// How long the stats must stay unchanged before they are written, so a
+/// burst of ratings results in a single save.
+const SAVE_DELAY: Duration = Duration::from_secs(2);
+
+/// Play count and rating of a track. Kept apart from the tags so the files
+/// aren't rewritten on every play.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TrackStats {
+    pub plays: u32,
+    /// Rating from 1 to 5 stars, `None` if unrated.
+    pub rating: Option<u8>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Stats {
+    tracks: HashMap<PathBuf, TrackStats>,
+}
+
+impl Global for Stats {}
+
+/// The save scheduled by the last change. Replacing the task cancels the
+/// previous one.
+#[derive(Default)]
+struct PendingSave {
+    /// Whether there are changes the scheduled save hasn't picked up yet.
+    dirty: bool,
+    _task: Option<Task<()>>,
+}
+
+impl Global for PendingSave {}
+
+impl Stats {
+    pub fn path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("wiremann").join("stats.json"))
+    }
+
+    pub fn load() -> Result<Self> {
+        let Some(path) = Self::path().filter(|path| path.exists()) else {
+            return Ok(Stats::default());
+        };
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("could not read {}", path.display()))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("invalid stats in {}", path.display()))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path().context("could not determine the data directory")?;
+        fs_util::write_atomic(&path, serde_json::to_string(self)?)
+    }
+
+    pub fn get(&self, path: &Path) -> TrackStats {
+        self.tracks.get(path).copied().unwrap_or_default()
+    }
+}
+
+/// Writes changes still waiting for their save when the app quits.
+pub fn init(cx: &mut App) {
+    cx.on_app_quit(|cx| {
+        if cx
+            .try_global::<PendingSave>()
+            .is_some_and(|pending| pending.dirty)
+        {
+            cx.set_global(PendingSave::default());
+            if let Err(err) = cx.global::<Stats>().save() {
+                report_error("Could not save play counts", err, cx);
+            }
+        }
+        async {}
+    })
+    .detach();
+}
+
+/// Counts a play of the track. The stats are saved in the background once
+/// they settle.
+pub fn record_play(path: &Path, cx: &mut App) {
+    update(cx, |stats| {
+        stats.tracks.entry(path.to_path_buf()).or_default().plays += 1;
+    });
+}
+
+/// Rates the tracks from 1 to 5 stars, or clears their rating with `None`.
+pub fn set_rating(paths: &[PathBuf], rating: Option<u8>, cx: &mut App) {
+    let rating = rating.map(|rating| rating.clamp(1, 5));
+
+    update(cx, |stats| {
+        for path in paths {
+            stats.tracks.entry(path.clone()).or_default().rating = rating;
+        }
+    });
+}
+
+fn update(cx: &mut App, f: impl FnOnce(&mut Stats)) {
+    let stats = cx.global_mut::<Stats>();
+    f(stats);
+    stats
+        .tracks
+        .retain(|_, stats| *stats != TrackStats::default());
+
+    let task = cx.spawn(async move |cx| {
+        cx.background_executor().timer(SAVE_DELAY).await;
+        let Ok(stats) = cx.update(|cx| {
+            cx.global_mut::<PendingSave>().dirty = false;
+            cx.global::<Stats>().clone()
+        }) else {
+            return;
+        };
+
+        let save = cx.background_executor().spawn(async move { stats.save() });
+        if let Err(err) = save.await {
+            let _ = cx.update(|cx| report_error("Could not save play counts", err, cx));
+        }
+    });
+    cx.set_global(PendingSave {
+        dirty: true,
+        _task: Some(task),
+    });
+}
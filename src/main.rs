@@ -1,6 +1,11 @@
 pub mod app;
 pub mod audio;
+pub mod config;
 pub mod controller;
+pub mod fs_util;
+pub mod integrations;
+pub mod library;
+pub mod session;
 pub mod ui;
 
 fn main() {
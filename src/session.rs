@@ -0,0 +1,159 @@
+use crate::audio::engine::RepeatMode;
+use crate::controller::player::{AudioCommand, Controller, PlayerState};
+use crate::fs_util;
+use crate::ui::components::notifications::report_error;
+use anyhow::{Context, Result};
+use gpui::{App, Bounds, Window, WindowBounds, point, px, size};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf, time::Duration};
+
+/// Playback and window state carried across restarts.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Session {
+    pub queue: Vec<PathBuf>,
+    pub index: Option<usize>,
+    pub position: Duration,
+    pub volume: f32,
+    pub muted: bool,
+    pub repeat: RepeatMode,
+    pub shuffle: bool,
+    pub window: Option<WindowGeometry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct WindowGeometry {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub maximized: bool,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session {
+            queue: Vec::new(),
+            index: None,
+            position: Duration::ZERO,
+            volume: 1.0,
+            muted: false,
+            repeat: RepeatMode::Off,
+            shuffle: false,
+            window: None,
+        }
+    }
+}
+
+impl Session {
+    pub fn path() -> Option<PathBuf> {
+        dirs::state_dir()
+            .or_else(dirs::data_local_dir)
+            .map(|dir| dir.join("wiremann").join("session.toml"))
+    }
+
+    pub fn load() -> Result<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Session::default());
+        };
+
+        if !path.exists() {
+            return Ok(Session::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("could not read {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("invalid session in {}", path.display()))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path().context("could not determine the state directory")?;
+        fs_util::write_atomic(&path, toml::to_string_pretty(self)?)
+    }
+
+    pub fn capture(controller: &Controller, window: Option<WindowBounds>) -> Self {
+        let state = &controller.state;
+
+        Session {
+            queue: state.queue.clone(),
+            index: state.index,
+            position: controller.position(),
+            volume: state.volume,
+            muted: state.muted,
+            repeat: state.repeat,
+            shuffle: state.shuffle,
+            window: window.map(WindowGeometry::from),
+        }
+    }
+
+    /// The state the UI should show before the engine reports back.
+    pub fn player_state(&self) -> PlayerState {
+        PlayerState {
+            volume: self.volume,
+            muted: self.muted,
+            repeat: self.repeat,
+            shuffle: self.shuffle,
+            ..PlayerState::default()
+        }
+    }
+
+    pub fn restore(&self, controller: &Controller) {
+        let commands = [
+            AudioCommand::Volume(self.volume),
+            AudioCommand::Mute(self.muted),
+            AudioCommand::SetRepeat(self.repeat),
+            AudioCommand::SetShuffle(self.shuffle),
+            AudioCommand::Restore {
+                queue: self.queue.clone(),
+                index: self.index,
+                position: self.position,
+            },
+        ];
+
+        for command in commands {
+            let _ = controller.audio_tx.send(command);
+        }
+    }
+}
+
+impl From<WindowBounds> for WindowGeometry {
+    fn from(bounds: WindowBounds) -> Self {
+        let (rect, maximized) = match bounds {
+            WindowBounds::Windowed(rect) => (rect, false),
+            WindowBounds::Maximized(rect) | WindowBounds::Fullscreen(rect) => (rect, true),
+        };
+
+        WindowGeometry {
+            x: f32::from(rect.origin.x),
+            y: f32::from(rect.origin.y),
+            width: f32::from(rect.size.width),
+            height: f32::from(rect.size.height),
+            maximized,
+        }
+    }
+}
+
+impl From<WindowGeometry> for WindowBounds {
+    fn from(geometry: WindowGeometry) -> Self {
+        let rect = Bounds {
+            origin: point(px(geometry.x), px(geometry.y)),
+            size: size(px(geometry.width), px(geometry.height)),
+        };
+
+        if geometry.maximized {
+            WindowBounds::Maximized(rect)
+        } else {
+            WindowBounds::Windowed(rect)
+        }
+    }
+}
+
+/// Saves the player and the main window's bounds, reporting failures in the
+/// notifications.
+pub fn save(window: &Window, cx: &mut App) {
+    let session = Session::capture(cx.global::<Controller>(), Some(window.window_bounds()));
+
+    if let Err(err) = session.save() {
+        report_error("Could not save the session", err, cx);
+    }
+}
@@ -0,0 +1,198 @@
+use crate::controller::metadata::Metadata;
+use crate::ui::theme::Theme;
+use gpui::{App, Global, Hsla, Rgba};
+use std::path::{Path, PathBuf};
+
+/// WCAG contrast asked of text drawn on (or next to) derived colors.
+const MIN_CONTRAST: f32 = 4.5;
+
+/// Hue buckets used to find the cover's most prominent vibrant color.
+const HUE_BUCKETS: usize = 24;
+
+/// Colors picked from a cover image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    /// Average color of the most common hue, greys included.
+    pub dominant: Rgba,
+    /// Most prominent saturated color, if the cover has one.
+    pub vibrant: Option<Rgba>,
+}
+
+/// Palette of the current track's cover art. `path` is the track it belongs
+/// to, so a slow extraction can't overwrite the palette of a newer track.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AlbumPalette {
+    pub path: Option<PathBuf>,
+    pub palette: Option<Palette>,
+}
+
+impl Global for AlbumPalette {}
+
+impl Palette {
+    pub fn from_image(bytes: &[u8]) -> Option<Self> {
+        let image = image::load_from_memory(bytes)
+            .ok()?
+            .thumbnail(64, 64)
+            .to_rgb8();
+
+        let mut all = [Bucket::default(); HUE_BUCKETS];
+        let mut vibrant = [Bucket::default(); HUE_BUCKETS];
+
+        for pixel in image.pixels() {
+            let [r, g, b] = pixel.0;
+            let rgba = Rgba {
+                r: r as f32 / 255.0,
+                g: g as f32 / 255.0,
+                b: b as f32 / 255.0,
+                a: 1.0,
+            };
+            let hsla = Hsla::from(rgba);
+            let bucket = ((hsla.h * HUE_BUCKETS as f32) as usize).min(HUE_BUCKETS - 1);
+
+            all[bucket].add(rgba, hsla.s);
+            if hsla.s >= 0.35 && (0.2..=0.8).contains(&hsla.l) {
+                vibrant[bucket].add(rgba, hsla.s);
+            }
+        }
+
+        let dominant = all
+            .iter()
+            .max_by_key(|bucket| bucket.count)
+            .filter(|bucket| bucket.count > 0)?
+            .average();
+
+        // Weighting by saturation favours a small vivid patch over a large
+        // washed-out one, which reads better as an accent.
+        let vibrant = vibrant
+            .iter()
+            .filter(|bucket| bucket.count > 0)
+            .max_by(|a, b| a.saturation.total_cmp(&b.saturation))
+            .map(Bucket::average);
+
+        Some(Palette { dominant, vibrant })
+    }
+
+    /// Reads the cover of the track at `path` and extracts its palette.
+    pub fn from_track(path: &Path) -> Option<Self> {
+        Metadata::cover(path).and_then(|bytes| Self::from_image(&bytes))
+    }
+
+    /// Overrides the theme's accent and highlight with colors from this
+    /// palette, adjusted until `theme.text` stays readable on them. Colors
+    /// that can't be made readable are left as the theme had them.
+    pub fn apply(&self, theme: &mut Theme) {
+        let color = self.vibrant.unwrap_or(self.dominant);
+
+        if let Some(accent) = with_contrast(color, theme.text) {
+            theme.accent = accent;
+
+            let highlighted = mix(theme.panel, accent, 0.2);
+            if contrast(highlighted, theme.text) >= MIN_CONTRAST {
+                theme.highlighted = highlighted;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Bucket {
+    count: u32,
+    r: f32,
+    g: f32,
+    b: f32,
+    saturation: f32,
+}
+
+impl Bucket {
+    fn add(&mut self, color: Rgba, saturation: f32) {
+        self.count += 1;
+        self.r += color.r;
+        self.g += color.g;
+        self.b += color.b;
+        self.saturation += saturation;
+    }
+
+    fn average(&self) -> Rgba {
+        let count = self.count.max(1) as f32;
+        Rgba {
+            r: self.r / count,
+            g: self.g / count,
+            b: self.b / count,
+            a: 1.0,
+        }
+    }
+}
+
+/// Starts extracting the palette of a newly loaded track in the background.
+/// The previous palette is dropped right away so a track without a cover
+/// doesn't keep the last one's colors.
+pub fn load(path: PathBuf, cx: &mut App) {
+    cx.set_global(AlbumPalette {
+        path: Some(path.clone()),
+        palette: None,
+    });
+
+    let task = cx.background_executor().spawn({
+        let path = path.clone();
+        async move { Palette::from_track(&path) }
+    });
+
+    cx.spawn(async move |cx| {
+        let palette = task.await;
+        let _ = cx.update(|cx| {
+            if cx.global::<AlbumPalette>().path.as_ref() == Some(&path) {
+                cx.set_global(AlbumPalette {
+                    path: Some(path),
+                    palette,
+                });
+            }
+        });
+    })
+    .detach();
+}
+
+/// Relative luminance as defined by WCAG 2.
+fn luminance(color: Rgba) -> f32 {
+    let channel = |c: f32| {
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+
+    0.2126 * channel(color.r) + 0.7152 * channel(color.g) + 0.0722 * channel(color.b)
+}
+
+/// WCAG contrast ratio between two colors, from 1 to 21.
+pub fn contrast(a: Rgba, b: Rgba) -> f32 {
+    let (a, b) = (luminance(a), luminance(b));
+    let (light, dark) = if a > b { (a, b) } else { (b, a) };
+    (light + 0.05) / (dark + 0.05)
+}
+
+/// Darkens or lightens `color`, away from `text`, until the two meet
+/// [`MIN_CONTRAST`]. Hue and saturation are kept.
+fn with_contrast(color: Rgba, text: Rgba) -> Option<Rgba> {
+    let mut hsla = Hsla::from(color);
+    let step = if luminance(text) > 0.5 { -0.02 } else { 0.02 };
+
+    while (0.0..=1.0).contains(&hsla.l) {
+        let candidate = Rgba::from(hsla);
+        if contrast(candidate, text) >= MIN_CONTRAST {
+            return Some(candidate);
+        }
+        hsla.l += step;
+    }
+
+    None
+}
+
+fn mix(a: Rgba, b: Rgba, amount: f32) -> Rgba {
+    Rgba {
+        r: a.r + (b.r - a.r) * amount,
+        g: a.g + (b.g - a.g) * amount,
+        b: a.b + (b.b - a.b) * amount,
+        a: 1.0,
+    }
+}
@@ -1,33 +1,99 @@
+use crate::audio::engine::{PlaybackState, RepeatMode};
+use crate::config::{Config, VisualizerMode};
 use crate::controller::player::Controller;
-use crate::ui::theme::Theme;
+use crate::ui::{
+    components::{notifications::report_error, seekbar::SeekBar, visualizer::Visualizer},
+    theme::Theme,
+};
+use std::time::Duration;
 
 use crate::ui::icons::Icons;
-use gpui::*;
+use gpui::{prelude::FluentBuilder, *};
 use gpui_component::Icon;
 use gpui_component::slider::{Slider, SliderState};
 
-#[derive(Clone)]
 pub struct ControlBar {
-    pub playback_slider_state: Entity<SliderState>,
     pub vol_slider_state: Entity<SliderState>,
+    visualizer: Entity<Visualizer>,
+    seekbar: Entity<SeekBar>,
+    animation: Option<Task<()>>,
+    /// The volume last pushed into the slider.
+    volume: f32,
 }
 
 impl ControlBar {
-    pub fn new(
-        playback_slider_state: Entity<SliderState>,
-        vol_slider_state: Entity<SliderState>,
-    ) -> Self {
+    pub fn new(vol_slider_state: Entity<SliderState>, cx: &mut Context<Self>) -> Self {
+        cx.observe_global::<Controller>(|this, cx| this.sync_animation(cx))
+            .detach();
+        let seekbar = cx.new(|cx| SeekBar::new(cx));
+        cx.observe(&seekbar, |_, _, cx| cx.notify()).detach();
+
         ControlBar {
-            playback_slider_state,
             vol_slider_state,
+            visualizer: cx.new(|cx| Visualizer::new(cx)),
+            seekbar,
+            animation: None,
+            volume: f32::NAN,
+        }
+    }
+
+    /// Interpolates the position between engine updates so the seek bar
+    /// moves smoothly, but only while playing so an idle player doesn't wake
+    /// up at all.
+    fn sync_animation(&mut self, cx: &mut Context<Self>) {
+        cx.notify();
+
+        // The volume can also change from shortcuts or a restored session.
+        let volume = cx.global::<Controller>().state.volume;
+        if volume != self.volume {
+            self.volume = volume;
+            self.vol_slider_state.update(cx, |this, cx| {
+                this.set_value(volume * 100.0, cx);
+            });
         }
+
+        if cx.global::<Controller>().state.state != PlaybackState::Playing {
+            self.animation = None;
+            return;
+        }
+
+        if self.animation.is_some() {
+            return;
+        }
+
+        self.animation = Some(cx.spawn(async move |this, cx| {
+            loop {
+                cx.background_executor()
+                    .timer(Duration::from_millis(33))
+                    .await;
+
+                if this.update(cx, |_, cx| cx.notify()).is_err() {
+                    break;
+                }
+            }
+        }));
     }
 }
 
 impl Render for ControlBar {
     fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         let theme = cx.global::<Theme>();
-        let state = cx.global::<Controller>().state.clone();
+        let controller = cx.global::<Controller>();
+        let duration = controller.duration().unwrap_or_default();
+        let position = match self.seekbar.read(cx).seeking() {
+            Some(fraction) => duration.mul_f32(fraction),
+            None => controller.position(),
+        };
+        let playing = controller.state.state == PlaybackState::Playing;
+        let muted = controller.state.muted;
+        let repeat = controller.state.repeat;
+        let shuffle = controller.state.shuffle;
+        let queue_open = cx.global::<Config>().ui.layout.queue;
+        let visualizer = cx.global::<Config>().ui.visualizer != VisualizerMode::Off;
+        let (title, artists) = match &controller.state.meta {
+            Some(meta) => (meta.title.clone(), meta.artists.join(", ")),
+            None => (String::from("Nothing playing"), String::new()),
+        };
 
         div()
             .w_full()
@@ -46,23 +112,162 @@ impl Render for ControlBar {
                     .px_16()
                     .items_center()
                     .justify_center()
-                    .child(div().flex().flex_shrink_0().child(format!(
-                        "{:02}:{:02}",
-                        state.position / 60,
-                        state.position % 60
-                    )))
                     .child(
                         div()
                             .flex()
-                            .w_full()
-                            .flex_1()
-                            .child(Slider::new(&self.vol_slider_state)),
+                            .flex_shrink_0()
+                            .child(format_duration(position)),
                     )
-                    .child(div().flex().flex_shrink_0().child(format!(
-                        "{:02}:{:02}",
-                        state.duration / 60,
-                        state.duration % 60
-                    ))),
+                    .child(div().flex().h_10().flex_1().child(self.seekbar.clone()))
+                    .child(
+                        div()
+                            .flex()
+                            .flex_shrink_0()
+                            .child(format_duration(duration)),
+                    ),
+            )
+            .child(
+                div()
+                    .w_full()
+                    .h_1_2()
+                    .flex()
+                    .px_16()
+                    .items_center()
+                    .justify_between()
+                    .child(
+                        div()
+                            .w_1_3()
+                            .flex()
+                            .gap_3()
+                            .items_center()
+                            .overflow_hidden()
+                            .when(visualizer, |this| this.child(self.visualizer.clone()))
+                            .child(
+                                div()
+                                    .flex()
+                                    .flex_col()
+                                    .min_w_0()
+                                    .child(div().truncate().text_color(theme.text).child(title))
+                                    .child(
+                                        div()
+                                            .truncate()
+                                            .text_size(theme.font_size_sm)
+                                            .text_color(theme.text_muted)
+                                            .child(artists),
+                                    ),
+                            ),
+                    )
+                    .child(
+                        div()
+                            .flex()
+                            .flex_shrink_0()
+                            .gap_2()
+                            .items_center()
+                            .justify_center()
+                            .child(
+                                control_button("previous", Icons::SkipBack, theme)
+                                    .on_click(|_, _, cx| cx.global::<Controller>().previous()),
+                            )
+                            .child(
+                                control_button(
+                                    "play_pause",
+                                    if playing { Icons::Pause } else { Icons::Play },
+                                    theme,
+                                )
+                                .bg(theme.accent)
+                                .hover(|this| this.bg(theme.accent))
+                                .on_click(|_, _, cx| cx.global::<Controller>().play_pause()),
+                            )
+                            .child(
+                                control_button("stop", Icons::Stop, theme)
+                                    .on_click(|_, _, cx| cx.global::<Controller>().stop()),
+                            )
+                            .child(
+                                control_button("next", Icons::SkipForward, theme)
+                                    .on_click(|_, _, cx| cx.global::<Controller>().next()),
+                            ),
+                    )
+                    .child(
+                        div()
+                            .w_1_3()
+                            .flex()
+                            .gap_2()
+                            .items_center()
+                            .justify_end()
+                            .child(
+                                toggle_button("queue", Icons::MusicList, queue_open, theme)
+                                    .on_click(|_, _, cx| {
+                                        let toggled = Config::update(cx, |config| {
+                                            config.ui.layout.queue = !config.ui.layout.queue
+                                        });
+                                        if let Err(err) = toggled {
+                                            report_error("Could not update config", err, cx);
+                                        }
+                                    }),
+                            )
+                            .child(
+                                toggle_button("shuffle", Icons::Shuffle, shuffle, theme).on_click(
+                                    |_, _, cx| cx.global::<Controller>().toggle_shuffle(),
+                                ),
+                            )
+                            .child(
+                                toggle_button(
+                                    "repeat",
+                                    if repeat == RepeatMode::One {
+                                        Icons::RepeatOne
+                                    } else {
+                                        Icons::Repeat
+                                    },
+                                    repeat != RepeatMode::Off,
+                                    theme,
+                                )
+                                .on_click(|_, _, cx| cx.global::<Controller>().cycle_repeat()),
+                            )
+                            .child(
+                                control_button(
+                                    "mute",
+                                    if muted {
+                                        Icons::VolumeMuted
+                                    } else {
+                                        Icons::Volume
+                                    },
+                                    theme,
+                                )
+                                .on_click(|_, _, cx| cx.global::<Controller>().toggle_mute()),
+                            )
+                            .child(
+                                div()
+                                    .w_32()
+                                    .flex()
+                                    .child(Slider::new(&self.vol_slider_state)),
+                            ),
+                    ),
             )
     }
 }
+
+fn control_button(id: &'static str, icon: Icons, theme: &Theme) -> Stateful<Div> {
+    toggle_button(id, icon, false, theme)
+}
+
+fn toggle_button(id: &'static str, icon: Icons, active: bool, theme: &Theme) -> Stateful<Div> {
+    div()
+        .id(id)
+        .size_9()
+        .rounded(theme.radius)
+        .flex()
+        .flex_shrink_0()
+        .items_center()
+        .justify_center()
+        .hover(|this| this.bg(theme.highlighted))
+        .child(
+            Icon::new(icon)
+                .size_5()
+                .text_color(if active { theme.accent } else { theme.text }),
+        )
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
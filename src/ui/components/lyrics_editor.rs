@@ -0,0 +1,440 @@
+use super::palette::{SelectNext, SelectPrevious};
+use crate::controller::{
+    lyrics::{self, CurrentLyrics, LyricLine, Lyrics},
+    player::Controller,
+};
+use crate::ui::theme::Theme;
+use gpui::{prelude::FluentBuilder, *};
+use gpui_component::input::{Input, InputState};
+use std::{path::PathBuf, time::Duration};
+
+const CONTEXT: &str = "LyricsEditor";
+
+/// How far the nudge shortcuts move a timestamp, in milliseconds.
+const NUDGE_STEP: i64 = 100;
+
+actions!(
+    lyrics_editor,
+    [StampLine, ClearStamp, NudgeEarlier, NudgeLater]
+);
+
+/// Binds the keys used while timing lines. The context is only set once the
+/// text has been entered, so they don't get in the way of typing.
+pub fn init(cx: &mut App) {
+    cx.bind_keys([
+        KeyBinding::new("enter", StampLine, Some(CONTEXT)),
+        KeyBinding::new("backspace", ClearStamp, Some(CONTEXT)),
+        KeyBinding::new("[", NudgeEarlier, Some(CONTEXT)),
+        KeyBinding::new("]", NudgeLater, Some(CONTEXT)),
+        KeyBinding::new("down", SelectNext, Some(CONTEXT)),
+        KeyBinding::new("up", SelectPrevious, Some(CONTEXT)),
+    ]);
+}
+
+#[derive(Clone, Copy)]
+enum Target {
+    Sidecar,
+    Tags,
+}
+
+/// Writes lyrics for a track, optionally timed: paste the text, then stamp
+/// each line at the playback position as it's sung and fine-tune the
+/// timestamps before saving.
+///
+/// Emits [`DismissEvent`] once saved or cancelled.
+pub struct LyricsEditor {
+    path: PathBuf,
+    /// The text being entered. While set, it's shown instead of the lines.
+    text: Option<Entity<InputState>>,
+    lines: Vec<LyricLine>,
+    /// The line the next stamp goes to.
+    selected: usize,
+    focus_handle: FocusHandle,
+    scroll_handle: ScrollHandle,
+    saving: bool,
+    error: Option<String>,
+}
+
+impl EventEmitter<DismissEvent> for LyricsEditor {}
+
+impl LyricsEditor {
+    pub fn new(
+        path: PathBuf,
+        lyrics: Option<Lyrics>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        cx.observe_global::<Controller>(|_, cx| cx.notify())
+            .detach();
+
+        let lines = lyrics.map(|lyrics| lyrics.lines).unwrap_or_default();
+        let synced = lines.first().is_some_and(|line| line.time.is_some());
+
+        let mut editor = LyricsEditor {
+            path,
+            text: None,
+            lines,
+            selected: 0,
+            focus_handle: cx.focus_handle(),
+            scroll_handle: ScrollHandle::new(),
+            saving: false,
+            error: None,
+        };
+
+        if synced {
+            window.focus(&editor.focus_handle);
+        } else {
+            editor.edit_text(window, cx);
+        }
+
+        editor
+    }
+
+    /// Whether the text field is taking keyboard input.
+    pub fn is_typing(&self) -> bool {
+        self.text.is_some()
+    }
+
+    fn edit_text(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let value = Lyrics {
+            lines: self.lines.clone(),
+        }
+        .to_plain();
+        let text = cx.new(|cx| {
+            InputState::new(window, cx)
+                .multi_line()
+                .placeholder("Paste the lyrics, one line per line")
+                .default_value(value)
+        });
+
+        window.focus(&text.focus_handle(cx));
+        self.text = Some(text);
+        self.error = None;
+        cx.notify();
+    }
+
+    /// Splits the text into lines to time. Lines that didn't change keep
+    /// their timestamps.
+    fn start_syncing(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(text) = self.text.take() else {
+            return;
+        };
+
+        let old = std::mem::take(&mut self.lines);
+        self.lines = text
+            .read(cx)
+            .value()
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .enumerate()
+            .map(|(ix, text)| match old.get(ix) {
+                Some(line) if line.text == text => line.clone(),
+                _ => LyricLine {
+                    time: None,
+                    text: text.to_string(),
+                    words: Vec::new(),
+                },
+            })
+            .collect();
+
+        self.selected = self
+            .lines
+            .iter()
+            .position(|line| line.time.is_none())
+            .unwrap_or(0);
+        window.focus(&self.focus_handle);
+        cx.notify();
+    }
+
+    fn select(&mut self, index: usize, cx: &mut Context<Self>) {
+        if index < self.lines.len() {
+            self.selected = index;
+            self.scroll_handle.scroll_to_item(index);
+            cx.notify();
+        }
+    }
+
+    fn select_next(&mut self, _: &SelectNext, _: &mut Window, cx: &mut Context<Self>) {
+        self.select(self.selected + 1, cx);
+    }
+
+    fn select_previous(&mut self, _: &SelectPrevious, _: &mut Window, cx: &mut Context<Self>) {
+        if let Some(index) = self.selected.checked_sub(1) {
+            self.select(index, cx);
+        }
+    }
+
+    /// Times the selected line at the playback position and moves on to the
+    /// next one.
+    fn stamp(&mut self, _: &StampLine, _: &mut Window, cx: &mut Context<Self>) {
+        let position = cx.global::<Controller>().position();
+        let Some(line) = self.lines.get_mut(self.selected) else {
+            return;
+        };
+
+        retime(line, position);
+        cx.notify();
+        self.select(self.selected + 1, cx);
+    }
+
+    fn clear_stamp(&mut self, _: &ClearStamp, _: &mut Window, cx: &mut Context<Self>) {
+        if let Some(line) = self.lines.get_mut(self.selected) {
+            line.time = None;
+            line.words.clear();
+            cx.notify();
+        }
+    }
+
+    fn nudge_earlier(&mut self, _: &NudgeEarlier, _: &mut Window, cx: &mut Context<Self>) {
+        self.nudge(-NUDGE_STEP, cx);
+    }
+
+    fn nudge_later(&mut self, _: &NudgeLater, _: &mut Window, cx: &mut Context<Self>) {
+        self.nudge(NUDGE_STEP, cx);
+    }
+
+    fn nudge(&mut self, millis: i64, cx: &mut Context<Self>) {
+        let Some(line) = self.lines.get_mut(self.selected) else {
+            return;
+        };
+        let Some(time) = line.time else {
+            return;
+        };
+
+        let ms = (time.as_millis() as i64 + millis).max(0);
+        retime(line, Duration::from_millis(ms as u64));
+        cx.notify();
+    }
+
+    fn save(&mut self, target: Target, window: &mut Window, cx: &mut Context<Self>) {
+        if self.text.is_some() {
+            self.start_syncing(window, cx);
+        }
+
+        let unstamped = self.lines.iter().filter(|line| line.time.is_none()).count();
+        if unstamped > 0 && unstamped < self.lines.len() {
+            self.error = Some(match unstamped {
+                1 => String::from("1 line has no timestamp yet"),
+                n => format!("{n} lines have no timestamp yet"),
+            });
+            cx.notify();
+            return;
+        }
+
+        let mut lines = self.lines.clone();
+        lines.sort_by_key(|line| line.time);
+        let lyrics = Lyrics { lines };
+
+        self.saving = true;
+        self.error = None;
+        cx.notify();
+
+        let path = self.path.clone();
+        let task = cx.background_executor().spawn(async move {
+            match target {
+                Target::Sidecar => lyrics.write_sidecar(&path),
+                Target::Tags => lyrics.write_tags(&path),
+            }
+        });
+
+        cx.spawn(async move |this, cx| {
+            let result = task.await;
+            let _ = this.update(cx, |this, cx| match result {
+                Ok(()) => {
+                    // Shows the new lyrics, unless another track started.
+                    if cx.global::<CurrentLyrics>().path.as_ref() == Some(&this.path) {
+                        lyrics::load(this.path.clone(), cx);
+                    }
+                    cx.emit(DismissEvent);
+                }
+                Err(err) => {
+                    this.saving = false;
+                    this.error = Some(format!("{err:#}"));
+                    cx.notify();
+                }
+            });
+        })
+        .detach();
+    }
+
+    fn render_lines(&self, cx: &Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+
+        div()
+            .id("lyrics_editor_lines")
+            .flex_1()
+            .min_h_0()
+            .flex()
+            .flex_col()
+            .overflow_y_scroll()
+            .track_scroll(&self.scroll_handle)
+            .children(self.lines.iter().enumerate().map(|(index, line)| {
+                let time = line.time;
+
+                div()
+                    .id(("lyrics_editor_line", index))
+                    .flex()
+                    .flex_shrink_0()
+                    .gap_4()
+                    .px_2()
+                    .py_1()
+                    .rounded(theme.radius)
+                    .when(index == self.selected, |this| this.bg(theme.highlighted))
+                    .hover(|this| this.bg(theme.highlighted))
+                    .on_click(cx.listener(move |this, _, window, cx| {
+                        window.focus(&this.focus_handle);
+                        this.select(index, cx);
+                    }))
+                    .child(
+                        div()
+                            .id(("lyrics_editor_time", index))
+                            .w_20()
+                            .flex_shrink_0()
+                            .text_color(if time.is_some() {
+                                theme.accent
+                            } else {
+                                theme.text_muted
+                            })
+                            .child(
+                                time.map_or_else(|| String::from("--:--.--"), lyrics::format_time),
+                            )
+                            // Plays from the timestamp to check it.
+                            .when_some(time, |this, time| {
+                                this.cursor_pointer()
+                                    .on_click(move |_, _, cx| cx.global::<Controller>().seek(time))
+                            }),
+                    )
+                    .child(div().flex_1().min_w_0().child(line.text.clone()))
+            }))
+    }
+}
+
+impl Render for LyricsEditor {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let position = lyrics::format_time(cx.global::<Controller>().position());
+        let syncing = self.text.is_none();
+
+        div()
+            .id("lyrics_editor")
+            .when(syncing, |this| this.key_context(CONTEXT))
+            .track_focus(&self.focus_handle)
+            .on_action(cx.listener(Self::stamp))
+            .on_action(cx.listener(Self::clear_stamp))
+            .on_action(cx.listener(Self::nudge_earlier))
+            .on_action(cx.listener(Self::nudge_later))
+            .on_action(cx.listener(Self::select_next))
+            .on_action(cx.listener(Self::select_previous))
+            .size_full()
+            .max_w(px(720.0))
+            .mx_auto()
+            .flex()
+            .flex_col()
+            .gap_3()
+            .p_4()
+            .text_color(theme.text)
+            .child(
+                div()
+                    .flex()
+                    .items_center()
+                    .justify_between()
+                    .child(div().text_size(theme.font_size_lg).child("Edit lyrics"))
+                    .child(
+                        div()
+                            .text_size(theme.font_size_sm)
+                            .text_color(theme.text_muted)
+                            .child(position),
+                    ),
+            )
+            .child(
+                div()
+                    .text_size(theme.font_size_sm)
+                    .text_color(theme.text_muted)
+                    .child(if syncing {
+                        "Enter stamps the selected line at the current position. \
+                         [ and ] move its timestamp by 0.1 s, Backspace clears it."
+                    } else {
+                        "Paste or type the lyrics, then time them line by line, or save them \
+                         as they are."
+                    }),
+            )
+            .map(|this| match self.text.clone() {
+                Some(text) => {
+                    this.child(div().flex_1().min_h_0().child(Input::new(&text).h_full()))
+                }
+                None => this.child(self.render_lines(cx)),
+            })
+            .when_some(self.error.clone(), |this, error| {
+                this.child(
+                    div()
+                        .text_size(theme.font_size_sm)
+                        .text_color(theme.error)
+                        .child(error),
+                )
+            })
+            .child(
+                div()
+                    .flex()
+                    .flex_wrap()
+                    .justify_end()
+                    .gap_2()
+                    .child(
+                        button("lyrics_editor_cancel", "Cancel", theme)
+                            .on_click(cx.listener(|_, _, _, cx| cx.emit(DismissEvent))),
+                    )
+                    .map(|this| {
+                        if syncing {
+                            this.child(button("lyrics_editor_text", "Edit text", theme).on_click(
+                                cx.listener(|this, _, window, cx| this.edit_text(window, cx)),
+                            ))
+                        } else {
+                            this.child(button("lyrics_editor_sync", "Time lines", theme).on_click(
+                                cx.listener(|this, _, window, cx| this.start_syncing(window, cx)),
+                            ))
+                        }
+                    })
+                    .when(!self.saving, |this| {
+                        this.child(
+                            button("lyrics_editor_sidecar", "Save as .lrc", theme).on_click(
+                                cx.listener(|this, _, window, cx| {
+                                    this.save(Target::Sidecar, window, cx)
+                                }),
+                            ),
+                        )
+                        .child(
+                            button("lyrics_editor_tags", "Save to tags", theme)
+                                .bg(theme.accent)
+                                .on_click(cx.listener(|this, _, window, cx| {
+                                    this.save(Target::Tags, window, cx)
+                                })),
+                        )
+                    })
+                    .when(self.saving, |this| {
+                        this.child(button("lyrics_editor_saving", "Saving…", theme))
+                    }),
+            )
+    }
+}
+
+/// Moves a line to `time`, shifting its word timings along with it.
+fn retime(line: &mut LyricLine, time: Duration) {
+    if let Some(old) = line.time {
+        for (start, _) in &mut line.words {
+            *start = (*start + time).saturating_sub(old);
+        }
+    }
+
+    line.time = Some(time);
+}
+
+fn button(id: &'static str, label: &'static str, theme: &Theme) -> Stateful<Div> {
+    div()
+        .id(id)
+        .px_3()
+        .py_1()
+        .rounded(theme.radius)
+        .bg(theme.highlighted)
+        .hover(|this| this.bg(theme.border))
+        .child(label)
+}
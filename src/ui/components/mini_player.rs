@@ -0,0 +1,254 @@
+use super::{controlbar::format_duration, seekbar::SeekBar};
+use crate::audio::engine::PlaybackState;
+use crate::config::Config;
+use crate::controller::{metadata::Metadata, player::Controller};
+use crate::ui::{
+    icons::Icons,
+    keymap::{KEY_CONTEXT, ToggleMiniPlayer},
+    pages::library::image_format,
+    theme::Theme,
+};
+use gpui::{prelude::FluentBuilder, *};
+use gpui_component::Icon;
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+/// Size the mini player window opens at the first time.
+pub const SIZE: Size<Pixels> = Size {
+    width: px(420.0),
+    height: px(112.0),
+};
+
+/// Cover, title, transport and seek bar of the current track, shown in a
+/// small window of its own while the main one is minimized. Emits
+/// [`DismissEvent`] to go back to the full layout.
+pub struct MiniPlayer {
+    seekbar: Entity<SeekBar>,
+    focus_handle: FocusHandle,
+    /// Cover of the track at the path, `None` while loading or when the
+    /// track has none.
+    cover: Option<(PathBuf, Option<Arc<Image>>)>,
+    animation: Option<Task<()>>,
+}
+
+impl EventEmitter<DismissEvent> for MiniPlayer {}
+
+impl MiniPlayer {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        cx.observe_global::<Controller>(|this, cx| this.sync(cx))
+            .detach();
+        let seekbar = cx.new(|cx| SeekBar::new(cx));
+        cx.observe(&seekbar, |_, _, cx| cx.notify()).detach();
+
+        let mut this = MiniPlayer {
+            seekbar,
+            focus_handle: cx.focus_handle(),
+            cover: None,
+            animation: None,
+        };
+        this.sync(cx);
+        this
+    }
+
+    pub fn focus(&self, window: &mut Window) {
+        window.focus(&self.focus_handle);
+    }
+
+    /// Follows the current track's cover, and moves the position along
+    /// between engine updates while playing.
+    fn sync(&mut self, cx: &mut Context<Self>) {
+        cx.notify();
+
+        let current = cx.global::<Controller>().state.current.clone();
+        if self.cover.as_ref().map(|(path, _)| path) != current.as_ref() {
+            self.cover = None;
+            if let Some(path) = current {
+                self.load_cover(path, cx);
+            }
+        }
+
+        if cx.global::<Controller>().state.state != PlaybackState::Playing {
+            self.animation = None;
+            return;
+        }
+
+        if self.animation.is_some() {
+            return;
+        }
+
+        self.animation = Some(cx.spawn(async move |this, cx| {
+            loop {
+                cx.background_executor()
+                    .timer(Duration::from_millis(33))
+                    .await;
+
+                if this.update(cx, |_, cx| cx.notify()).is_err() {
+                    break;
+                }
+            }
+        }));
+    }
+
+    fn load_cover(&mut self, path: PathBuf, cx: &mut Context<Self>) {
+        self.cover = Some((path.clone(), None));
+
+        let task = cx.background_executor().spawn({
+            let path = path.clone();
+            async move {
+                let bytes = Metadata::cover(&path)?;
+                let format = image_format(&bytes)?;
+                Some(Arc::new(Image::from_bytes(format, bytes)))
+            }
+        });
+
+        cx.spawn(async move |this, cx| {
+            let cover = task.await;
+            let _ = this.update(cx, |this, cx| {
+                if let Some((current, image)) = &mut this.cover
+                    && *current == path
+                {
+                    *image = cover;
+                    cx.notify();
+                }
+            });
+        })
+        .detach();
+    }
+
+    fn toggle_mini_player(&mut self, _: &ToggleMiniPlayer, _: &mut Window, cx: &mut Context<Self>) {
+        cx.emit(DismissEvent);
+    }
+}
+
+impl Render for MiniPlayer {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let ui = &cx.global::<Config>().ui;
+        let controller = cx.global::<Controller>();
+        let duration = controller.duration().unwrap_or_default();
+        let position = match self.seekbar.read(cx).seeking() {
+            Some(fraction) => duration.mul_f32(fraction),
+            None => controller.position(),
+        };
+        let playing = controller.state.state == PlaybackState::Playing;
+        let cover = self.cover.as_ref().and_then(|(_, cover)| cover.clone());
+        let (title, artists) = match &controller.state.meta {
+            Some(meta) => (meta.title.clone(), meta.artists.join(", ")),
+            None => (String::from("Nothing playing"), String::new()),
+        };
+
+        div()
+            .id("mini_player")
+            .key_context(KEY_CONTEXT)
+            .track_focus(&self.focus_handle)
+            .on_action(cx.listener(Self::toggle_mini_player))
+            .size_full()
+            .flex()
+            .gap_3()
+            .p_3()
+            .bg(theme.panel)
+            .text_color(theme.text)
+            .text_size(ui.font_size.map(px).unwrap_or(theme.font_size))
+            .when_some(ui.font.clone(), |this, font| this.font_family(font))
+            .child(
+                div()
+                    .size_20()
+                    .flex_shrink_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .rounded(theme.radius)
+                    .overflow_hidden()
+                    .bg(theme.highlighted)
+                    .map(|this| match cover {
+                        Some(cover) => {
+                            this.child(img(cover).size_full().object_fit(ObjectFit::Cover))
+                        }
+                        None => this.child(
+                            Icon::new(Icons::Music)
+                                .size_8()
+                                .text_color(theme.text_muted),
+                        ),
+                    }),
+            )
+            .child(
+                div()
+                    .flex_1()
+                    .min_w_0()
+                    .h_full()
+                    .flex()
+                    .flex_col()
+                    .justify_between()
+                    .child(
+                        div()
+                            .flex()
+                            .gap_2()
+                            .child(
+                                div()
+                                    .id("mini_player_drag")
+                                    .flex_1()
+                                    .min_w_0()
+                                    .flex()
+                                    .flex_col()
+                                    .window_control_area(WindowControlArea::Drag)
+                                    .child(div().truncate().child(title))
+                                    .child(
+                                        div()
+                                            .truncate()
+                                            .text_size(theme.font_size_sm)
+                                            .text_color(theme.text_muted)
+                                            .child(artists),
+                                    ),
+                            )
+                            .child(
+                                button("expand", Icons::Expand, theme)
+                                    .on_click(cx.listener(|_, _, _, cx| cx.emit(DismissEvent))),
+                            ),
+                    )
+                    .child(
+                        div()
+                            .flex()
+                            .gap_2()
+                            .items_center()
+                            .child(
+                                button("previous", Icons::SkipBack, theme)
+                                    .on_click(|_, _, cx| cx.global::<Controller>().previous()),
+                            )
+                            .child(
+                                button(
+                                    "play_pause",
+                                    if playing { Icons::Pause } else { Icons::Play },
+                                    theme,
+                                )
+                                .bg(theme.accent)
+                                .hover(|this| this.bg(theme.accent))
+                                .on_click(|_, _, cx| cx.global::<Controller>().play_pause()),
+                            )
+                            .child(
+                                button("next", Icons::SkipForward, theme)
+                                    .on_click(|_, _, cx| cx.global::<Controller>().next()),
+                            )
+                            .child(div().flex().h_6().flex_1().child(self.seekbar.clone()))
+                            .child(
+                                div()
+                                    .flex_shrink_0()
+                                    .text_size(theme.font_size_sm)
+                                    .text_color(theme.text_muted)
+                                    .child(format_duration(position)),
+                            ),
+                    ),
+            )
+    }
+}
+
+fn button(id: &'static str, icon: Icons, theme: &Theme) -> Stateful<Div> {
+    div()
+        .id(id)
+        .size_7()
+        .rounded(theme.radius)
+        .flex()
+        .flex_shrink_0()
+        .items_center()
+        .justify_center()
+        .hover(|this| this.bg(theme.highlighted))
+        .child(Icon::new(icon).size_4().text_color(theme.text))
+}
@@ -1,12 +1,43 @@
 pub mod controlbar;
+pub mod lyrics_editor;
+pub mod mini_player;
 pub mod navbar;
+pub mod notifications;
+pub mod palette;
+pub mod queue;
+pub mod search;
+pub mod seekbar;
+pub mod shortcuts;
+pub mod tag_editor;
 pub mod titlebar;
+pub mod track_table;
+pub mod visualizer;
+pub mod waveform;
 
 #[derive(Clone, Copy, PartialEq)]
 pub enum Page {
     Home,
     Playlists,
+    Lyrics,
     Settings,
 }
 
 impl gpui::Global for Page {}
+
+/// What the library on the home page shows.
+#[derive(Clone, Default, PartialEq)]
+pub enum LibraryView {
+    #[default]
+    Tracks,
+    Artists,
+    Artist(String),
+    Albums,
+    Album {
+        name: String,
+        artist: String,
+    },
+    Genres,
+    Genre(String),
+}
+
+impl gpui::Global for LibraryView {}
@@ -35,7 +35,7 @@ impl Render for NavBar {
                 div()
                     .id("home")
                     .size_16()
-                    .rounded_md()
+                    .rounded(theme.radius)
                     .flex()
                     .flex_shrink_0()
                     .items_center()
@@ -59,7 +59,7 @@ impl Render for NavBar {
                 div()
                     .id("playlist")
                     .size_16()
-                    .rounded_md()
+                    .rounded(theme.radius)
                     .flex()
                     .flex_shrink_0()
                     .items_center()
@@ -79,6 +79,30 @@ impl Render for NavBar {
                     .on_click(|_, _, cx| *cx.global_mut::<Page>() = Page::Playlists)
                     .child(Icon::new(Icons::MusicList).size_6().text_color(theme.text)),
             )
+            .child(
+                div()
+                    .id("lyrics")
+                    .size_16()
+                    .rounded(theme.radius)
+                    .flex()
+                    .flex_shrink_0()
+                    .items_center()
+                    .justify_center()
+                    .bg(if page == &Page::Lyrics {
+                        theme.accent
+                    } else {
+                        theme.bg
+                    })
+                    .hover(|this| {
+                        if page != &Page::Lyrics {
+                            this.bg(theme.highlighted)
+                        } else {
+                            this.bg(theme.accent)
+                        }
+                    })
+                    .on_click(|_, _, cx| *cx.global_mut::<Page>() = Page::Lyrics)
+                    .child(Icon::new(Icons::Lyrics).size_6().text_color(theme.text)),
+            )
             .child(
                 div()
                     .w_full()
@@ -90,7 +114,7 @@ impl Render for NavBar {
                 div()
                     .id("settings")
                     .size_16()
-                    .rounded_md()
+                    .rounded(theme.radius)
                     .flex()
                     .flex_shrink_0()
                     .items_center()
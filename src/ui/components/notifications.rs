@@ -0,0 +1,150 @@
+use crate::ui::icons::Icons;
+use crate::ui::theme::Theme;
+use gpui::*;
+use gpui_component::Icon;
+use std::time::Duration;
+
+const AUTO_DISMISS: Duration = Duration::from_secs(8);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum NotificationLevel {
+    Info,
+    Error,
+}
+
+#[derive(Clone)]
+pub struct Notification {
+    pub id: usize,
+    pub level: NotificationLevel,
+    pub title: SharedString,
+    pub message: SharedString,
+}
+
+#[derive(Clone)]
+pub struct Notifications {
+    items: Vec<Notification>,
+    next_id: usize,
+}
+
+/// The main window's notifications, for errors raised by components that
+/// don't hold them.
+struct MainNotifications(WeakEntity<Notifications>);
+
+impl Global for MainNotifications {}
+
+impl Notifications {
+    pub fn new() -> Self {
+        Notifications {
+            items: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn push(
+        &mut self,
+        level: NotificationLevel,
+        title: impl Into<SharedString>,
+        message: impl Into<SharedString>,
+        cx: &mut Context<Self>,
+    ) {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.items.push(Notification {
+            id,
+            level,
+            title: title.into(),
+            message: message.into(),
+        });
+
+        cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(AUTO_DISMISS).await;
+            let _ = this.update(cx, |this, cx| this.dismiss(id, cx));
+        })
+        .detach();
+
+        cx.notify();
+    }
+
+    pub fn dismiss(&mut self, id: usize, cx: &mut Context<Self>) {
+        self.items.retain(|item| item.id != id);
+        cx.notify();
+    }
+
+    /// Makes these the notifications [`report_error`] shows errors in.
+    pub fn set_main(this: &Entity<Self>, cx: &mut App) {
+        cx.set_global(MainNotifications(this.downgrade()));
+    }
+}
+
+/// Shows `err` in the main window's notifications, or logs it when there
+/// are none.
+pub fn report_error(title: &'static str, err: anyhow::Error, cx: &mut App) {
+    let notifications = cx
+        .try_global::<MainNotifications>()
+        .and_then(|main| main.0.upgrade());
+
+    match notifications {
+        Some(notifications) => notifications.update(cx, |this, cx| {
+            this.push(NotificationLevel::Error, title, format!("{err:#}"), cx)
+        }),
+        None => eprintln!("{err:#}"),
+    }
+}
+
+impl Render for Notifications {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+
+        div()
+            .absolute()
+            .bottom_36()
+            .right_4()
+            .w_96()
+            .flex()
+            .flex_col()
+            .gap_2()
+            .children(self.items.iter().map(|item| {
+                let id = item.id;
+
+                div()
+                    .w_full()
+                    .flex()
+                    .gap_2()
+                    .p_3()
+                    .rounded(theme.radius)
+                    .bg(theme.highlighted)
+                    .border_1()
+                    .border_color(match item.level {
+                        NotificationLevel::Info => theme.border,
+                        NotificationLevel::Error => theme.error,
+                    })
+                    .child(
+                        div()
+                            .flex_1()
+                            .flex()
+                            .flex_col()
+                            .overflow_hidden()
+                            .child(div().text_color(theme.text).child(item.title.clone()))
+                            .child(
+                                div()
+                                    .text_size(theme.font_size_sm)
+                                    .text_color(theme.text_muted)
+                                    .child(item.message.clone()),
+                            ),
+                    )
+                    .child(
+                        div()
+                            .id(("dismiss_notification", id))
+                            .size_6()
+                            .flex_shrink_0()
+                            .flex()
+                            .items_center()
+                            .justify_center()
+                            .rounded(theme.radius)
+                            .hover(|this| this.bg(theme.border))
+                            .on_click(cx.listener(move |this, _, _, cx| this.dismiss(id, cx)))
+                            .child(Icon::new(Icons::WinClose).size_3().text_color(theme.text)),
+                    )
+            }))
+    }
+}
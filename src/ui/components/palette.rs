@@ -0,0 +1,344 @@
+use super::{LibraryView, Page};
+use crate::controller::player::Controller;
+use crate::library::{
+    Group, Library,
+    playlist::{self, Playlists},
+    search::fuzzy_score,
+};
+use crate::ui::keymap::{self, ACTIONS, Dismiss, Keymap};
+use crate::ui::theme::Theme;
+use gpui::{prelude::FluentBuilder, *};
+use gpui_component::input::{Input, InputEvent, InputState};
+
+const CONTEXT: &str = "CommandPalette";
+
+/// Results past this many aren't worth scrolling through; refine the query
+/// instead.
+const MAX_RESULTS: usize = 100;
+
+actions!(command_palette, [SelectNext, SelectPrevious]);
+
+/// Binds the keys used to move through the results. They apply while the
+/// query input has focus, taking precedence over its own bindings.
+pub fn init(cx: &mut App) {
+    let context = format!("{CONTEXT} > Input");
+    cx.bind_keys([
+        KeyBinding::new("down", SelectNext, Some(&context)),
+        KeyBinding::new("ctrl-n", SelectNext, Some(&context)),
+        KeyBinding::new("up", SelectPrevious, Some(&context)),
+        KeyBinding::new("ctrl-p", SelectPrevious, Some(&context)),
+        KeyBinding::new("escape", Dismiss, Some(&context)),
+    ]);
+}
+
+/// What a palette entry does once chosen.
+#[derive(Clone)]
+pub enum Command {
+    /// Dispatches the action at this index of [`ACTIONS`].
+    Action(usize),
+    /// Plays an album, looked up by name and album artist when run so a
+    /// rescan in the meantime doesn't play the wrong one.
+    PlayAlbum {
+        name: String,
+        artist: String,
+    },
+    /// Plays an artist's tracks, looked up by name when run.
+    PlayArtist(String),
+    PlayPlaylist(String),
+    GoTo(LibraryView),
+    AddToPlaylist(String),
+}
+
+impl Command {
+    pub fn run(self, window: &mut Window, cx: &mut App) {
+        match self {
+            Command::Action(index) => window.dispatch_action((ACTIONS[index].build)(), cx),
+            Command::PlayAlbum { name, artist } => {
+                let library = cx.global::<Library>();
+                if let Some(album) = find_group(&library.albums, &name, &artist) {
+                    cx.global::<Controller>()
+                        .play_tracks(library.paths(album), 0);
+                }
+            }
+            Command::PlayArtist(name) => {
+                let library = cx.global::<Library>();
+                if let Some(artist) = find_group(&library.artists, &name, "") {
+                    cx.global::<Controller>()
+                        .play_tracks(library.paths(artist), 0);
+                }
+            }
+            Command::PlayPlaylist(name) => {
+                if let Some(playlist) = cx.global::<Playlists>().get(&name) {
+                    cx.global::<Controller>()
+                        .play_tracks(playlist.tracks.clone(), 0);
+                }
+            }
+            Command::GoTo(view) => {
+                cx.set_global(view);
+                cx.set_global(Page::Home);
+            }
+            Command::AddToPlaylist(name) => {
+                if let Some(current) = cx.global::<Controller>().state.current.clone() {
+                    playlist::add_to_playlist(&name, vec![current], cx);
+                }
+            }
+        }
+    }
+}
+
+fn find_group<'a>(groups: &'a [Group], name: &str, artist: &str) -> Option<&'a Group> {
+    groups
+        .iter()
+        .find(|group| group.name == name && group.artist == artist)
+}
+
+struct Entry {
+    label: SharedString,
+    detail: SharedString,
+    command: Command,
+}
+
+pub enum CommandPaletteEvent {
+    Run(Command),
+    Dismissed,
+}
+
+pub struct CommandPalette {
+    input: Entity<InputState>,
+    entries: Vec<Entry>,
+    /// Indices into `entries` matching the query, best first.
+    matches: Vec<usize>,
+    selected: usize,
+    scroll_handle: ScrollHandle,
+}
+
+impl EventEmitter<CommandPaletteEvent> for CommandPalette {}
+
+impl CommandPalette {
+    pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let input = cx.new(|cx| InputState::new(window, cx).placeholder("Type a command…"));
+
+        cx.subscribe_in(
+            &input,
+            window,
+            |this, input, event: &InputEvent, _, cx| match event {
+                InputEvent::Change => {
+                    let query = input.read(cx).value().to_string();
+                    this.filter(&query);
+                    cx.notify();
+                }
+                InputEvent::PressEnter { .. } => this.confirm(this.selected, cx),
+                _ => {}
+            },
+        )
+        .detach();
+
+        window.focus(&input.focus_handle(cx));
+
+        let mut palette = CommandPalette {
+            input,
+            entries: entries(cx),
+            matches: Vec::new(),
+            selected: 0,
+            scroll_handle: ScrollHandle::new(),
+        };
+        palette.filter("");
+        palette
+    }
+
+    fn filter(&mut self, query: &str) {
+        let mut scored: Vec<(i32, usize)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(ix, entry)| fuzzy_score(query, &entry.label).map(|score| (score, ix)))
+            .collect();
+
+        // Stable, so equally good matches keep actions ahead of library
+        // entries.
+        scored.sort_by(|a, b| b.0.cmp(&a.0));
+        self.matches = scored
+            .into_iter()
+            .take(MAX_RESULTS)
+            .map(|(_, ix)| ix)
+            .collect();
+        self.selected = 0;
+        self.scroll_handle.scroll_to_item(0);
+    }
+
+    fn select_next(&mut self, _: &SelectNext, _: &mut Window, cx: &mut Context<Self>) {
+        if !self.matches.is_empty() {
+            self.selected = (self.selected + 1) % self.matches.len();
+            self.scroll_handle.scroll_to_item(self.selected);
+            cx.notify();
+        }
+    }
+
+    fn select_previous(&mut self, _: &SelectPrevious, _: &mut Window, cx: &mut Context<Self>) {
+        if !self.matches.is_empty() {
+            self.selected = self
+                .selected
+                .checked_sub(1)
+                .unwrap_or(self.matches.len() - 1);
+            self.scroll_handle.scroll_to_item(self.selected);
+            cx.notify();
+        }
+    }
+
+    fn dismiss(&mut self, _: &Dismiss, _: &mut Window, cx: &mut Context<Self>) {
+        cx.emit(CommandPaletteEvent::Dismissed);
+    }
+
+    fn confirm(&mut self, selected: usize, cx: &mut Context<Self>) {
+        if let Some(entry) = self.matches.get(selected).map(|ix| &self.entries[*ix]) {
+            cx.emit(CommandPaletteEvent::Run(entry.command.clone()));
+        }
+    }
+}
+
+/// Every action, followed by entries generated from the library and
+/// playlists.
+fn entries(cx: &App) -> Vec<Entry> {
+    let keymap = cx.global::<Keymap>();
+    let library = cx.global::<Library>();
+    let has_current = cx.global::<Controller>().state.current.is_some();
+
+    let actions = ACTIONS
+        .iter()
+        .enumerate()
+        .filter(|(_, action)| action.name != "command_palette")
+        .map(|(ix, action)| Entry {
+            label: action.description.into(),
+            detail: keymap
+                .keys(action.name)
+                .iter()
+                .map(|keys| keymap::display(keys))
+                .collect::<Vec<_>>()
+                .join(", ")
+                .into(),
+            command: Command::Action(ix),
+        });
+
+    let albums = library.albums.iter().map(|album| Entry {
+        label: format!("Play album {}", album.name).into(),
+        detail: album.artist.clone().into(),
+        command: Command::PlayAlbum {
+            name: album.name.clone(),
+            artist: album.artist.clone(),
+        },
+    });
+
+    let artists = library.artists.iter().flat_map(|artist| {
+        [
+            Entry {
+                label: format!("Play artist {}", artist.name).into(),
+                detail: format!("{} tracks", artist.tracks.len()).into(),
+                command: Command::PlayArtist(artist.name.clone()),
+            },
+            Entry {
+                label: format!("Go to artist {}", artist.name).into(),
+                detail: SharedString::default(),
+                command: Command::GoTo(LibraryView::Artist(artist.name.clone())),
+            },
+        ]
+    });
+
+    let playlists = cx
+        .global::<Playlists>()
+        .playlists
+        .iter()
+        .flat_map(|playlist| {
+            let play = Entry {
+                label: format!("Play playlist {}", playlist.name).into(),
+                detail: format!("{} tracks", playlist.tracks.len()).into(),
+                command: Command::PlayPlaylist(playlist.name.clone()),
+            };
+            let add = has_current.then(|| Entry {
+                label: format!("Add to playlist {}", playlist.name).into(),
+                detail: "Current track".into(),
+                command: Command::AddToPlaylist(playlist.name.clone()),
+            });
+
+            std::iter::once(play).chain(add)
+        });
+
+    actions
+        .chain(playlists)
+        .chain(albums)
+        .chain(artists)
+        .collect()
+}
+
+impl Focusable for CommandPalette {
+    fn focus_handle(&self, cx: &App) -> FocusHandle {
+        self.input.focus_handle(cx)
+    }
+}
+
+impl Render for CommandPalette {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+
+        div()
+            .id("command_palette")
+            .key_context(CONTEXT)
+            .on_action(cx.listener(Self::select_next))
+            .on_action(cx.listener(Self::select_previous))
+            .on_action(cx.listener(Self::dismiss))
+            .w_128()
+            .max_h_96()
+            .flex()
+            .flex_col()
+            .p_2()
+            .gap_2()
+            .rounded(theme.radius_lg)
+            .bg(theme.panel)
+            .border_1()
+            .border_color(theme.border)
+            .text_color(theme.text)
+            // Clicks inside the palette shouldn't reach the backdrop.
+            .on_click(|_, _, cx| cx.stop_propagation())
+            .child(Input::new(&self.input))
+            .child(
+                div()
+                    .id("command_palette_results")
+                    .flex()
+                    .flex_col()
+                    .overflow_y_scroll()
+                    .track_scroll(&self.scroll_handle)
+                    .when(self.matches.is_empty(), |this| {
+                        this.child(
+                            div()
+                                .p_2()
+                                .text_color(theme.text_muted)
+                                .child("No matching commands"),
+                        )
+                    })
+                    .children(self.matches.iter().enumerate().map(|(position, ix)| {
+                        let entry = &self.entries[*ix];
+                        let selected = position == self.selected;
+
+                        div()
+                            .id(("command", position))
+                            .flex()
+                            .flex_shrink_0()
+                            .gap_4()
+                            .justify_between()
+                            .px_2()
+                            .py_1()
+                            .rounded(theme.radius)
+                            .when(selected, |this| this.bg(theme.highlighted))
+                            .hover(|this| this.bg(theme.highlighted))
+                            .on_click(cx.listener(move |this, _, _, cx| this.confirm(position, cx)))
+                            .child(div().truncate().child(entry.label.clone()))
+                            .child(
+                                div()
+                                    .flex_shrink_0()
+                                    .text_size(theme.font_size_sm)
+                                    .text_color(theme.text_muted)
+                                    .child(entry.detail.clone()),
+                            )
+                    })),
+            )
+    }
+}
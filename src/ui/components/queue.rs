@@ -0,0 +1,416 @@
+use crate::controller::player::Controller;
+use crate::library::{
+    Library,
+    playlist::{self, Playlists},
+};
+use crate::ui::theme::Theme;
+use gpui::{prelude::FluentBuilder, *};
+use gpui_component::input::{Input, InputEvent, InputState};
+use std::{collections::BTreeSet, ops::Range, path::Path};
+
+const ROW_HEIGHT: f32 = 44.0;
+
+/// A queue entry being dragged to a new position.
+#[derive(Clone)]
+struct DraggedEntry {
+    index: usize,
+    title: SharedString,
+}
+
+impl Render for DraggedEntry {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+
+        div()
+            .px_3()
+            .py_1()
+            .rounded(theme.radius)
+            .bg(theme.panel)
+            .border_1()
+            .border_color(theme.accent)
+            .text_color(theme.text)
+            .child(self.title.clone())
+    }
+}
+
+/// Side panel listing the controller's queue: played tracks dimmed, the
+/// current one highlighted and upcoming ones after it. Entries can be
+/// dragged to reorder them.
+///
+/// Emits [`DismissEvent`] once a playlist name has been entered, so the
+/// window can take focus back.
+pub struct QueuePanel {
+    /// Selected queue positions.
+    selected: BTreeSet<usize>,
+    anchor: Option<usize>,
+    queue_len: usize,
+    scroll_handle: UniformListScrollHandle,
+    /// Name field shown while saving the queue as a playlist.
+    save_input: Option<Entity<InputState>>,
+    error: Option<String>,
+}
+
+impl EventEmitter<DismissEvent> for QueuePanel {}
+
+impl QueuePanel {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        // Positions mean other tracks once the queue changes length.
+        cx.observe_global::<Controller>(|this, cx| {
+            let len = cx.global::<Controller>().state.queue.len();
+            if len != this.queue_len {
+                this.queue_len = len;
+                this.selected.clear();
+                this.anchor = None;
+            }
+            cx.notify();
+        })
+        .detach();
+        cx.observe_global::<Library>(|_, cx| cx.notify()).detach();
+
+        QueuePanel {
+            selected: BTreeSet::new(),
+            anchor: None,
+            queue_len: cx.global::<Controller>().state.queue.len(),
+            scroll_handle: UniformListScrollHandle::new(),
+            save_input: None,
+            error: None,
+        }
+    }
+
+    pub fn is_editing(&self) -> bool {
+        self.save_input.is_some()
+    }
+
+    fn click(&mut self, index: usize, event: &ClickEvent, cx: &mut Context<Self>) {
+        let modifiers = event.modifiers();
+
+        if modifiers.shift {
+            let anchor = self.anchor.unwrap_or(index);
+            if !modifiers.secondary() {
+                self.selected.clear();
+            }
+            self.selected.extend(anchor.min(index)..=anchor.max(index));
+        } else if modifiers.secondary() {
+            if !self.selected.remove(&index) {
+                self.selected.insert(index);
+            }
+            self.anchor = Some(index);
+        } else {
+            self.selected.clear();
+            self.selected.insert(index);
+            self.anchor = Some(index);
+        }
+
+        if event.click_count() > 1 {
+            cx.global::<Controller>().play_index(index);
+        }
+
+        cx.notify();
+    }
+
+    fn move_entry(&mut self, from: usize, to: usize, cx: &mut Context<Self>) {
+        cx.global::<Controller>().move_in_queue(from, to);
+        self.selected.clear();
+        self.selected.insert(to);
+        self.anchor = Some(to);
+        cx.notify();
+    }
+
+    fn remove(&mut self, indices: Vec<usize>, cx: &mut Context<Self>) {
+        cx.global::<Controller>().remove_from_queue(indices);
+        self.selected.clear();
+        self.anchor = None;
+        cx.notify();
+    }
+
+    /// Moves the selection right after the current track, keeping its
+    /// order. Each move shifts the entries after it, so positions are
+    /// tracked on a local copy of the order as the engine will see it.
+    fn play_next(&mut self, cx: &mut Context<Self>) {
+        let controller = cx.global::<Controller>();
+        let current = controller.state.index;
+        let mut order: Vec<usize> = (0..controller.state.queue.len()).collect();
+        let mut placed = 0;
+
+        for entry in self.selected.iter().copied() {
+            if Some(entry) == current {
+                continue;
+            }
+            let Some(from) = order.iter().position(|ix| *ix == entry) else {
+                continue;
+            };
+
+            let after = current
+                .and_then(|current| order.iter().position(|ix| *ix == current))
+                .map_or(0, |position| position + 1);
+            let mut to = after + placed;
+            if from < to {
+                to -= 1;
+            }
+
+            order.remove(from);
+            order.insert(to, entry);
+            controller.move_in_queue(from, to);
+            placed += 1;
+        }
+
+        self.selected.clear();
+        self.anchor = None;
+        cx.notify();
+    }
+
+    fn start_saving(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let input = cx.new(|cx| InputState::new(window, cx).placeholder("Playlist name"));
+
+        cx.subscribe_in(
+            &input,
+            window,
+            |this, input, event: &InputEvent, _, cx| match event {
+                InputEvent::PressEnter { .. } => {
+                    let name = input.read(cx).value().trim().to_string();
+                    this.save(name, cx);
+                }
+                InputEvent::Blur => {
+                    this.save_input = None;
+                    this.error = None;
+                    cx.notify();
+                }
+                _ => {}
+            },
+        )
+        .detach();
+
+        window.focus(&input.focus_handle(cx));
+        self.save_input = Some(input);
+        self.error = None;
+        cx.notify();
+    }
+
+    fn save(&mut self, name: String, cx: &mut Context<Self>) {
+        if name.is_empty() || name.contains(['/', '\\']) {
+            self.error = Some(String::from("Enter a name without slashes"));
+        } else if cx.global::<Playlists>().get(&name).is_some() {
+            self.error = Some(format!("A playlist named \"{name}\" already exists"));
+        } else {
+            let queue = cx.global::<Controller>().state.queue.clone();
+            playlist::add_to_playlist(&name, queue, cx);
+            self.save_input = None;
+            self.error = None;
+            cx.emit(DismissEvent);
+        }
+
+        cx.notify();
+    }
+
+    fn render_rows(
+        &mut self,
+        range: Range<usize>,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Vec<Stateful<Div>> {
+        let theme = cx.global::<Theme>();
+        let library = cx.global::<Library>();
+        let state = &cx.global::<Controller>().state;
+
+        range
+            .filter_map(|index| {
+                let path = state.queue.get(index)?;
+                let (title, artist) = describe(library, path);
+                let current = state.index == Some(index);
+                let played = state.index.is_some_and(|current| index < current);
+                let selected = self.selected.contains(&index);
+                let dragged = DraggedEntry {
+                    index,
+                    title: title.clone(),
+                };
+
+                Some(
+                    div()
+                        .id(("queue_entry", index))
+                        .group("queue_entry")
+                        .h(px(ROW_HEIGHT))
+                        .w_full()
+                        .flex()
+                        .items_center()
+                        .gap_2()
+                        .px_2()
+                        .rounded(theme.radius)
+                        .when(played, |this| this.opacity(0.6))
+                        .when(selected, |this| this.bg(theme.highlighted))
+                        .when(current, |this| this.bg(theme.accent))
+                        .hover(|this| this.bg(theme.highlighted))
+                        .on_click(
+                            cx.listener(move |this, event, _, cx| this.click(index, event, cx)),
+                        )
+                        .on_drag(dragged, |dragged, _, _, cx| cx.new(|_| dragged.clone()))
+                        .drag_over::<DraggedEntry>(|style, _, _, cx| {
+                            style.border_t_2().border_color(cx.global::<Theme>().accent)
+                        })
+                        .on_drop(cx.listener(move |this, dragged: &DraggedEntry, _, cx| {
+                            this.move_entry(dragged.index, index, cx)
+                        }))
+                        .child(
+                            div()
+                                .flex_1()
+                                .min_w_0()
+                                .flex()
+                                .flex_col()
+                                .child(div().truncate().text_color(theme.text).child(title))
+                                .child(
+                                    div()
+                                        .truncate()
+                                        .text_size(theme.font_size_sm)
+                                        .text_color(theme.text_muted)
+                                        .child(artist),
+                                ),
+                        )
+                        .when(!current, |this| {
+                            this.child(
+                                div()
+                                    .id(("queue_remove", index))
+                                    .invisible()
+                                    .group_hover("queue_entry", |this| this.visible())
+                                    .px_2()
+                                    .rounded(theme.radius)
+                                    .text_color(theme.text_muted)
+                                    .hover(|this| this.text_color(theme.text))
+                                    .on_click(cx.listener(move |this, _, _, cx| {
+                                        cx.stop_propagation();
+                                        this.remove(vec![index], cx);
+                                    }))
+                                    .child("×"),
+                            )
+                        }),
+                )
+            })
+            .collect()
+    }
+}
+
+impl Render for QueuePanel {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let state = &cx.global::<Controller>().state;
+        let len = state.queue.len();
+        let upcoming = match state.index {
+            Some(index) => len.saturating_sub(index + 1),
+            None => len,
+        };
+        let has_selection = !self.selected.is_empty();
+
+        div()
+            .id("queue_panel")
+            .h_full()
+            .w_80()
+            .flex_shrink_0()
+            .flex()
+            .flex_col()
+            .gap_2()
+            .p_2()
+            .bg(theme.panel)
+            .border_l_1()
+            .border_color(theme.border)
+            .text_color(theme.text)
+            .child(
+                div()
+                    .flex()
+                    .items_center()
+                    .justify_between()
+                    .px_2()
+                    .child(div().text_size(theme.font_size_lg).child("Queue"))
+                    .child(
+                        div()
+                            .text_size(theme.font_size_sm)
+                            .text_color(theme.text_muted)
+                            .child(format!("{upcoming} up next")),
+                    ),
+            )
+            .child(
+                div()
+                    .flex()
+                    .flex_wrap()
+                    .gap_1()
+                    .text_size(theme.font_size_sm)
+                    .when(has_selection, |this| {
+                        this.child(
+                            button("queue_play_next", "Play next", theme)
+                                .on_click(cx.listener(|this, _, _, cx| this.play_next(cx))),
+                        )
+                        .child(
+                            button("queue_remove_selected", "Remove", theme).on_click(cx.listener(
+                                |this, _, _, cx| {
+                                    let selected = this.selected.iter().copied().collect();
+                                    this.remove(selected, cx)
+                                },
+                            )),
+                        )
+                    })
+                    .when(len > 0, |this| {
+                        this.child(button("queue_save", "Save as playlist", theme).on_click(
+                            cx.listener(|this, _, window, cx| this.start_saving(window, cx)),
+                        ))
+                        .child(
+                            button("queue_clear", "Clear", theme)
+                                .on_click(|_, _, cx| cx.global::<Controller>().clear_queue()),
+                        )
+                    }),
+            )
+            .when_some(self.save_input.clone(), |this, input| {
+                this.child(Input::new(&input))
+            })
+            .when_some(self.error.clone(), |this, error| {
+                this.child(
+                    div()
+                        .px_2()
+                        .text_size(theme.font_size_sm)
+                        .text_color(theme.error)
+                        .child(error),
+                )
+            })
+            .child(if len == 0 {
+                div()
+                    .flex_1()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .text_color(theme.text_muted)
+                    .child("The queue is empty")
+                    .into_any_element()
+            } else {
+                uniform_list("queue", len, cx.processor(Self::render_rows))
+                    .track_scroll(self.scroll_handle.clone())
+                    .flex_1()
+                    .w_full()
+                    .into_any_element()
+            })
+    }
+}
+
+/// Title and artist from the library, or the file name for tracks outside
+/// it.
+pub(crate) fn describe(library: &Library, path: &Path) -> (SharedString, SharedString) {
+    match library.track(path) {
+        Some(track) => (
+            track.meta.title.clone().into(),
+            track.meta.artists.join(", ").into(),
+        ),
+        None => (
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default()
+                .into(),
+            SharedString::default(),
+        ),
+    }
+}
+
+fn button(id: &'static str, label: &'static str, theme: &Theme) -> Stateful<Div> {
+    div()
+        .id(id)
+        .px_2()
+        .py_1()
+        .rounded(theme.radius)
+        .bg(theme.highlighted)
+        .hover(|this| this.bg(theme.border))
+        .child(label)
+}
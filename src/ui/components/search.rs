@@ -0,0 +1,284 @@
+use super::palette::{SelectNext, SelectPrevious};
+use super::{LibraryView, Page};
+use crate::controller::player::Controller;
+use crate::library::{Library, search::SearchResults};
+use crate::ui::keymap::Dismiss;
+use crate::ui::theme::Theme;
+use gpui::{prelude::FluentBuilder, *};
+use gpui_component::{
+    Sizable,
+    input::{Input, InputEvent, InputState},
+};
+
+const CONTEXT: &str = "Search";
+
+/// Binds the keys used to move through the results while the search field
+/// has focus, like in the command palette.
+pub fn init(cx: &mut App) {
+    let context = format!("{CONTEXT} > Input");
+    cx.bind_keys([
+        KeyBinding::new("down", SelectNext, Some(&context)),
+        KeyBinding::new("up", SelectPrevious, Some(&context)),
+        KeyBinding::new("escape", Dismiss, Some(&context)),
+    ]);
+}
+
+#[derive(Clone, Copy)]
+enum Hit {
+    Artist(usize),
+    Album(usize),
+    /// Position within the track results.
+    Track(usize),
+}
+
+/// The library search field in the titlebar. Results update as you type
+/// and drop down below the field, grouped into artists, albums and tracks.
+///
+/// Emits [`DismissEvent`] once done so the window can take focus back.
+pub struct SearchBar {
+    input: Entity<InputState>,
+    results: SearchResults,
+    selected: usize,
+    open: bool,
+}
+
+impl EventEmitter<DismissEvent> for SearchBar {}
+
+impl SearchBar {
+    pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let input = cx.new(|cx| InputState::new(window, cx).placeholder("Search library"));
+
+        cx.subscribe_in(
+            &input,
+            window,
+            |this, input, event: &InputEvent, _, cx| match event {
+                InputEvent::Change => {
+                    let query = input.read(cx).value().to_string();
+                    this.results = cx.global::<Library>().search(&query);
+                    this.selected = 0;
+                    this.open = true;
+                    cx.notify();
+                }
+                InputEvent::PressEnter { .. } => this.confirm(this.selected, cx),
+                _ => {}
+            },
+        )
+        .detach();
+
+        let focus_handle = input.focus_handle(cx);
+        cx.on_focus(&focus_handle, window, |this, _, cx| {
+            this.open = true;
+            cx.notify();
+        })
+        .detach();
+        cx.on_blur(&focus_handle, window, |this, _, cx| {
+            this.open = false;
+            cx.notify();
+        })
+        .detach();
+
+        // Indices go stale once the library changes.
+        cx.observe_global::<Library>(|this, cx| {
+            let query = this.input.read(cx).value().to_string();
+            this.results = cx.global::<Library>().search(&query);
+            this.selected = 0;
+            cx.notify();
+        })
+        .detach();
+
+        SearchBar {
+            input,
+            results: SearchResults::default(),
+            selected: 0,
+            open: false,
+        }
+    }
+
+    pub fn focus(&self, window: &mut Window, cx: &App) {
+        window.focus(&self.input.focus_handle(cx));
+    }
+
+    pub fn is_focused(&self, window: &Window, cx: &App) -> bool {
+        self.input.focus_handle(cx).is_focused(window)
+    }
+
+    fn hits(&self) -> Vec<Hit> {
+        let artists = self.results.artists.iter().map(|ix| Hit::Artist(*ix));
+        let albums = self.results.albums.iter().map(|ix| Hit::Album(*ix));
+        let tracks = (0..self.results.tracks.len()).map(Hit::Track);
+
+        artists.chain(albums).chain(tracks).collect()
+    }
+
+    fn select_next(&mut self, _: &SelectNext, _: &mut Window, cx: &mut Context<Self>) {
+        let count = self.hits().len();
+        if count > 0 {
+            self.selected = (self.selected + 1) % count;
+            cx.notify();
+        }
+    }
+
+    fn select_previous(&mut self, _: &SelectPrevious, _: &mut Window, cx: &mut Context<Self>) {
+        let count = self.hits().len();
+        if count > 0 {
+            self.selected = self.selected.checked_sub(1).unwrap_or(count - 1);
+            cx.notify();
+        }
+    }
+
+    fn dismiss(&mut self, _: &Dismiss, _: &mut Window, cx: &mut Context<Self>) {
+        cx.emit(DismissEvent);
+    }
+
+    /// Artists and albums open in the library; tracks play along with the
+    /// tracks found after them.
+    fn confirm(&mut self, selected: usize, cx: &mut Context<Self>) {
+        let Some(hit) = self.hits().get(selected).copied() else {
+            return;
+        };
+        let library = cx.global::<Library>();
+
+        match hit {
+            Hit::Artist(ix) => {
+                let view = LibraryView::Artist(library.artists[ix].name.clone());
+                cx.set_global(view);
+                cx.set_global(Page::Home);
+            }
+            Hit::Album(ix) => {
+                let album = &library.albums[ix];
+                let view = LibraryView::Album {
+                    name: album.name.clone(),
+                    artist: album.artist.clone(),
+                };
+                cx.set_global(view);
+                cx.set_global(Page::Home);
+            }
+            Hit::Track(position) => {
+                let paths = self
+                    .results
+                    .tracks
+                    .iter()
+                    .filter_map(|ix| library.tracks.get(*ix))
+                    .map(|track| track.path.clone())
+                    .collect();
+                cx.global::<Controller>().play_tracks(paths, position);
+            }
+        }
+
+        cx.emit(DismissEvent);
+    }
+
+    fn render_results(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let library = cx.global::<Library>();
+        let hits = self.hits();
+
+        let heading = |label: &'static str| {
+            div()
+                .px_2()
+                .pt_2()
+                .text_size(theme.font_size_sm)
+                .text_color(theme.text_muted)
+                .child(label)
+        };
+
+        let mut list = div()
+            .id("search_results")
+            .w_96()
+            .max_h_96()
+            .mt_1()
+            .flex()
+            .flex_col()
+            .p_1()
+            .overflow_y_scroll()
+            .rounded(theme.radius_lg)
+            .bg(theme.panel)
+            .border_1()
+            .border_color(theme.border)
+            .text_color(theme.text)
+            .when(hits.is_empty(), |this| {
+                this.child(div().p_2().text_color(theme.text_muted).child("No matches"))
+            });
+
+        for (position, hit) in hits.into_iter().enumerate() {
+            let (label, detail) = match hit {
+                Hit::Artist(ix) => {
+                    let artist = &library.artists[ix];
+                    (
+                        artist.name.clone(),
+                        format!("{} tracks", artist.tracks.len()),
+                    )
+                }
+                Hit::Album(ix) => {
+                    let album = &library.albums[ix];
+                    (album.name.clone(), album.artist.clone())
+                }
+                Hit::Track(ix) => {
+                    let meta = &library.tracks[self.results.tracks[ix]].meta;
+                    (meta.title.clone(), meta.artists.join(", "))
+                }
+            };
+
+            list = match hit {
+                Hit::Artist(_) if position == 0 => list.child(heading("Artists")),
+                Hit::Album(_) if position == self.results.artists.len() => {
+                    list.child(heading("Albums"))
+                }
+                Hit::Track(0) => list.child(heading("Tracks")),
+                _ => list,
+            };
+
+            list = list.child(
+                div()
+                    .id(("search_result", position))
+                    .flex()
+                    .flex_shrink_0()
+                    .gap_4()
+                    .justify_between()
+                    .px_2()
+                    .py_1()
+                    .rounded(theme.radius)
+                    .when(position == self.selected, |this| this.bg(theme.highlighted))
+                    .hover(|this| this.bg(theme.highlighted))
+                    // Acting on mouse down, before the field loses focus and
+                    // closes the results.
+                    .on_mouse_down(
+                        MouseButton::Left,
+                        cx.listener(move |this, _, _, cx| {
+                            cx.stop_propagation();
+                            this.confirm(position, cx);
+                        }),
+                    )
+                    .child(div().truncate().child(label))
+                    .child(
+                        div()
+                            .flex_shrink_0()
+                            .max_w_40()
+                            .truncate()
+                            .text_size(theme.font_size_sm)
+                            .text_color(theme.text_muted)
+                            .child(detail),
+                    ),
+            );
+        }
+
+        deferred(anchored().snap_to_window_with_margin(px(8.0)).child(list)).with_priority(1)
+    }
+}
+
+impl Render for SearchBar {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let show_results = self.open && !self.input.read(cx).value().trim().is_empty();
+
+        div()
+            .key_context(CONTEXT)
+            .on_action(cx.listener(Self::select_next))
+            .on_action(cx.listener(Self::select_previous))
+            .on_action(cx.listener(Self::dismiss))
+            .w_72()
+            .flex()
+            .flex_col()
+            .child(Input::new(&self.input).small())
+            .when(show_results, |this| this.child(self.render_results(cx)))
+    }
+}
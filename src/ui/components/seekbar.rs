@@ -0,0 +1,110 @@
+use crate::audio::engine::PlaybackState;
+use crate::controller::player::Controller;
+use crate::ui::{
+    components::waveform::{self, CurrentWaveform},
+    theme::Theme,
+};
+use gpui::*;
+use std::{cell::Cell, rc::Rc};
+
+/// The current track's waveform, seeking where it's clicked or dragged to.
+pub struct SeekBar {
+    /// Where the waveform was last painted, to turn clicks into positions.
+    bounds: Rc<Cell<Bounds<Pixels>>>,
+    /// Position being dragged to, as a fraction of the track. The seek
+    /// happens on release.
+    seeking: Option<f32>,
+}
+
+impl SeekBar {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        cx.observe_global::<CurrentWaveform>(|_, cx| cx.notify())
+            .detach();
+
+        SeekBar {
+            bounds: Rc::default(),
+            seeking: None,
+        }
+    }
+
+    /// The position being dragged to, for showing it before the seek.
+    pub fn seeking(&self) -> Option<f32> {
+        self.seeking
+    }
+
+    fn fraction_at(&self, position: Point<Pixels>) -> f32 {
+        let bounds = self.bounds.get();
+        if bounds.size.width <= px(0.0) {
+            return 0.0;
+        }
+
+        ((position.x - bounds.origin.x) / bounds.size.width).clamp(0.0, 1.0)
+    }
+
+    fn drag_seek(&mut self, position: Point<Pixels>, cx: &mut Context<Self>) {
+        if cx.global::<Controller>().state.state != PlaybackState::Stopped {
+            self.seeking = Some(self.fraction_at(position));
+            cx.notify();
+        }
+    }
+
+    fn finish_seek(&mut self, cx: &mut Context<Self>) {
+        if let Some(fraction) = self.seeking.take() {
+            cx.global::<Controller>().seek_fraction(fraction);
+            cx.notify();
+        }
+    }
+}
+
+impl Render for SeekBar {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let progress = self
+            .seeking
+            .unwrap_or_else(|| cx.global::<Controller>().progress());
+        let waveform = cx.global::<CurrentWaveform>().waveform.clone();
+        let painted = self.bounds.clone();
+        let (played, unplayed) = (theme.accent, theme.text_muted);
+
+        div()
+            .id("waveform")
+            .flex()
+            .size_full()
+            .cursor_pointer()
+            .on_mouse_down(
+                MouseButton::Left,
+                cx.listener(|this, event: &MouseDownEvent, _, cx| {
+                    this.drag_seek(event.position, cx)
+                }),
+            )
+            .on_mouse_move(cx.listener(|this, event: &MouseMoveEvent, _, cx| {
+                if this.seeking.is_some() && event.dragging() {
+                    this.drag_seek(event.position, cx);
+                }
+            }))
+            .on_mouse_up(
+                MouseButton::Left,
+                cx.listener(|this, _, _, cx| this.finish_seek(cx)),
+            )
+            .on_mouse_up_out(
+                MouseButton::Left,
+                cx.listener(|this, _, _, cx| this.finish_seek(cx)),
+            )
+            .child(
+                canvas(
+                    move |bounds, _, _| painted.set(bounds),
+                    move |bounds, _, window, _| {
+                        waveform::paint(
+                            waveform.as_deref(),
+                            progress,
+                            played,
+                            unplayed,
+                            bounds,
+                            window,
+                        )
+                    },
+                )
+                .size_full(),
+            )
+    }
+}
@@ -0,0 +1,77 @@
+use crate::ui::keymap::{self, ACTIONS, Dismiss, Keymap};
+use crate::ui::theme::Theme;
+use gpui::{prelude::FluentBuilder, *};
+
+/// Lists every action with the keys currently bound to it.
+pub fn shortcuts_overlay(keymap: &Keymap, theme: &Theme) -> Stateful<Div> {
+    div()
+        .id("shortcuts_overlay")
+        .absolute()
+        .size_full()
+        .flex()
+        .items_center()
+        .justify_center()
+        .bg(rgba(0x00000080))
+        .on_click(|_, window, cx| window.dispatch_action(Box::new(Dismiss), cx))
+        .child(
+            div()
+                .id("shortcuts")
+                .w_128()
+                .max_h_3_4()
+                .flex()
+                .flex_col()
+                .gap_1()
+                .p_4()
+                .rounded(theme.radius_lg)
+                .bg(theme.panel)
+                .border_1()
+                .border_color(theme.border)
+                .overflow_y_scroll()
+                // Clicks inside the panel shouldn't close it.
+                .on_click(|_, _, cx| cx.stop_propagation())
+                .child(
+                    div()
+                        .pb_2()
+                        .text_size(theme.font_size_lg)
+                        .text_color(theme.text)
+                        .child("Keyboard shortcuts"),
+                )
+                .children(
+                    ACTIONS
+                        .iter()
+                        .zip(&keymap.keys)
+                        .map(|(action, keys)| shortcut_row(action.description, keys, theme)),
+                ),
+        )
+}
+
+/// One action and its keys, as shown in the overlay and on the settings
+/// page.
+pub fn shortcut_row(description: &'static str, keys: &[String], theme: &Theme) -> Div {
+    div()
+        .w_full()
+        .flex()
+        .gap_4()
+        .items_center()
+        .justify_between()
+        .py_1()
+        .child(div().text_color(theme.text).child(description))
+        .child(
+            div()
+                .flex()
+                .flex_shrink_0()
+                .gap_1()
+                .when(keys.is_empty(), |this| {
+                    this.child(div().text_color(theme.text_muted).child("Unbound"))
+                })
+                .children(keys.iter().map(|keys| {
+                    div()
+                        .px_2()
+                        .rounded(theme.radius)
+                        .bg(theme.highlighted)
+                        .text_size(theme.font_size_sm)
+                        .text_color(theme.text)
+                        .child(keymap::display(keys))
+                })),
+        )
+}
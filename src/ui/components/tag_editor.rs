@@ -0,0 +1,221 @@
+use crate::controller::metadata::{Metadata, TagEdit};
+use crate::library::{Library, Track};
+use crate::ui::theme::Theme;
+use gpui::{prelude::FluentBuilder, *};
+use gpui_component::input::{Input, InputState};
+use std::path::PathBuf;
+
+/// Edits the common tags of one or more tracks. Fields whose value differs
+/// between the tracks start empty and are only written if filled in.
+pub struct TagEditor {
+    paths: Vec<PathBuf>,
+    title: Entity<InputState>,
+    artists: Entity<InputState>,
+    album: Entity<InputState>,
+    genre: Entity<InputState>,
+    /// Values the fields started with, to tell edited fields apart.
+    initial: [String; 4],
+    saving: bool,
+    error: Option<String>,
+}
+
+impl EventEmitter<DismissEvent> for TagEditor {}
+
+impl TagEditor {
+    pub fn new(tracks: Vec<Track>, window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let common = |value: &dyn Fn(&Track) -> String| -> String {
+            let mut values = tracks.iter().map(value);
+            let first = values.next().unwrap_or_default();
+            if values.all(|v| v == first) && first != "None" {
+                first
+            } else {
+                String::new()
+            }
+        };
+
+        let initial = [
+            common(&|t| t.meta.title.clone()),
+            common(&|t| t.meta.artists.join("; ")),
+            common(&|t| t.meta.album.clone()),
+            common(&|t| t.meta.genre.clone()),
+        ];
+        let placeholder = if tracks.len() > 1 {
+            "Multiple values"
+        } else {
+            ""
+        };
+
+        let mut field = |value: &str| {
+            let value = value.to_string();
+            cx.new(|cx| {
+                InputState::new(window, cx)
+                    .placeholder(placeholder)
+                    .default_value(value)
+            })
+        };
+        let title = field(&initial[0]);
+        let artists = field(&initial[1]);
+        let album = field(&initial[2]);
+        let genre = field(&initial[3]);
+
+        window.focus(&title.focus_handle(cx));
+
+        TagEditor {
+            paths: tracks.into_iter().map(|track| track.path).collect(),
+            title,
+            artists,
+            album,
+            genre,
+            initial,
+            saving: false,
+            error: None,
+        }
+    }
+
+    fn edit(&self, cx: &App) -> TagEdit {
+        let value = |input: &Entity<InputState>| input.read(cx).value().trim().to_string();
+        let changed = |value: String, initial: &str| (value != initial).then_some(value);
+
+        TagEdit {
+            title: changed(value(&self.title), &self.initial[0]),
+            artists: changed(value(&self.artists), &self.initial[1]).map(|artists| {
+                artists
+                    .split(';')
+                    .map(str::trim)
+                    .filter(|artist| !artist.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }),
+            album: changed(value(&self.album), &self.initial[2]),
+            genre: changed(value(&self.genre), &self.initial[3]),
+        }
+    }
+
+    /// Writes the tags in the background, then refreshes the edited tracks
+    /// in the library. The editor stays open if any file failed.
+    fn save(&mut self, cx: &mut Context<Self>) {
+        let edit = self.edit(cx);
+        if edit == TagEdit::default() {
+            cx.emit(DismissEvent);
+            return;
+        }
+
+        self.saving = true;
+        self.error = None;
+        cx.notify();
+
+        let paths = self.paths.clone();
+        let task = cx.background_executor().spawn(async move {
+            let mut updated = Vec::new();
+            let mut errors = Vec::new();
+
+            for path in paths {
+                match Metadata::write(&path, &edit).and_then(|_| Metadata::read(path.clone())) {
+                    Ok(meta) => updated.push(Track { path, meta }),
+                    Err(err) => errors.push(format!("{}: {err:#}", path.display())),
+                }
+            }
+
+            (updated, errors)
+        });
+
+        cx.spawn(async move |this, cx| {
+            let (updated, errors) = task.await;
+            let _ = this.update(cx, |this, cx| {
+                cx.global_mut::<Library>().update_tracks(updated);
+
+                if errors.is_empty() {
+                    cx.emit(DismissEvent);
+                } else {
+                    this.saving = false;
+                    this.error = Some(errors.join("\n"));
+                    cx.notify();
+                }
+            });
+        })
+        .detach();
+    }
+}
+
+impl Render for TagEditor {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let heading = match self.paths.len() {
+            1 => String::from("Edit tags"),
+            n => format!("Edit tags of {n} tracks"),
+        };
+
+        div()
+            .id("tag_editor")
+            .w_112()
+            .flex()
+            .flex_col()
+            .gap_3()
+            .p_4()
+            .rounded(theme.radius_lg)
+            .bg(theme.panel)
+            .border_1()
+            .border_color(theme.border)
+            .text_color(theme.text)
+            .on_click(|_, _, cx| cx.stop_propagation())
+            .child(div().text_size(theme.font_size_lg).child(heading))
+            .child(field("Title", &self.title, theme))
+            .child(field("Artists (separated by ;)", &self.artists, theme))
+            .child(field("Album", &self.album, theme))
+            .child(field("Genre", &self.genre, theme))
+            .when_some(self.error.clone(), |this, error| {
+                this.child(
+                    div()
+                        .text_size(theme.font_size_sm)
+                        .text_color(theme.error)
+                        .child(error),
+                )
+            })
+            .child(
+                div()
+                    .flex()
+                    .justify_end()
+                    .gap_2()
+                    .child(
+                        button("tag_editor_cancel", "Cancel", theme)
+                            .on_click(cx.listener(|_, _, _, cx| cx.emit(DismissEvent))),
+                    )
+                    .child(
+                        button(
+                            "tag_editor_save",
+                            if self.saving { "Saving…" } else { "Save" },
+                            theme,
+                        )
+                        .bg(theme.accent)
+                        .when(!self.saving, |this| {
+                            this.on_click(cx.listener(|this, _, _, cx| this.save(cx)))
+                        }),
+                    ),
+            )
+    }
+}
+
+fn field(label: &'static str, input: &Entity<InputState>, theme: &Theme) -> Div {
+    div()
+        .flex()
+        .flex_col()
+        .gap_1()
+        .child(
+            div()
+                .text_size(theme.font_size_sm)
+                .text_color(theme.text_muted)
+                .child(label),
+        )
+        .child(Input::new(input))
+}
+
+fn button(id: &'static str, label: &'static str, theme: &Theme) -> Stateful<Div> {
+    div()
+        .id(id)
+        .px_3()
+        .py_1()
+        .rounded(theme.radius)
+        .bg(theme.highlighted)
+        .hover(|this| this.bg(theme.border))
+        .child(label)
+}
@@ -1,10 +1,15 @@
+use super::search::SearchBar;
+use crate::integrations::tray;
 use crate::ui::theme::Theme;
 
 use crate::ui::icons::Icons;
+use crate::ui::keymap::ToggleMiniPlayer;
 use gpui::*;
 
 #[derive(Clone)]
-pub struct Titlebar;
+pub struct Titlebar {
+    pub search: Entity<SearchBar>,
+}
 
 impl Render for Titlebar {
     fn render(&mut self, win: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
@@ -19,7 +24,16 @@ impl Render for Titlebar {
             .border_b_1()
             .border_color(theme.border)
             .bg(theme.panel)
-            .child(div().flex().flex_shrink_0().h_full().w_auto().flex())
+            .child(
+                div()
+                    .flex()
+                    .flex_shrink_0()
+                    .h_full()
+                    .w_auto()
+                    .items_center()
+                    .px_2()
+                    .child(self.search.clone()),
+            )
             .child(
                 div()
                     .flex()
@@ -33,6 +47,20 @@ impl Render for Titlebar {
                     .flex_shrink_0()
                     .flex()
                     .justify_end()
+                    .child(
+                        div()
+                            .id("mini_player")
+                            .h_full()
+                            .w_12()
+                            .flex()
+                            .items_center()
+                            .justify_center()
+                            .child(Icons::MiniPlayer)
+                            .hover(|this| this.bg(theme.highlighted))
+                            .on_click(|_, window, cx| {
+                                window.dispatch_action(Box::new(ToggleMiniPlayer), cx)
+                            }),
+                    )
                     .child(
                         div()
                             .id("win_min")
@@ -63,21 +91,30 @@ impl Render for Titlebar {
                     )
                     .child(
                         div()
+                            .id("win_close")
                             .h_full()
                             .w_12()
                             .flex()
                             .items_center()
                             .justify_center()
-                            .hover(|this| this.bg(rgb(0xe81123)))
+                            .hover(|this| this.bg(theme.error))
                             .child(Icons::WinClose)
-                            .window_control_area(WindowControlArea::Close),
+                            // Handled here rather than as a window control, so
+                            // the close-to-tray setting applies.
+                            .on_click(|_, window, cx| {
+                                if tray::should_close(window, cx) {
+                                    cx.quit();
+                                }
+                            }),
                     ),
             )
     }
 }
 
 impl Titlebar {
-    pub fn new() -> Titlebar {
-        Titlebar {}
+    pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Titlebar {
+        Titlebar {
+            search: cx.new(|cx| SearchBar::new(window, cx)),
+        }
     }
 }
@@ -0,0 +1,480 @@
+use super::{controlbar::format_duration, notifications::report_error};
+use crate::config::{Column, Config, TrackColumn};
+use crate::library::{
+    Library, Track,
+    stats::{self, Stats, TrackStats},
+};
+use crate::ui::theme::Theme;
+use gpui::{prelude::FluentBuilder, *};
+use std::{cmp::Reverse, collections::BTreeSet, ops::Range};
+
+const ROW_HEIGHT: f32 = 32.0;
+/// Horizontal padding of the header and the rows, needed to place the
+/// column being resized.
+const PADDING: f32 = 16.0;
+const MAX_RATING: u8 = 5;
+
+fn label(column: Column) -> &'static str {
+    match column {
+        Column::Title => "Title",
+        Column::Artist => "Artist",
+        Column::Album => "Album",
+        Column::Duration => "Duration",
+        Column::Year => "Year",
+        Column::Plays => "Plays",
+        Column::Rating => "Rating",
+        Column::Bitrate => "Bitrate",
+    }
+}
+
+/// A header cell being dragged to a new position.
+#[derive(Clone)]
+struct DraggedColumn {
+    index: usize,
+    label: &'static str,
+}
+
+impl Render for DraggedColumn {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+
+        div()
+            .px_3()
+            .py_1()
+            .rounded(theme.radius)
+            .bg(theme.panel)
+            .border_1()
+            .border_color(theme.accent)
+            .text_size(theme.font_size_sm)
+            .text_color(theme.text)
+            .child(self.label)
+    }
+}
+
+/// The resize handle of the column at this index being dragged.
+#[derive(Clone)]
+struct ResizeColumn(usize);
+
+impl Render for ResizeColumn {
+    fn render(&mut self, _: &mut Window, _: &mut Context<Self>) -> impl IntoElement {
+        Empty
+    }
+}
+
+pub enum TrackTableEvent {
+    /// A row was double-clicked. `tracks` is the whole table in display
+    /// order, `index` the position of the row within it.
+    Activate { tracks: Vec<usize>, index: usize },
+    /// A row was right-clicked; `tracks` is the selection.
+    ContextMenu {
+        position: Point<Pixels>,
+        tracks: Vec<usize>,
+    },
+}
+
+/// A sortable list of library tracks with multi-selection. Tracks are
+/// referred to by their index in [`Library::tracks`].
+///
+/// Only the visible rows are laid out, so the table stays smooth however
+/// large the library is. Columns come from the config and can be resized
+/// and reordered by dragging their headers.
+pub struct TrackTable {
+    tracks: Vec<usize>,
+    /// `tracks` in display order.
+    rows: Vec<usize>,
+    columns: Vec<TrackColumn>,
+    sort: Option<Column>,
+    ascending: bool,
+    selected: BTreeSet<usize>,
+    /// Row that shift-click selection extends from.
+    anchor: Option<usize>,
+    scroll_handle: UniformListScrollHandle,
+}
+
+impl EventEmitter<TrackTableEvent> for TrackTable {}
+
+impl TrackTable {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        cx.observe_global::<Config>(|this, cx| {
+            let columns = &cx.global::<Config>().ui.columns;
+            if *columns != this.columns {
+                this.columns = columns.clone();
+                cx.notify();
+            }
+        })
+        .detach();
+        cx.observe_global::<Stats>(|this, cx| {
+            if matches!(this.sort, Some(Column::Plays | Column::Rating)) {
+                this.resort(cx);
+            } else {
+                cx.notify();
+            }
+        })
+        .detach();
+
+        TrackTable {
+            tracks: Vec::new(),
+            rows: Vec::new(),
+            columns: cx.global::<Config>().ui.columns.clone(),
+            sort: None,
+            ascending: true,
+            selected: BTreeSet::new(),
+            anchor: None,
+            scroll_handle: UniformListScrollHandle::new(),
+        }
+    }
+
+    pub fn set_tracks(&mut self, tracks: Vec<usize>, cx: &mut Context<Self>) {
+        if tracks != self.tracks {
+            self.selected.clear();
+            self.anchor = None;
+            self.scroll_handle.scroll_to_item(0, ScrollStrategy::Top);
+        }
+
+        self.tracks = tracks;
+        self.resort(cx);
+    }
+
+    /// The tracks in display order.
+    pub fn rows(&self) -> &[usize] {
+        &self.rows
+    }
+
+    pub fn selection(&self) -> Vec<usize> {
+        self.rows
+            .iter()
+            .copied()
+            .filter(|track| self.selected.contains(track))
+            .collect()
+    }
+
+    fn sort_by(&mut self, column: Column, cx: &mut Context<Self>) {
+        if self.sort == Some(column) {
+            self.ascending = !self.ascending;
+        } else {
+            self.sort = Some(column);
+            self.ascending = true;
+        }
+
+        self.resort(cx);
+    }
+
+    fn resort(&mut self, cx: &mut Context<Self>) {
+        let tracks = &cx.global::<Library>().tracks;
+        let stats = cx.global::<Stats>();
+        let mut rows: Vec<usize> = self
+            .tracks
+            .iter()
+            .copied()
+            .filter(|ix| *ix < tracks.len())
+            .collect();
+
+        // Cached keys keep this quick on large libraries, where lowercasing
+        // on every comparison would dominate. Most played and best rated
+        // come first when ascending, as that's what one sorts those for.
+        match self.sort {
+            Some(Column::Title) => {
+                rows.sort_by_cached_key(|ix| tracks[*ix].meta.title.to_lowercase())
+            }
+            Some(Column::Artist) => {
+                rows.sort_by_cached_key(|ix| tracks[*ix].meta.artists.join(", ").to_lowercase())
+            }
+            Some(Column::Album) => {
+                rows.sort_by_cached_key(|ix| tracks[*ix].meta.album.to_lowercase())
+            }
+            Some(Column::Duration) => rows.sort_by_key(|ix| tracks[*ix].meta.duration),
+            Some(Column::Year) => rows.sort_by_key(|ix| tracks[*ix].meta.year),
+            Some(Column::Bitrate) => rows.sort_by_key(|ix| tracks[*ix].meta.bitrate),
+            Some(Column::Plays) => {
+                rows.sort_by_cached_key(|ix| Reverse(stats.get(&tracks[*ix].path).plays))
+            }
+            Some(Column::Rating) => {
+                rows.sort_by_cached_key(|ix| Reverse(stats.get(&tracks[*ix].path).rating))
+            }
+            None => {}
+        }
+
+        if !self.ascending {
+            rows.reverse();
+        }
+
+        self.rows = rows;
+        cx.notify();
+    }
+
+    fn click(&mut self, row: usize, event: &ClickEvent, cx: &mut Context<Self>) {
+        let track = self.rows[row];
+        let modifiers = event.modifiers();
+
+        if modifiers.shift {
+            let anchor = self.anchor.unwrap_or(row);
+            let range = anchor.min(row)..=anchor.max(row);
+            if !modifiers.secondary() {
+                self.selected.clear();
+            }
+            self.selected.extend(self.rows[range].iter().copied());
+        } else if modifiers.secondary() {
+            if !self.selected.remove(&track) {
+                self.selected.insert(track);
+            }
+            self.anchor = Some(row);
+        } else {
+            self.selected.clear();
+            self.selected.insert(track);
+            self.anchor = Some(row);
+        }
+
+        if event.click_count() > 1 {
+            cx.emit(TrackTableEvent::Activate {
+                tracks: self.rows.clone(),
+                index: row,
+            });
+        }
+
+        cx.notify();
+    }
+
+    fn context_menu(&mut self, row: usize, position: Point<Pixels>, cx: &mut Context<Self>) {
+        let track = self.rows[row];
+
+        // Right-clicking outside the selection acts on that row alone.
+        if !self.selected.contains(&track) {
+            self.selected.clear();
+            self.selected.insert(track);
+            self.anchor = Some(row);
+        }
+
+        cx.emit(TrackTableEvent::ContextMenu {
+            position,
+            tracks: self.selection(),
+        });
+        cx.notify();
+    }
+
+    /// Rates the row, or the whole selection if the row is part of it.
+    /// Giving a track the rating it already has clears it.
+    fn rate(&mut self, row: usize, rating: u8, cx: &mut Context<Self>) {
+        let track = self.rows[row];
+        let targets = if self.selected.contains(&track) {
+            self.selection()
+        } else {
+            vec![track]
+        };
+
+        let tracks = &cx.global::<Library>().tracks;
+        let current = cx.global::<Stats>().get(&tracks[track].path).rating;
+        let paths: Vec<_> = targets.iter().map(|ix| tracks[*ix].path.clone()).collect();
+        let rating = (current != Some(rating)).then_some(rating);
+
+        stats::set_rating(&paths, rating, cx);
+    }
+
+    fn move_column(&mut self, from: usize, to: usize, cx: &mut Context<Self>) {
+        if from == to || from >= self.columns.len() || to >= self.columns.len() {
+            return;
+        }
+
+        let column = self.columns.remove(from);
+        self.columns.insert(to, column);
+        self.save_columns(cx);
+    }
+
+    /// Follows the resize handle. The width is only saved once it's dropped,
+    /// rather than on every mouse move.
+    fn resize(
+        &mut self,
+        event: &DragMoveEvent<ResizeColumn>,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let ix = event.drag(cx).0;
+        if ix >= self.columns.len() {
+            return;
+        }
+
+        let offset: f32 = self.columns[..ix].iter().map(|column| column.width).sum();
+        let left = event.bounds.left() + px(PADDING + offset);
+        let width = ((event.event.position.x - left) / px(1.0))
+            .clamp(TrackColumn::MIN_WIDTH, TrackColumn::MAX_WIDTH)
+            .round();
+
+        if self.columns[ix].width != width {
+            self.columns[ix].width = width;
+            cx.notify();
+        }
+    }
+
+    fn save_columns(&mut self, cx: &mut Context<Self>) {
+        let columns = self.columns.clone();
+        if let Err(err) = Config::update(cx, |config| config.ui.columns = columns) {
+            report_error("Could not update config", err, cx);
+        }
+        cx.notify();
+    }
+
+    fn render_rows(
+        &mut self,
+        range: Range<usize>,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Vec<Stateful<Div>> {
+        let theme = cx.global::<Theme>();
+        let tracks = &cx.global::<Library>().tracks;
+        let stats = cx.global::<Stats>();
+
+        range
+            .map(|row| {
+                let track = &tracks[self.rows[row]];
+                let track_stats = stats.get(&track.path);
+                let selected = self.selected.contains(&self.rows[row]);
+
+                div()
+                    .id(("track", row))
+                    .h(px(ROW_HEIGHT))
+                    .w_full()
+                    .flex()
+                    .items_center()
+                    .px(px(PADDING))
+                    .rounded(theme.radius)
+                    .when(selected, |this| this.bg(theme.highlighted))
+                    .hover(|this| this.bg(theme.highlighted))
+                    .on_click(cx.listener(move |this, event, _, cx| this.click(row, event, cx)))
+                    .on_mouse_down(
+                        MouseButton::Right,
+                        cx.listener(move |this, event: &MouseDownEvent, _, cx| {
+                            this.context_menu(row, event.position, cx)
+                        }),
+                    )
+                    .children(self.columns.iter().map(|column| {
+                        let cell = div()
+                            .w(px(column.width))
+                            .flex_shrink_0()
+                            .pr_4()
+                            .truncate()
+                            .text_color(if column.column == Column::Title {
+                                theme.text
+                            } else {
+                                theme.text_muted
+                            });
+
+                        match column.column {
+                            Column::Rating => cell.child(self.stars(row, track_stats, theme, cx)),
+                            _ => cell.child(text(column.column, track, track_stats)),
+                        }
+                    }))
+            })
+            .collect()
+    }
+
+    fn stars(&self, row: usize, stats: TrackStats, theme: &Theme, cx: &Context<Self>) -> Div {
+        let rating = stats.rating.unwrap_or(0);
+
+        div().flex().children((1..=MAX_RATING).map(|star| {
+            div()
+                .id(("star", star as usize))
+                .text_color(if star <= rating {
+                    theme.accent
+                } else {
+                    theme.border
+                })
+                .hover(|this| this.text_color(theme.text))
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    cx.stop_propagation();
+                    this.rate(row, star, cx);
+                }))
+                .child(if star <= rating { "★" } else { "☆" })
+        }))
+    }
+
+    fn render_header(&self, cx: &Context<Self>) -> Div {
+        let theme = cx.global::<Theme>();
+
+        div()
+            .w_full()
+            .flex()
+            .px(px(PADDING))
+            .py_2()
+            .border_b_1()
+            .border_color(theme.border)
+            .text_size(theme.font_size_sm)
+            .text_color(theme.text_muted)
+            .children(self.columns.iter().enumerate().map(|(ix, column)| {
+                let column = column.column;
+                let label = label(column);
+                let arrow = match (self.sort == Some(column), self.ascending) {
+                    (false, _) => "",
+                    (true, true) => " ▲",
+                    (true, false) => " ▼",
+                };
+
+                div()
+                    .id(("column", ix))
+                    .relative()
+                    .w(px(self.columns[ix].width))
+                    .flex_shrink_0()
+                    .pr_4()
+                    .truncate()
+                    .hover(|this| this.text_color(theme.text))
+                    .on_click(cx.listener(move |this, _, _, cx| this.sort_by(column, cx)))
+                    .on_drag(DraggedColumn { index: ix, label }, |drag, _, _, cx| {
+                        cx.new(|_| drag.clone())
+                    })
+                    .drag_over::<DraggedColumn>(|style, _, _, cx| {
+                        style.bg(cx.global::<Theme>().highlighted)
+                    })
+                    .on_drop(cx.listener(move |this, drag: &DraggedColumn, _, cx| {
+                        this.move_column(drag.index, ix, cx)
+                    }))
+                    .child(format!("{label}{arrow}"))
+                    .child(
+                        div()
+                            .id(("resize", ix))
+                            .absolute()
+                            .top_0()
+                            .right_0()
+                            .h_full()
+                            .w(px(6.0))
+                            .cursor_col_resize()
+                            .hover(|this| this.bg(theme.border))
+                            .on_click(|_, _, cx| cx.stop_propagation())
+                            .on_drag(ResizeColumn(ix), |drag, _, _, cx| cx.new(|_| drag.clone())),
+                    )
+            }))
+    }
+}
+
+impl Render for TrackTable {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        div()
+            .size_full()
+            .flex()
+            .flex_col()
+            .on_drag_move(cx.listener(Self::resize))
+            .on_drop(cx.listener(|this, _: &ResizeColumn, _, cx| this.save_columns(cx)))
+            .child(self.render_header(cx))
+            .child(
+                uniform_list("tracks", self.rows.len(), cx.processor(Self::render_rows))
+                    .track_scroll(self.scroll_handle.clone())
+                    .flex_1()
+                    .w_full(),
+            )
+    }
+}
+
+fn text(column: Column, track: &Track, stats: TrackStats) -> String {
+    let meta = &track.meta;
+
+    match column {
+        Column::Title => meta.title.clone(),
+        Column::Artist => meta.artists.join(", "),
+        Column::Album => meta.album.clone(),
+        Column::Duration => format_duration(meta.duration),
+        Column::Year => meta.year.map(|year| year.to_string()).unwrap_or_default(),
+        Column::Plays => stats.plays.to_string(),
+        Column::Bitrate => meta
+            .bitrate
+            .map(|bitrate| format!("{bitrate} kbps"))
+            .unwrap_or_default(),
+        Column::Rating => String::new(),
+    }
+}
@@ -0,0 +1,224 @@
+use crate::audio::{analyzer::Snapshot, engine::PlaybackState};
+use crate::config::{Config, VisualizerMode};
+use crate::controller::player::Controller;
+use crate::ui::{components::notifications::report_error, theme::Theme};
+use gpui::*;
+use std::{f32::consts::FRAC_1_SQRT_2, time::Duration};
+
+const BANDS: usize = 24;
+
+/// How far a bar can drop per frame, so peaks fall smoothly instead of
+/// flickering.
+const FALL: f32 = 0.04;
+
+/// Frames drawn by the oscilloscope and vectorscope.
+const SCOPE_FRAMES: usize = 512;
+
+/// Spectrum bars, an oscilloscope or a vectorscope of what's playing.
+/// Clicking it switches to the next mode.
+pub struct Visualizer {
+    bars: Vec<f32>,
+    scope: Vec<[f32; 2]>,
+    animation: Option<Task<()>>,
+}
+
+impl Visualizer {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        cx.observe_global::<Controller>(|this, cx| this.sync_animation(cx))
+            .detach();
+        cx.observe_global::<Config>(|this, cx| this.sync_animation(cx))
+            .detach();
+
+        Visualizer {
+            bars: vec![0.0; BANDS],
+            scope: Vec::new(),
+            animation: None,
+        }
+    }
+
+    /// Redraws at display rate while playing with a mode selected, and
+    /// stays asleep otherwise.
+    fn sync_animation(&mut self, cx: &mut Context<Self>) {
+        let playing = cx.global::<Controller>().state.state == PlaybackState::Playing;
+        let mode = cx.global::<Config>().ui.visualizer;
+
+        if !playing || mode == VisualizerMode::Off {
+            self.animation = None;
+            self.bars = vec![0.0; BANDS];
+            self.scope.clear();
+            cx.notify();
+            return;
+        }
+
+        if self.animation.is_some() {
+            return;
+        }
+
+        self.animation = Some(cx.spawn(async move |this, cx| {
+            loop {
+                cx.background_executor()
+                    .timer(Duration::from_millis(33))
+                    .await;
+
+                let Ok((mode, snapshot)) = this.update(cx, |_, cx| {
+                    let mode = cx.global::<Config>().ui.visualizer;
+                    (mode, cx.global::<Controller>().analyzer.snapshot())
+                }) else {
+                    break;
+                };
+
+                // The FFT would eat into the UI thread's frame budget.
+                let frame = cx
+                    .background_executor()
+                    .spawn(async move { analyze(mode, &snapshot) })
+                    .await;
+
+                if this.update(cx, |this, cx| this.show(frame, cx)).is_err() {
+                    break;
+                }
+            }
+        }));
+    }
+
+    fn show(&mut self, frame: Option<Frame>, cx: &mut Context<Self>) {
+        match frame {
+            Some(Frame::Bars(levels)) => {
+                for (bar, level) in self.bars.iter_mut().zip(levels) {
+                    *bar = level.max(*bar - FALL);
+                }
+            }
+            Some(Frame::Scope(scope)) => self.scope = scope,
+            None => {}
+        }
+
+        cx.notify();
+    }
+}
+
+/// What a mode draws of a snapshot.
+enum Frame {
+    Bars(Vec<f32>),
+    Scope(Vec<[f32; 2]>),
+}
+
+fn analyze(mode: VisualizerMode, snapshot: &Snapshot) -> Option<Frame> {
+    match mode {
+        VisualizerMode::Off => None,
+        VisualizerMode::Spectrum => Some(Frame::Bars(snapshot.spectrum(BANDS))),
+        VisualizerMode::Oscilloscope => Some(Frame::Scope(triggered(snapshot))),
+        VisualizerMode::Vectorscope => {
+            let start = snapshot.frames.len().saturating_sub(SCOPE_FRAMES);
+            Some(Frame::Scope(snapshot.frames[start..].to_vec()))
+        }
+    }
+}
+
+impl Render for Visualizer {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let mode = cx.global::<Config>().ui.visualizer;
+        let color = theme.accent;
+        let bars = self.bars.clone();
+        let scope = self.scope.clone();
+
+        div()
+            .id("visualizer")
+            .w_24()
+            .h_10()
+            .flex_shrink_0()
+            .rounded(theme.radius)
+            .overflow_hidden()
+            .hover(|this| this.bg(theme.highlighted))
+            .on_click(|_, _, cx| {
+                let switched = Config::update(cx, |config| {
+                    config.ui.visualizer = config.ui.visualizer.next()
+                });
+                if let Err(err) = switched {
+                    report_error("Could not update config", err, cx);
+                }
+            })
+            .child(
+                canvas(
+                    |_, _, _| {},
+                    move |bounds, _, window, _| match mode {
+                        VisualizerMode::Off => {}
+                        VisualizerMode::Spectrum => paint_bars(&bars, bounds, color, window),
+                        VisualizerMode::Oscilloscope => {
+                            let points = scope.iter().enumerate().map(|(ix, [left, right])| {
+                                let x = ix as f32 / (SCOPE_FRAMES - 1) as f32;
+                                let y = 0.5 - (left + right) * 0.25;
+                                point(x, y)
+                            });
+                            paint_line(points, bounds, color, window);
+                        }
+                        VisualizerMode::Vectorscope => {
+                            // Rotated so mono sits on the vertical axis.
+                            let points = scope.iter().map(|[left, right]| {
+                                let x = 0.5 + (left - right) * FRAC_1_SQRT_2 * 0.5;
+                                let y = 0.5 - (left + right) * FRAC_1_SQRT_2 * 0.5;
+                                point(x, y)
+                            });
+                            paint_line(points, bounds, color, window);
+                        }
+                    },
+                )
+                .size_full(),
+            )
+    }
+}
+
+fn paint_bars(bars: &[f32], bounds: Bounds<Pixels>, color: Rgba, window: &mut Window) {
+    let slot = bounds.size.width / bars.len() as f32;
+    let gap = px(1.0);
+
+    for (ix, level) in bars.iter().enumerate() {
+        let height = bounds.size.height * level.clamp(0.0, 1.0);
+        let origin = point(
+            bounds.origin.x + slot * ix as f32,
+            bounds.origin.y + bounds.size.height - height,
+        );
+        window.paint_quad(fill(Bounds::new(origin, size(slot - gap, height)), color));
+    }
+}
+
+/// Draws a line through points given as fractions of the bounds.
+fn paint_line(
+    points: impl Iterator<Item = Point<f32>>,
+    bounds: Bounds<Pixels>,
+    color: Rgba,
+    window: &mut Window,
+) {
+    let mut builder = PathBuilder::stroke(px(1.0));
+
+    for (ix, fraction) in points.enumerate() {
+        let position = point(
+            bounds.origin.x + bounds.size.width * fraction.x.clamp(0.0, 1.0),
+            bounds.origin.y + bounds.size.height * fraction.y.clamp(0.0, 1.0),
+        );
+        if ix == 0 {
+            builder.move_to(position);
+        } else {
+            builder.line_to(position);
+        }
+    }
+
+    if let Ok(path) = builder.build() {
+        window.paint_path(path, color);
+    }
+}
+
+/// The latest frames for the oscilloscope, starting at a rising zero
+/// crossing so a steady tone stands still instead of scrolling.
+fn triggered(snapshot: &Snapshot) -> Vec<[f32; 2]> {
+    let mono = snapshot.mono();
+    let Some(latest) = mono.len().checked_sub(SCOPE_FRAMES) else {
+        return snapshot.frames.clone();
+    };
+
+    let start = (1..=latest)
+        .rev()
+        .find(|ix| mono[ix - 1] < 0.0 && mono[*ix] >= 0.0)
+        .unwrap_or(latest);
+
+    snapshot.frames[start..start + SCOPE_FRAMES].to_vec()
+}
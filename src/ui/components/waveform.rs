@@ -0,0 +1,125 @@
+use crate::audio::waveform::Waveform;
+use crate::ui::components::notifications::report_error;
+use gpui::*;
+use std::{
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+/// Width of a waveform bar and the gap after it, in pixels.
+const BAR_WIDTH: f32 = 2.0;
+const BAR_GAP: f32 = 1.0;
+
+/// Waveform of the current track. `path` is the track it belongs to, so a
+/// slow decode can't replace the waveform of a newer track.
+#[derive(Default)]
+pub struct CurrentWaveform {
+    pub path: Option<PathBuf>,
+    pub waveform: Option<Arc<Waveform>>,
+    /// Stops the decode of `path` once another track is loaded.
+    cancel: Arc<AtomicBool>,
+}
+
+impl Global for CurrentWaveform {}
+
+/// Loads the waveform of a newly loaded track in the background, decoding
+/// it if it isn't cached yet. A decode still running for the previous
+/// track is cancelled.
+pub fn load(path: PathBuf, cx: &mut App) {
+    let current = cx.global::<CurrentWaveform>();
+    if current.path.as_ref() == Some(&path) {
+        return;
+    }
+    current.cancel.store(true, Ordering::Relaxed);
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    cx.set_global(CurrentWaveform {
+        path: Some(path.clone()),
+        waveform: None,
+        cancel: cancel.clone(),
+    });
+
+    let task = cx.background_executor().spawn({
+        let path = path.clone();
+        async move { Waveform::load(&path, &cancel) }
+    });
+
+    cx.spawn(async move |cx| {
+        let waveform = task.await;
+
+        let _ = cx.update(|cx| {
+            if cx.global::<CurrentWaveform>().path.as_ref() != Some(&path) {
+                return;
+            }
+
+            match waveform {
+                Ok((waveform, errors)) => {
+                    cx.global_mut::<CurrentWaveform>().waveform = Some(Arc::new(waveform));
+                    for err in errors {
+                        report_error("Could not cache the waveform", err, cx);
+                    }
+                }
+                Err(err) => report_error("Could not load the waveform", err, cx),
+            }
+        });
+    })
+    .detach();
+}
+
+/// Paints the waveform as mirrored bars, peaks faint behind the RMS level,
+/// in `played` up to `progress` and `unplayed` after it. Without a waveform
+/// a flat line stands in while it loads.
+pub fn paint(
+    waveform: Option<&Waveform>,
+    progress: f32,
+    played: Rgba,
+    unplayed: Rgba,
+    bounds: Bounds<Pixels>,
+    window: &mut Window,
+) {
+    let width = f32::from(bounds.size.width);
+    let columns = ((width + BAR_GAP) / (BAR_WIDTH + BAR_GAP)).floor().max(1.0) as usize;
+    let center = bounds.origin.y + bounds.size.height / 2.0;
+
+    for column in 0..columns {
+        let x = bounds.origin.x + px(column as f32 * (BAR_WIDTH + BAR_GAP));
+        let color = if (column as f32 + 0.5) / columns as f32 <= progress {
+            played
+        } else {
+            unplayed
+        };
+
+        let (peak, rms) = match waveform {
+            Some(waveform) => {
+                let len = waveform.peaks.len();
+                let start = column * len / columns;
+                let end = ((column + 1) * len / columns).max(start + 1).min(len);
+                let max = |levels: &[f32]| {
+                    levels[start.min(end)..end]
+                        .iter()
+                        .fold(0.0f32, |max, level| max.max(*level))
+                };
+                (max(&waveform.peaks), max(&waveform.rms))
+            }
+            None => (0.0, 0.0),
+        };
+
+        // At least a sliver, so silence still reads as part of the track.
+        let bar = |level: f32| {
+            let height = (bounds.size.height * level.clamp(0.0, 1.0)).max(px(2.0));
+            Bounds::new(point(x, center - height / 2.0), size(px(BAR_WIDTH), height))
+        };
+
+        window.paint_quad(fill(
+            bar(peak),
+            Rgba {
+                a: color.a * 0.4,
+                ..color
+            },
+        ));
+        window.paint_quad(fill(bar(rms), color));
+    }
+}
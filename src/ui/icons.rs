@@ -10,6 +10,19 @@ pub enum Icons {
     WinRes,
     WinMin,
     Settings,
+    Play,
+    Pause,
+    Stop,
+    SkipBack,
+    SkipForward,
+    Volume,
+    VolumeMuted,
+    Repeat,
+    RepeatOne,
+    Shuffle,
+    Lyrics,
+    MiniPlayer,
+    Expand,
 }
 
 impl IconNamed for Icons {
@@ -22,6 +35,19 @@ impl IconNamed for Icons {
             Icons::WinRes => "icons/window-restore.svg",
             Icons::WinMin => "icons/window-minimize.svg",
             Icons::Settings => "icons/settings.svg",
+            Icons::Play => "icons/play.svg",
+            Icons::Pause => "icons/pause.svg",
+            Icons::Stop => "icons/square.svg",
+            Icons::SkipBack => "icons/skip-back.svg",
+            Icons::SkipForward => "icons/skip-forward.svg",
+            Icons::Volume => "icons/volume-2.svg",
+            Icons::VolumeMuted => "icons/volume-x.svg",
+            Icons::Repeat => "icons/repeat.svg",
+            Icons::RepeatOne => "icons/repeat-1.svg",
+            Icons::Shuffle => "icons/shuffle.svg",
+            Icons::Lyrics => "icons/mic-vocal.svg",
+            Icons::MiniPlayer => "icons/picture-in-picture-2.svg",
+            Icons::Expand => "icons/maximize-2.svg",
         }
         .into()
     }
@@ -0,0 +1,401 @@
+use crate::config::{self, Config};
+use crate::controller::player::Controller;
+use crate::ui::components::{Page, notifications::report_error};
+use anyhow::{Context, Result, anyhow};
+use gpui::{Action, App, Global, KeyBinding, Keystroke, NoAction, actions};
+use notify::RecommendedWatcher;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::PathBuf,
+};
+
+/// Key context set on the main window's root.
+pub const KEY_CONTEXT: &str = "Wiremann";
+
+/// Added to [`KEY_CONTEXT`] while a text field has focus.
+pub const TYPING_CONTEXT: &str = "Typing";
+
+/// Context for bindings without a modifier, which would otherwise fire while
+/// typing into a text field.
+const PLAIN_KEY_CONTEXT: &str = "Wiremann && !Typing";
+
+/// How far the short and long seek shortcuts move, in seconds.
+const SEEK_STEP: f32 = 5.0;
+const JUMP_STEP: f32 = 30.0;
+
+/// How far the volume shortcuts move the volume, in percentage points.
+const VOLUME_STEP: f32 = 5.0;
+
+actions!(
+    wiremann,
+    [
+        PlayPause,
+        Stop,
+        Next,
+        Previous,
+        SeekForward,
+        SeekBackward,
+        JumpForward,
+        JumpBackward,
+        VolumeUp,
+        VolumeDown,
+        ToggleMute,
+        ToggleShuffle,
+        CycleRepeat,
+        ClearQueue,
+        ToggleQueue,
+        ShowHome,
+        ShowPlaylists,
+        ShowLyrics,
+        ShowSettings,
+        ToggleMiniPlayer,
+        FocusSearch,
+        ToggleCommandPalette,
+        ToggleShortcuts,
+        Dismiss,
+    ]
+);
+
+/// An action that can be bound in the keymap file.
+pub struct ActionInfo {
+    /// Name used in the keymap file.
+    pub name: &'static str,
+    pub description: &'static str,
+    pub default_keys: &'static [&'static str],
+    /// Creates the action, for dispatching it without a key press.
+    pub build: fn() -> Box<dyn Action>,
+    binding: fn(&str, &str) -> KeyBinding,
+}
+
+pub const ACTIONS: &[ActionInfo] = &[
+    action::<PlayPause>("play_pause", "Play or pause", &["space"]),
+    action::<Stop>("stop", "Stop", &["ctrl-s"]),
+    action::<Next>("next", "Next track", &["ctrl-right"]),
+    action::<Previous>("previous", "Previous track", &["ctrl-left"]),
+    action::<SeekForward>("seek_forward", "Seek forward 5 seconds", &["right"]),
+    action::<SeekBackward>("seek_backward", "Seek back 5 seconds", &["left"]),
+    action::<JumpForward>("jump_forward", "Seek forward 30 seconds", &["shift-right"]),
+    action::<JumpBackward>("jump_backward", "Seek back 30 seconds", &["shift-left"]),
+    action::<VolumeUp>("volume_up", "Volume up", &["ctrl-up"]),
+    action::<VolumeDown>("volume_down", "Volume down", &["ctrl-down"]),
+    action::<ToggleMute>("toggle_mute", "Mute or unmute", &["m"]),
+    action::<ToggleShuffle>("toggle_shuffle", "Toggle shuffle", &["s"]),
+    action::<CycleRepeat>("cycle_repeat", "Cycle repeat mode", &["r"]),
+    action::<ClearQueue>("clear_queue", "Clear the queue", &["ctrl-shift-backspace"]),
+    action::<ToggleQueue>("toggle_queue", "Show or hide the queue", &["ctrl-u"]),
+    action::<ShowHome>("show_home", "Go to the library", &["ctrl-1"]),
+    action::<ShowPlaylists>("show_playlists", "Go to playlists", &["ctrl-2"]),
+    action::<ShowLyrics>("show_lyrics", "Go to lyrics", &["ctrl-l"]),
+    action::<ShowSettings>("show_settings", "Go to settings", &["ctrl-3", "ctrl-,"]),
+    action::<ToggleMiniPlayer>(
+        "toggle_mini_player",
+        "Switch to or from the mini player",
+        &["ctrl-shift-m"],
+    ),
+    action::<FocusSearch>("focus_search", "Search", &["ctrl-f", "/"]),
+    action::<ToggleCommandPalette>(
+        "command_palette",
+        "Open the command palette",
+        &["ctrl-shift-p"],
+    ),
+    action::<ToggleShortcuts>(
+        "toggle_shortcuts",
+        "Show keyboard shortcuts",
+        &["ctrl-/", "f1"],
+    ),
+    action::<Dismiss>("dismiss", "Close the open overlay", &["escape"]),
+];
+
+const fn action<A: Action + Default>(
+    name: &'static str,
+    description: &'static str,
+    default_keys: &'static [&'static str],
+) -> ActionInfo {
+    ActionInfo {
+        name,
+        description,
+        default_keys,
+        build: build::<A>,
+        binding: binding::<A>,
+    }
+}
+
+fn build<A: Action + Default>() -> Box<dyn Action> {
+    Box::new(A::default())
+}
+
+fn binding<A: Action + Default>(keys: &str, context: &str) -> KeyBinding {
+    KeyBinding::new(keys, A::default(), Some(context))
+}
+
+/// The bindings in effect: the defaults, with any action listed in the
+/// keymap file taking the file's keys instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    /// Keys bound to each entry of [`ACTIONS`], in the same order.
+    pub keys: Vec<Vec<String>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap {
+            keys: ACTIONS
+                .iter()
+                .map(|action| action.default_keys.iter().map(|k| k.to_string()).collect())
+                .collect(),
+        }
+    }
+}
+
+impl Global for Keymap {}
+
+impl Keymap {
+    pub fn path() -> Option<PathBuf> {
+        Config::dir().map(|dir| dir.join("keymap.toml"))
+    }
+
+    /// Loads the keymap file on top of the defaults. Unknown actions,
+    /// unparsable keys and keys bound to more than one action are reported
+    /// and left out; everything else still applies.
+    pub fn load() -> (Self, Vec<anyhow::Error>) {
+        let mut keymap = Keymap::default();
+        let mut errors = Vec::new();
+
+        let Some(path) = Self::path().filter(|path| path.exists()) else {
+            return (keymap, errors);
+        };
+
+        let file: BTreeMap<String, Vec<String>> = match fs::read_to_string(&path)
+            .with_context(|| format!("could not read {}", path.display()))
+            .and_then(|contents| {
+                toml::from_str(&contents)
+                    .with_context(|| format!("invalid keymap in {}", path.display()))
+            }) {
+            Ok(file) => file,
+            Err(err) => return (keymap, vec![err]),
+        };
+
+        // Keys claimed by the file, and the action that claimed them first.
+        let mut claimed: HashMap<String, &str> = HashMap::new();
+        let mut overrides: Vec<(usize, Vec<String>)> = Vec::new();
+
+        for (name, keys) in &file {
+            let Some(index) = ACTIONS.iter().position(|action| action.name == name) else {
+                errors.push(anyhow!("unknown action \"{name}\" in {}", path.display()));
+                continue;
+            };
+
+            let mut bound = Vec::new();
+            for keys in keys {
+                let normalized = match normalize(keys) {
+                    Ok(normalized) => normalized,
+                    Err(err) => {
+                        errors.push(err.context(format!("{name} in {}", path.display())));
+                        continue;
+                    }
+                };
+
+                match claimed.get(&normalized) {
+                    Some(other) => errors.push(anyhow!(
+                        "\"{keys}\" is bound to both {other} and {name} in {}, keeping {other}",
+                        path.display()
+                    )),
+                    None => {
+                        claimed.insert(normalized.clone(), name);
+                        bound.push(normalized);
+                    }
+                }
+            }
+
+            overrides.push((index, bound));
+        }
+
+        // A key taken over by the file no longer triggers its default action.
+        for keys in &mut keymap.keys {
+            keys.retain(|key| !claimed.contains_key(key));
+        }
+        for (index, keys) in overrides {
+            keymap.keys[index] = keys;
+        }
+
+        (keymap, errors)
+    }
+
+    pub fn keys(&self, action: &str) -> &[String] {
+        ACTIONS
+            .iter()
+            .position(|info| info.name == action)
+            .and_then(|index| self.keys.get(index))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Registers the bindings with gpui. Keys that `previous` bound but this
+    /// keymap doesn't are unbound, so a reload can take bindings away.
+    pub fn bind(&self, previous: Option<&Keymap>, cx: &mut App) {
+        let mut bindings = Vec::new();
+
+        if let Some(previous) = previous {
+            for keys in previous.keys.iter().flatten() {
+                if !self.keys.iter().flatten().any(|k| k == keys) {
+                    bindings.push(KeyBinding::new(keys, NoAction, Some(context(keys))));
+                }
+            }
+        }
+
+        for (action, keys) in ACTIONS.iter().zip(&self.keys) {
+            for keys in keys {
+                bindings.push((action.binding)(keys, context(keys)));
+            }
+        }
+
+        cx.bind_keys(bindings);
+    }
+
+    /// Writes the default bindings to the keymap file, if there is none yet,
+    /// so there's something to edit.
+    pub fn ensure_file() -> Result<PathBuf> {
+        let path = Self::path().context("could not determine the config directory")?;
+
+        if !path.exists() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            let mut contents = String::from(
+                "# Each action takes a list of keys, like \"ctrl-shift-p\" or \"space\".\n\
+                 # An empty list unbinds the action.\n\n",
+            );
+            for action in ACTIONS {
+                contents.push_str(&format!("# {}\n", action.description));
+                contents.push_str(&format!(
+                    "{} = {}\n\n",
+                    action.name,
+                    toml::Value::from(action.default_keys.to_vec())
+                ));
+            }
+
+            fs::write(&path, contents)
+                .with_context(|| format!("could not write {}", path.display()))?;
+        }
+
+        Ok(path)
+    }
+
+    /// Signals whenever the keymap file changes. The returned watcher stops
+    /// watching when dropped.
+    pub fn watch(tx: async_channel::Sender<()>) -> Result<RecommendedWatcher> {
+        let dir = Config::dir().context("could not determine the config directory")?;
+        let path = dir.join("keymap.toml");
+
+        config::watch_dir(
+            &dir,
+            move |changed| changed == path,
+            move || {
+                let _ = tx.try_send(());
+            },
+        )
+    }
+}
+
+/// Parses a space-separated sequence of keystrokes and writes it back out
+/// in gpui's canonical form, so equal bindings compare equal.
+fn normalize(keys: &str) -> Result<String> {
+    let keystrokes = keys
+        .split_whitespace()
+        .map(|keystroke| {
+            Keystroke::parse(keystroke)
+                .map(|keystroke| keystroke.unparse())
+                .map_err(|err| anyhow!("invalid key \"{keystroke}\": {err}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if keystrokes.is_empty() {
+        anyhow::bail!("empty key binding");
+    }
+
+    Ok(keystrokes.join(" "))
+}
+
+fn context(keys: &str) -> &'static str {
+    let plain = keys
+        .split_whitespace()
+        .next()
+        .and_then(|keystroke| Keystroke::parse(keystroke).ok())
+        .is_some_and(|keystroke| {
+            let modifiers = keystroke.modifiers;
+            let function_key = keystroke
+                .key
+                .strip_prefix('f')
+                .is_some_and(|n| n.parse::<u8>().is_ok());
+
+            !(modifiers.control || modifiers.alt || modifiers.platform || function_key)
+        });
+
+    if plain {
+        PLAIN_KEY_CONTEXT
+    } else {
+        KEY_CONTEXT
+    }
+}
+
+/// Formats a binding for display, e.g. `ctrl-shift-right` as
+/// `Ctrl+Shift+Right`.
+pub fn display(keys: &str) -> String {
+    keys.split_whitespace()
+        .map(|keystroke| {
+            let (modifiers, key) = match keystroke.strip_suffix("--") {
+                Some(modifiers) => (modifiers, "-"),
+                None => keystroke.rsplit_once('-').unwrap_or(("", keystroke)),
+            };
+
+            modifiers
+                .split('-')
+                .filter(|modifier| !modifier.is_empty())
+                .chain([key])
+                .map(|part| {
+                    let mut chars = part.chars();
+                    match chars.next() {
+                        Some(first) => first.to_uppercase().chain(chars).collect(),
+                        None => String::new(),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("+")
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Registers the handlers for actions that only touch global state. Actions
+/// that need a view, like toggling the shortcuts overlay, are handled by
+/// that view.
+pub fn init(cx: &mut App) {
+    cx.on_action(|_: &PlayPause, cx| cx.global::<Controller>().play_pause());
+    cx.on_action(|_: &Stop, cx| cx.global::<Controller>().stop());
+    cx.on_action(|_: &Next, cx| cx.global::<Controller>().next());
+    cx.on_action(|_: &Previous, cx| cx.global::<Controller>().previous());
+    cx.on_action(|_: &SeekForward, cx| cx.global::<Controller>().seek_by(SEEK_STEP));
+    cx.on_action(|_: &SeekBackward, cx| cx.global::<Controller>().seek_by(-SEEK_STEP));
+    cx.on_action(|_: &JumpForward, cx| cx.global::<Controller>().seek_by(JUMP_STEP));
+    cx.on_action(|_: &JumpBackward, cx| cx.global::<Controller>().seek_by(-JUMP_STEP));
+    cx.on_action(|_: &VolumeUp, cx| cx.global::<Controller>().step_volume(VOLUME_STEP));
+    cx.on_action(|_: &VolumeDown, cx| cx.global::<Controller>().step_volume(-VOLUME_STEP));
+    cx.on_action(|_: &ToggleMute, cx| cx.global::<Controller>().toggle_mute());
+    cx.on_action(|_: &ToggleShuffle, cx| cx.global::<Controller>().toggle_shuffle());
+    cx.on_action(|_: &CycleRepeat, cx| cx.global::<Controller>().cycle_repeat());
+    cx.on_action(|_: &ClearQueue, cx| cx.global::<Controller>().clear_queue());
+    cx.on_action(|_: &ToggleQueue, cx| {
+        let toggled = Config::update(cx, |config| {
+            config.ui.layout.queue = !config.ui.layout.queue
+        });
+        if let Err(err) = toggled {
+            report_error("Could not update config", err, cx);
+        }
+    });
+    cx.on_action(|_: &ShowHome, cx| cx.set_global(Page::Home));
+    cx.on_action(|_: &ShowPlaylists, cx| cx.set_global(Page::Playlists));
+    cx.on_action(|_: &ShowLyrics, cx| cx.set_global(Page::Lyrics));
+    cx.on_action(|_: &ShowSettings, cx| cx.set_global(Page::Settings));
+}
@@ -1,5 +1,8 @@
+pub mod accent;
 pub mod assets;
 pub mod components;
 pub mod icons;
+pub mod keymap;
+pub mod pages;
 pub mod theme;
 pub mod wiremann;
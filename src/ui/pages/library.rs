@@ -0,0 +1,668 @@
+use crate::controller::{metadata::Metadata, player::Controller};
+use crate::library::{
+    Group, Library, Track,
+    playlist::{self, Playlists},
+};
+use crate::ui::components::{
+    LibraryView,
+    tag_editor::TagEditor,
+    track_table::{TrackTable, TrackTableEvent},
+};
+use crate::ui::theme::Theme;
+use gpui::{prelude::FluentBuilder, *};
+use std::{
+    collections::HashMap,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+const GROUP_ROW_HEIGHT: f32 = 40.0;
+const ALBUMS_PER_ROW: usize = 5;
+
+#[derive(Clone)]
+enum MenuAction {
+    Play,
+    PlayNext,
+    Enqueue,
+    AddToPlaylist(String),
+    NewPlaylist,
+    EditTags,
+    ShowInFolder,
+}
+
+struct ContextMenu {
+    position: Point<Pixels>,
+    /// Indices into [`Library::tracks`] the menu acts on.
+    tracks: Vec<usize>,
+}
+
+/// The home page: every track, or the library grouped by artist, album or
+/// genre, with a track list for each group.
+pub struct LibraryPage {
+    table: Entity<TrackTable>,
+    scroll_handle: UniformListScrollHandle,
+    /// Album covers by the path of the album's first track. `None` while
+    /// loading or when the album has no cover.
+    covers: HashMap<PathBuf, Option<Arc<Image>>>,
+    menu: Option<ContextMenu>,
+    tag_editor: Option<Entity<TagEditor>>,
+    /// Where focus goes back to once the tag editor closes.
+    restore_focus: Option<FocusHandle>,
+}
+
+impl LibraryPage {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        let table = cx.new(|cx| TrackTable::new(cx));
+
+        cx.subscribe(&table, |this, _, event: &TrackTableEvent, cx| match event {
+            TrackTableEvent::Activate { tracks, index } => {
+                let library = cx.global::<Library>();
+                let paths = paths(library, tracks);
+                cx.global::<Controller>().play_tracks(paths, *index);
+            }
+            TrackTableEvent::ContextMenu { position, tracks } => {
+                this.open_menu(*position, tracks.clone(), cx);
+            }
+        })
+        .detach();
+
+        cx.observe_global::<Library>(|this, cx| this.refresh(cx))
+            .detach();
+        cx.observe_global::<LibraryView>(|this, cx| {
+            this.menu = None;
+            this.scroll_handle.scroll_to_item(0, ScrollStrategy::Top);
+            this.refresh(cx);
+        })
+        .detach();
+        cx.observe_global::<Playlists>(|_, cx| cx.notify()).detach();
+        cx.observe_global::<Theme>(|_, cx| cx.notify()).detach();
+
+        let mut page = LibraryPage {
+            table,
+            scroll_handle: UniformListScrollHandle::new(),
+            covers: HashMap::new(),
+            menu: None,
+            tag_editor: None,
+            restore_focus: None,
+        };
+        page.refresh(cx);
+        page
+    }
+
+    /// Whether a text field on this page is taking keyboard input.
+    pub fn is_editing(&self) -> bool {
+        self.tag_editor.is_some()
+    }
+
+    /// Shows the tracks of the current view in the table.
+    fn refresh(&mut self, cx: &mut Context<Self>) {
+        let library = cx.global::<Library>();
+        let tracks = match cx.global::<LibraryView>() {
+            LibraryView::Tracks => (0..library.tracks.len()).collect(),
+            LibraryView::Artist(name) => group_tracks(&library.artists, name, ""),
+            LibraryView::Album { name, artist } => group_tracks(&library.albums, name, artist),
+            LibraryView::Genre(name) => group_tracks(&library.genres, name, ""),
+            LibraryView::Artists | LibraryView::Albums | LibraryView::Genres => Vec::new(),
+        };
+
+        self.table
+            .update(cx, |table, cx| table.set_tracks(tracks, cx));
+        cx.notify();
+    }
+
+    fn open_menu(&mut self, position: Point<Pixels>, tracks: Vec<usize>, cx: &mut Context<Self>) {
+        if !tracks.is_empty() {
+            self.menu = Some(ContextMenu { position, tracks });
+            cx.notify();
+        }
+    }
+
+    fn close_menu(&mut self, cx: &mut Context<Self>) {
+        self.menu = None;
+        cx.notify();
+    }
+
+    fn run(&mut self, action: MenuAction, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(menu) = self.menu.take() else {
+            return;
+        };
+        cx.notify();
+
+        let library = cx.global::<Library>();
+        let paths = paths(library, &menu.tracks);
+
+        match action {
+            MenuAction::Play => cx.global::<Controller>().play_tracks(paths, 0),
+            MenuAction::PlayNext => cx.global::<Controller>().play_next(paths),
+            MenuAction::Enqueue => cx.global::<Controller>().enqueue(paths),
+            MenuAction::AddToPlaylist(name) => playlist::add_to_playlist(&name, paths, cx),
+            MenuAction::NewPlaylist => {
+                let name = new_playlist_name(cx.global::<Playlists>());
+                playlist::add_to_playlist(&name, paths, cx);
+            }
+            MenuAction::EditTags => {
+                let tracks = menu
+                    .tracks
+                    .iter()
+                    .filter_map(|ix| library.tracks.get(*ix).cloned())
+                    .collect();
+                self.open_tag_editor(tracks, window, cx);
+            }
+            MenuAction::ShowInFolder => {
+                if let Some(path) = paths.first() {
+                    cx.reveal_path(path);
+                }
+            }
+        }
+    }
+
+    fn open_tag_editor(&mut self, tracks: Vec<Track>, window: &mut Window, cx: &mut Context<Self>) {
+        self.restore_focus = window.focused(cx);
+        let editor = cx.new(|cx| TagEditor::new(tracks, window, cx));
+
+        cx.subscribe_in(&editor, window, |this, _, _: &DismissEvent, window, cx| {
+            this.close_tag_editor(window, cx);
+        })
+        .detach();
+
+        self.tag_editor = Some(editor);
+    }
+
+    fn close_tag_editor(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.tag_editor = None;
+        if let Some(focus) = self.restore_focus.take() {
+            window.focus(&focus);
+        }
+        cx.notify();
+    }
+
+    /// Loads an album's cover in the background the first time it's shown.
+    fn cover(&mut self, path: &Path, cx: &mut Context<Self>) -> Option<Arc<Image>> {
+        if let Some(cover) = self.covers.get(path) {
+            return cover.clone();
+        }
+
+        let path = path.to_path_buf();
+        self.covers.insert(path.clone(), None);
+
+        let task = cx.background_executor().spawn({
+            let path = path.clone();
+            async move {
+                let bytes = Metadata::cover(&path)?;
+                let format = image_format(&bytes)?;
+                Some(Arc::new(Image::from_bytes(format, bytes)))
+            }
+        });
+
+        cx.spawn(async move |this, cx| {
+            let cover = task.await;
+            if cover.is_some() {
+                let _ = this.update(cx, |this, cx| {
+                    this.covers.insert(path, cover);
+                    cx.notify();
+                });
+            }
+        })
+        .detach();
+
+        None
+    }
+
+    fn render_groups(
+        &mut self,
+        range: Range<usize>,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Vec<Stateful<Div>> {
+        let theme = cx.global::<Theme>();
+        let library = cx.global::<Library>();
+        let groups = match cx.global::<LibraryView>() {
+            LibraryView::Genres => &library.genres,
+            _ => &library.artists,
+        };
+
+        range
+            .filter_map(|ix| {
+                let group = groups.get(ix)?;
+                let view = match cx.global::<LibraryView>() {
+                    LibraryView::Genres => LibraryView::Genre(group.name.clone()),
+                    _ => LibraryView::Artist(group.name.clone()),
+                };
+                let tracks = group.tracks.clone();
+
+                Some(
+                    div()
+                        .id(("group", ix))
+                        .h(px(GROUP_ROW_HEIGHT))
+                        .w_full()
+                        .flex()
+                        .items_center()
+                        .justify_between()
+                        .px_4()
+                        .rounded(theme.radius)
+                        .hover(|this| this.bg(theme.highlighted))
+                        .on_click(move |_, _, cx| cx.set_global(view.clone()))
+                        .on_mouse_down(
+                            MouseButton::Right,
+                            cx.listener(move |this, event: &MouseDownEvent, _, cx| {
+                                this.open_menu(event.position, tracks.clone(), cx)
+                            }),
+                        )
+                        .child(div().truncate().child(group.name.clone()))
+                        .child(
+                            div()
+                                .flex_shrink_0()
+                                .text_size(theme.font_size_sm)
+                                .text_color(theme.text_muted)
+                                .child(format!("{} tracks", group.tracks.len())),
+                        ),
+                )
+            })
+            .collect()
+    }
+
+    fn render_albums(
+        &mut self,
+        range: Range<usize>,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Vec<Stateful<Div>> {
+        range
+            .map(|row| {
+                let start = row * ALBUMS_PER_ROW;
+                let library = cx.global::<Library>();
+                let albums: Vec<(usize, Group, Option<PathBuf>)> = library
+                    .albums
+                    .iter()
+                    .enumerate()
+                    .skip(start)
+                    .take(ALBUMS_PER_ROW)
+                    .map(|(ix, album)| {
+                        let first = album
+                            .tracks
+                            .first()
+                            .and_then(|track| library.tracks.get(*track))
+                            .map(|track| track.path.clone());
+                        (ix, album.clone(), first)
+                    })
+                    .collect();
+
+                let cards: Vec<Stateful<Div>> = albums
+                    .into_iter()
+                    .map(|(ix, album, first)| {
+                        let cover = first.and_then(|path| self.cover(&path, cx));
+                        self.album_card(ix, album, cover, cx)
+                    })
+                    .collect();
+
+                div()
+                    .id(("album_row", row))
+                    .w_full()
+                    .flex()
+                    .gap_4()
+                    .pb_4()
+                    .children(cards)
+            })
+            .collect()
+    }
+
+    fn album_card(
+        &self,
+        ix: usize,
+        album: Group,
+        cover: Option<Arc<Image>>,
+        cx: &mut Context<Self>,
+    ) -> Stateful<Div> {
+        let theme = cx.global::<Theme>();
+        let view = LibraryView::Album {
+            name: album.name.clone(),
+            artist: album.artist.clone(),
+        };
+        let tracks = album.tracks.clone();
+        let name = album.name.clone();
+        let artist = album.artist.clone();
+
+        div()
+            .id(("album", ix))
+            .w_40()
+            .flex()
+            .flex_col()
+            .gap_1()
+            .p_2()
+            .rounded(theme.radius_lg)
+            .hover(|this| this.bg(theme.highlighted))
+            .on_click(move |event, _, cx| {
+                if event.click_count() > 1 {
+                    let library = cx.global::<Library>();
+                    let paths = library.paths(&album);
+                    cx.global::<Controller>().play_tracks(paths, 0);
+                } else {
+                    cx.set_global(view.clone());
+                }
+            })
+            .on_mouse_down(
+                MouseButton::Right,
+                cx.listener(move |this, event: &MouseDownEvent, _, cx| {
+                    this.open_menu(event.position, tracks.clone(), cx)
+                }),
+            )
+            .child(
+                div()
+                    .size_36()
+                    .rounded(theme.radius)
+                    .overflow_hidden()
+                    .bg(theme.panel)
+                    .when_some(cover, |this, cover| {
+                        this.child(img(cover).size_full().object_fit(ObjectFit::Cover))
+                    }),
+            )
+            .child(div().truncate().child(name))
+            .child(
+                div()
+                    .truncate()
+                    .text_size(theme.font_size_sm)
+                    .text_color(theme.text_muted)
+                    .child(artist),
+            )
+    }
+
+    fn render_menu(&self, menu: &ContextMenu, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let playlists: Vec<String> = cx
+            .global::<Playlists>()
+            .playlists
+            .iter()
+            .map(|playlist| playlist.name.clone())
+            .collect();
+        let count = menu.tracks.len();
+
+        let item = |id: ElementId, label: SharedString, action: MenuAction| {
+            div()
+                .id(id)
+                .px_3()
+                .py_1()
+                .rounded(theme.radius)
+                .truncate()
+                .hover(|this| this.bg(theme.highlighted))
+                .on_click(
+                    cx.listener(move |this, _, window, cx| this.run(action.clone(), window, cx)),
+                )
+                .child(label)
+        };
+        let separator = || div().my_1().h_px().bg(theme.border);
+
+        let menu_element = div()
+            .id("library_menu")
+            .w_56()
+            .flex()
+            .flex_col()
+            .p_1()
+            .rounded(theme.radius_lg)
+            .bg(theme.panel)
+            .border_1()
+            .border_color(theme.border)
+            .text_size(theme.font_size_sm)
+            .text_color(theme.text)
+            .on_mouse_down_out(cx.listener(|this, _, _, cx| this.close_menu(cx)))
+            .child(
+                div()
+                    .px_3()
+                    .py_1()
+                    .text_color(theme.text_muted)
+                    .child(match count {
+                        1 => String::from("1 track"),
+                        n => format!("{n} tracks"),
+                    }),
+            )
+            .child(item("menu_play".into(), "Play".into(), MenuAction::Play))
+            .child(item(
+                "menu_play_next".into(),
+                "Play next".into(),
+                MenuAction::PlayNext,
+            ))
+            .child(item(
+                "menu_enqueue".into(),
+                "Add to queue".into(),
+                MenuAction::Enqueue,
+            ))
+            .child(separator())
+            .children(playlists.into_iter().enumerate().map(|(ix, name)| {
+                item(
+                    ("menu_playlist", ix).into(),
+                    format!("Add to {name}").into(),
+                    MenuAction::AddToPlaylist(name),
+                )
+            }))
+            .child(item(
+                "menu_new_playlist".into(),
+                "Add to new playlist".into(),
+                MenuAction::NewPlaylist,
+            ))
+            .child(separator())
+            .child(item(
+                "menu_edit_tags".into(),
+                "Edit tags…".into(),
+                MenuAction::EditTags,
+            ))
+            .child(item(
+                "menu_show_in_folder".into(),
+                "Show in folder".into(),
+                MenuAction::ShowInFolder,
+            ));
+
+        deferred(
+            anchored()
+                .position(menu.position)
+                .snap_to_window_with_margin(px(8.0))
+                .child(menu_element),
+        )
+        .with_priority(1)
+    }
+
+    fn render_header(&self, view: &LibraryView, cx: &mut Context<Self>) -> Div {
+        let theme = cx.global::<Theme>();
+
+        let (parent, title) = match view {
+            LibraryView::Artist(name) => (LibraryView::Artists, name.clone()),
+            LibraryView::Album { name, .. } => (LibraryView::Albums, name.clone()),
+            LibraryView::Genre(name) => (LibraryView::Genres, name.clone()),
+            _ => {
+                let tabs = [
+                    (LibraryView::Tracks, "Tracks"),
+                    (LibraryView::Artists, "Artists"),
+                    (LibraryView::Albums, "Albums"),
+                    (LibraryView::Genres, "Genres"),
+                ];
+
+                return div()
+                    .flex()
+                    .gap_2()
+                    .children(tabs.into_iter().map(|(tab, label)| {
+                        let active = &tab == view;
+
+                        div()
+                            .id(label)
+                            .px_3()
+                            .py_1()
+                            .rounded(theme.radius)
+                            .bg(if active { theme.accent } else { theme.bg })
+                            .hover(|this| {
+                                if active {
+                                    this.bg(theme.accent)
+                                } else {
+                                    this.bg(theme.highlighted)
+                                }
+                            })
+                            .on_click(move |_, _, cx| cx.set_global(tab.clone()))
+                            .child(label)
+                    }));
+            }
+        };
+
+        let back = match parent {
+            LibraryView::Artists => "‹ Artists",
+            LibraryView::Albums => "‹ Albums",
+            _ => "‹ Genres",
+        };
+
+        div()
+            .flex()
+            .items_center()
+            .gap_4()
+            .child(
+                div()
+                    .id("library_back")
+                    .px_3()
+                    .py_1()
+                    .rounded(theme.radius)
+                    .text_color(theme.text_muted)
+                    .hover(|this| this.bg(theme.highlighted).text_color(theme.text))
+                    .on_click(move |_, _, cx| cx.set_global(parent.clone()))
+                    .child(back),
+            )
+            .child(
+                div()
+                    .flex_1()
+                    .truncate()
+                    .text_size(theme.font_size_lg)
+                    .child(title),
+            )
+            .child(
+                div()
+                    .id("library_play_all")
+                    .px_3()
+                    .py_1()
+                    .rounded(theme.radius)
+                    .bg(theme.accent)
+                    .on_click(cx.listener(|this, _, _, cx| {
+                        let library = cx.global::<Library>();
+                        let tracks = this.table.read(cx).rows().to_vec();
+                        let paths = paths(library, &tracks);
+                        cx.global::<Controller>().play_tracks(paths, 0);
+                    }))
+                    .child("Play all"),
+            )
+    }
+}
+
+impl Render for LibraryPage {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let view = cx.global::<LibraryView>().clone();
+        let library = cx.global::<Library>();
+        let empty = library.tracks.is_empty();
+        let scanning = library.scanning;
+        let album_rows = library.albums.len().div_ceil(ALBUMS_PER_ROW);
+        let group_count = match view {
+            LibraryView::Artists => library.artists.len(),
+            LibraryView::Genres => library.genres.len(),
+            _ => 0,
+        };
+
+        let content = if empty {
+            div()
+                .size_full()
+                .flex()
+                .items_center()
+                .justify_center()
+                .text_color(theme.text_muted)
+                .child(if scanning {
+                    "Scanning…"
+                } else {
+                    "Your library is empty. Add a folder in Settings."
+                })
+                .into_any_element()
+        } else {
+            match view {
+                LibraryView::Artists | LibraryView::Genres => uniform_list(
+                    "library_groups",
+                    group_count,
+                    cx.processor(Self::render_groups),
+                )
+                .track_scroll(self.scroll_handle.clone())
+                .size_full()
+                .into_any_element(),
+                LibraryView::Albums => uniform_list(
+                    "library_albums",
+                    album_rows,
+                    cx.processor(Self::render_albums),
+                )
+                .track_scroll(self.scroll_handle.clone())
+                .size_full()
+                .into_any_element(),
+                _ => self.table.clone().into_any_element(),
+            }
+        };
+
+        div()
+            .id("library_page")
+            .size_full()
+            .relative()
+            .flex()
+            .flex_col()
+            .gap_4()
+            .p_4()
+            .text_color(theme.text)
+            .child(self.render_header(&view, cx))
+            .child(div().flex_1().min_h_0().child(content))
+            .when_some(self.menu.as_ref(), |this, menu| {
+                this.child(self.render_menu(menu, cx))
+            })
+            .when_some(self.tag_editor.clone(), |this, editor| {
+                this.child(
+                    div()
+                        .id("tag_editor_overlay")
+                        .absolute()
+                        .top_0()
+                        .left_0()
+                        .size_full()
+                        .flex()
+                        .items_center()
+                        .justify_center()
+                        .bg(rgba(0x00000080))
+                        .on_click(
+                            cx.listener(|this, _, window, cx| this.close_tag_editor(window, cx)),
+                        )
+                        .child(editor),
+                )
+            })
+    }
+}
+
+/// Track indices of the group with this name (and album artist).
+fn group_tracks(groups: &[Group], name: &str, artist: &str) -> Vec<usize> {
+    groups
+        .iter()
+        .find(|group| group.name == name && group.artist == artist)
+        .map(|group| group.tracks.clone())
+        .unwrap_or_default()
+}
+
+fn paths(library: &Library, tracks: &[usize]) -> Vec<PathBuf> {
+    tracks
+        .iter()
+        .filter_map(|ix| library.tracks.get(*ix))
+        .map(|track| track.path.clone())
+        .collect()
+}
+
+/// First free name of the form "Playlist N".
+fn new_playlist_name(playlists: &Playlists) -> String {
+    (1..)
+        .map(|n| format!("Playlist {n}"))
+        .find(|name| playlists.get(name).is_none())
+        .unwrap_or_default()
+}
+
+pub fn image_format(bytes: &[u8]) -> Option<ImageFormat> {
+    match ::image::guess_format(bytes).ok()? {
+        ::image::ImageFormat::Png => Some(ImageFormat::Png),
+        ::image::ImageFormat::Jpeg => Some(ImageFormat::Jpeg),
+        ::image::ImageFormat::Gif => Some(ImageFormat::Gif),
+        ::image::ImageFormat::WebP => Some(ImageFormat::Webp),
+        ::image::ImageFormat::Bmp => Some(ImageFormat::Bmp),
+        ::image::ImageFormat::Tiff => Some(ImageFormat::Tiff),
+        _ => None,
+    }
+}
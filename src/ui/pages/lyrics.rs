@@ -0,0 +1,294 @@
+use crate::audio::engine::PlaybackState;
+use crate::controller::{
+    lyrics::{CurrentLyrics, LyricLine},
+    player::Controller,
+};
+use crate::ui::{components::lyrics_editor::LyricsEditor, theme::Theme};
+use gpui::{prelude::FluentBuilder, *};
+use std::time::Duration;
+
+/// Lyrics of the current track. Synchronized lyrics follow playback, with
+/// the current line highlighted and kept in the middle of the page; clicking
+/// a line seeks to it.
+pub struct LyricsPage {
+    scroll_handle: ScrollHandle,
+    /// Line highlighted in the last frame, to scroll only when it changes.
+    current: Option<usize>,
+    animation: Option<Task<()>>,
+    editor: Option<Entity<LyricsEditor>>,
+    /// Where focus goes back to once the editor closes.
+    restore_focus: Option<FocusHandle>,
+}
+
+impl LyricsPage {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        cx.observe_global::<Controller>(|this, cx| this.sync_animation(cx))
+            .detach();
+        cx.observe_global::<CurrentLyrics>(|this, cx| {
+            this.current = None;
+            this.scroll_handle.set_offset(Point::default());
+            cx.notify();
+        })
+        .detach();
+
+        LyricsPage {
+            scroll_handle: ScrollHandle::new(),
+            current: None,
+            animation: None,
+            editor: None,
+            restore_focus: None,
+        }
+    }
+
+    /// Whether the editor's text field is taking keyboard input.
+    pub fn is_editing(&self, cx: &App) -> bool {
+        self.editor
+            .as_ref()
+            .is_some_and(|editor| editor.read(cx).is_typing())
+    }
+
+    fn open_editor(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let current = cx.global::<CurrentLyrics>();
+        let Some(path) = current.path.clone() else {
+            return;
+        };
+        let lyrics = current.lyrics.clone();
+
+        self.restore_focus = window.focused(cx);
+        let editor = cx.new(|cx| LyricsEditor::new(path, lyrics, window, cx));
+
+        cx.subscribe_in(&editor, window, |this, _, _: &DismissEvent, window, cx| {
+            this.editor = None;
+            if let Some(focus) = this.restore_focus.take() {
+                window.focus(&focus);
+            }
+            cx.notify();
+        })
+        .detach();
+
+        self.editor = Some(editor);
+        cx.notify();
+    }
+
+    /// Follows the position like the control bar does, only while playing.
+    fn sync_animation(&mut self, cx: &mut Context<Self>) {
+        self.update_line(cx);
+
+        if cx.global::<Controller>().state.state != PlaybackState::Playing {
+            self.animation = None;
+            return;
+        }
+
+        if self.animation.is_some() {
+            return;
+        }
+
+        self.animation = Some(cx.spawn(async move |this, cx| {
+            loop {
+                cx.background_executor()
+                    .timer(Duration::from_millis(33))
+                    .await;
+
+                if this.update(cx, |this, cx| this.update_line(cx)).is_err() {
+                    break;
+                }
+            }
+        }));
+    }
+
+    fn update_line(&mut self, cx: &mut Context<Self>) {
+        let Some(lyrics) = &cx.global::<CurrentLyrics>().lyrics else {
+            return;
+        };
+
+        let current = lyrics.line_at(cx.global::<Controller>().position());
+        if current != self.current {
+            self.current = current;
+            if let Some(current) = current {
+                self.center(current);
+            }
+        }
+
+        // Word timings move within a line too.
+        cx.notify();
+    }
+
+    /// Scrolls the line to the middle of the page, using the layout of the
+    /// last frame.
+    fn center(&self, line: usize) {
+        let Some(item) = self.scroll_handle.bounds_for_item(line) else {
+            return;
+        };
+
+        let viewport = self.scroll_handle.bounds();
+        let offset = self.scroll_handle.offset();
+        let max = self.scroll_handle.max_offset().height;
+        let y = offset.y - (item.center().y - viewport.center().y);
+
+        self.scroll_handle
+            .set_offset(point(offset.x, y.clamp(-max, px(0.0))));
+    }
+
+    fn render_line(
+        &self,
+        index: usize,
+        line: &LyricLine,
+        position: Duration,
+        theme: &Theme,
+    ) -> Stateful<Div> {
+        let current = self.current == Some(index);
+        let text: SharedString = if line.text.is_empty() {
+            "♪".into()
+        } else {
+            line.text.clone().into()
+        };
+
+        // Enhanced LRC: color the words already sung.
+        let sung = if current && !line.words.is_empty() {
+            let unsung: String = line
+                .words
+                .iter()
+                .filter(|(time, _)| *time > position)
+                .map(|(_, word)| word.as_str())
+                .collect();
+            let end = text.len().saturating_sub(unsung.trim_end().len());
+            text.is_char_boundary(end).then_some(end)
+        } else {
+            None
+        };
+
+        let label = match sung {
+            Some(end) => StyledText::new(text).with_highlights([(
+                0..end,
+                HighlightStyle {
+                    color: Some(theme.accent.into()),
+                    ..Default::default()
+                },
+            )]),
+            None => StyledText::new(text),
+        };
+
+        div()
+            .id(("lyric", index))
+            .w_full()
+            .max_w(px(720.0))
+            .px_4()
+            .py_2()
+            .rounded(theme.radius)
+            .text_center()
+            .text_color(if current || line.time.is_none() {
+                theme.text
+            } else {
+                theme.text_muted
+            })
+            .when(current, |this| this.font_weight(FontWeight::BOLD))
+            .when_some(line.time, |this, time| {
+                this.cursor_pointer()
+                    .hover(|this| this.bg(theme.highlighted))
+                    .on_click(move |_, _, cx| cx.global::<Controller>().seek(time))
+            })
+            .child(label)
+    }
+}
+
+impl Render for LyricsPage {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        if let Some(editor) = self.editor.clone() {
+            return div().size_full().bg(cx.global::<Theme>().bg).child(editor);
+        }
+
+        let theme = cx.global::<Theme>();
+        let controller = cx.global::<Controller>();
+        let position = controller.position();
+        let current = cx.global::<CurrentLyrics>();
+
+        let message = |message: &'static str| {
+            div()
+                .size_full()
+                .flex()
+                .items_center()
+                .justify_center()
+                .text_color(theme.text_muted)
+                .child(message)
+                .into_any_element()
+        };
+
+        let content = match (&current.path, &current.lyrics) {
+            (None, _) => message("Nothing is playing"),
+            (Some(_), None) => message("No lyrics for this track"),
+            (Some(_), Some(lyrics)) => {
+                let lines: Vec<_> = lyrics
+                    .lines
+                    .iter()
+                    .enumerate()
+                    .map(|(index, line)| self.render_line(index, line, position, theme))
+                    .collect();
+
+                // Lines are direct children so the scroll handle knows
+                // where each one is.
+                div()
+                    .id("lyrics")
+                    .size_full()
+                    .flex()
+                    .flex_col()
+                    .items_center()
+                    .py_16()
+                    .overflow_y_scroll()
+                    .track_scroll(&self.scroll_handle)
+                    .children(lines)
+                    .into_any_element()
+            }
+        };
+
+        let meta = controller.state.meta.as_ref();
+
+        div()
+            .size_full()
+            .flex()
+            .flex_col()
+            .bg(theme.bg)
+            .text_size(theme.font_size_lg)
+            .when_some(meta, |this, meta| {
+                this.child(
+                    div()
+                        .flex_shrink_0()
+                        .px_4()
+                        .pt_4()
+                        .flex()
+                        .flex_col()
+                        .items_center()
+                        .child(div().text_color(theme.text).child(meta.title.clone()))
+                        .child(
+                            div()
+                                .text_size(theme.font_size_sm)
+                                .text_color(theme.text_muted)
+                                .child(meta.artists.join(", ")),
+                        ),
+                )
+            })
+            .when(current.path.is_some(), |this| {
+                this.child(
+                    div().flex().justify_center().pt_2().child(
+                        div()
+                            .id("edit_lyrics")
+                            .px_3()
+                            .py_1()
+                            .rounded(theme.radius)
+                            .bg(theme.highlighted)
+                            .hover(|this| this.bg(theme.border))
+                            .text_size(theme.font_size_sm)
+                            .text_color(theme.text)
+                            .on_click(
+                                cx.listener(|this, _, window, cx| this.open_editor(window, cx)),
+                            )
+                            .child(if current.lyrics.is_some() {
+                                "Edit lyrics"
+                            } else {
+                                "Add lyrics"
+                            }),
+                    ),
+                )
+            })
+            .child(div().flex_1().min_h_0().child(content))
+    }
+}
@@ -0,0 +1,4 @@
+pub mod library;
+pub mod lyrics;
+pub mod playlists;
+pub mod settings;
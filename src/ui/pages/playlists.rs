@@ -0,0 +1,222 @@
+use crate::controller::player::Controller;
+use crate::library::{Library, playlist::Playlists};
+use crate::ui::components::queue::describe;
+use crate::ui::theme::Theme;
+use gpui::{prelude::FluentBuilder, *};
+use std::ops::Range;
+
+const ROW_HEIGHT: f32 = 44.0;
+
+/// The playlists page: every saved playlist on the left, and the tracks of
+/// the selected one on the right.
+pub struct PlaylistsPage {
+    /// Name of the selected playlist; the first one when unset or gone.
+    selected: Option<String>,
+    scroll_handle: UniformListScrollHandle,
+}
+
+impl PlaylistsPage {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        cx.observe_global::<Playlists>(|_, cx| cx.notify()).detach();
+        cx.observe_global::<Library>(|_, cx| cx.notify()).detach();
+        cx.observe_global::<Theme>(|_, cx| cx.notify()).detach();
+
+        PlaylistsPage {
+            selected: None,
+            scroll_handle: UniformListScrollHandle::new(),
+        }
+    }
+
+    fn select(&mut self, name: String, cx: &mut Context<Self>) {
+        self.selected = Some(name);
+        self.scroll_handle.scroll_to_item(0, ScrollStrategy::Top);
+        cx.notify();
+    }
+
+    /// Index of the selected playlist in [`Playlists::playlists`].
+    fn selected_index(&self, playlists: &Playlists) -> Option<usize> {
+        self.selected
+            .as_ref()
+            .and_then(|name| playlists.playlists.iter().position(|p| &p.name == name))
+            .or((!playlists.playlists.is_empty()).then_some(0))
+    }
+
+    fn play(&self, index: usize, cx: &mut Context<Self>) {
+        let playlists = cx.global::<Playlists>();
+        if let Some(playlist) = self
+            .selected_index(playlists)
+            .and_then(|ix| playlists.playlists.get(ix))
+        {
+            cx.global::<Controller>()
+                .play_tracks(playlist.tracks.clone(), index);
+        }
+    }
+
+    fn render_tracks(
+        &mut self,
+        range: Range<usize>,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Vec<Stateful<Div>> {
+        let theme = cx.global::<Theme>();
+        let library = cx.global::<Library>();
+        let playlists = cx.global::<Playlists>();
+        let Some(playlist) = self
+            .selected_index(playlists)
+            .and_then(|ix| playlists.playlists.get(ix))
+        else {
+            return Vec::new();
+        };
+
+        range
+            .filter_map(|index| {
+                let (title, artist) = describe(library, playlist.tracks.get(index)?);
+
+                Some(
+                    div()
+                        .id(("playlist_track", index))
+                        .h(px(ROW_HEIGHT))
+                        .w_full()
+                        .flex()
+                        .flex_col()
+                        .justify_center()
+                        .px_4()
+                        .rounded(theme.radius)
+                        .hover(|this| this.bg(theme.highlighted))
+                        .on_click(cx.listener(move |this, event: &ClickEvent, _, cx| {
+                            if event.click_count() > 1 {
+                                this.play(index, cx);
+                            }
+                        }))
+                        .child(div().truncate().text_color(theme.text).child(title))
+                        .child(
+                            div()
+                                .truncate()
+                                .text_size(theme.font_size_sm)
+                                .text_color(theme.text_muted)
+                                .child(artist),
+                        ),
+                )
+            })
+            .collect()
+    }
+}
+
+impl Render for PlaylistsPage {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let playlists = cx.global::<Playlists>();
+
+        if playlists.playlists.is_empty() {
+            return div()
+                .id("playlists_page")
+                .size_full()
+                .flex()
+                .items_center()
+                .justify_center()
+                .text_color(theme.text_muted)
+                .child("No playlists yet. Save the queue or add tracks from the library.");
+        }
+
+        let selected = self.selected_index(playlists).unwrap_or_default();
+        let playlist = &playlists.playlists[selected];
+        let track_count = playlist.tracks.len();
+
+        let rows = playlists
+            .playlists
+            .iter()
+            .enumerate()
+            .map(|(ix, playlist)| {
+                let name = playlist.name.clone();
+
+                div()
+                    .id(("playlist", ix))
+                    .px_3()
+                    .py_2()
+                    .flex()
+                    .justify_between()
+                    .gap_2()
+                    .rounded(theme.radius)
+                    .when(ix == selected, |this| this.bg(theme.highlighted))
+                    .hover(|this| this.bg(theme.highlighted))
+                    .on_click(cx.listener(move |this, _, _, cx| this.select(name.clone(), cx)))
+                    .child(div().truncate().child(playlist.name.clone()))
+                    .child(
+                        div()
+                            .flex_shrink_0()
+                            .text_size(theme.font_size_sm)
+                            .text_color(theme.text_muted)
+                            .child(playlist.tracks.len().to_string()),
+                    )
+            });
+
+        let list = div()
+            .id("playlists_list")
+            .w_64()
+            .flex_shrink_0()
+            .flex()
+            .flex_col()
+            .gap_1()
+            .overflow_y_scroll()
+            .children(rows);
+
+        let header = div()
+            .flex()
+            .items_center()
+            .gap_4()
+            .child(
+                div()
+                    .flex_1()
+                    .truncate()
+                    .text_size(theme.font_size_lg)
+                    .child(playlist.name.clone()),
+            )
+            .child(
+                div()
+                    .text_size(theme.font_size_sm)
+                    .text_color(theme.text_muted)
+                    .child(match track_count {
+                        1 => String::from("1 track"),
+                        n => format!("{n} tracks"),
+                    }),
+            )
+            .child(
+                div()
+                    .id("playlist_play")
+                    .px_3()
+                    .py_1()
+                    .rounded(theme.radius)
+                    .bg(theme.accent)
+                    .on_click(cx.listener(|this, _, _, cx| this.play(0, cx)))
+                    .child("Play"),
+            );
+
+        div()
+            .id("playlists_page")
+            .size_full()
+            .flex()
+            .gap_4()
+            .p_4()
+            .text_color(theme.text)
+            .child(list)
+            .child(
+                div()
+                    .flex_1()
+                    .min_w_0()
+                    .flex()
+                    .flex_col()
+                    .gap_4()
+                    .child(header)
+                    .child(
+                        uniform_list(
+                            "playlist_tracks",
+                            track_count,
+                            cx.processor(Self::render_tracks),
+                        )
+                        .track_scroll(self.scroll_handle.clone())
+                        .flex_1()
+                        .w_full(),
+                    ),
+            )
+    }
+}
@@ -0,0 +1,721 @@
+use crate::config::{Config, ControlBarPosition, DropAction, ReplayGainMode, VisualizerMode};
+use crate::controller::player::Controller;
+use crate::library::{self, Library};
+use crate::ui::components::{notifications::report_error, shortcuts::shortcut_row};
+use crate::ui::keymap::{ACTIONS, Keymap};
+use crate::ui::theme::{Appearance, SYSTEM_THEME, Theme, ThemeRegistry};
+use gpui::{prelude::FluentBuilder, *};
+use gpui_component::slider::{Slider, SliderEvent, SliderState};
+use std::{path::PathBuf, time::Duration};
+
+/// How long a slider has to rest before its value is saved.
+const SLIDER_SAVE_DELAY: Duration = Duration::from_millis(500);
+
+pub struct SettingsPage {
+    preamp_slider_state: Entity<SliderState>,
+    crossfade_slider_state: Entity<SliderState>,
+    font_size_slider_state: Entity<SliderState>,
+    /// Saves the config once the sliders stop moving.
+    slider_save: Option<Task<()>>,
+}
+
+impl SettingsPage {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        let config = cx.global::<Config>().clone();
+
+        let preamp_slider_state = cx.new(|_| {
+            SliderState::new()
+                .min(-15.0)
+                .max(15.0)
+                .default_value(config.audio.preamp)
+                .step(0.5)
+        });
+        let crossfade_slider_state = cx.new(|_| {
+            SliderState::new()
+                .min(0.0)
+                .max(12.0)
+                .default_value(config.audio.crossfade)
+                .step(0.5)
+        });
+        let font_size_slider_state = cx.new(|cx| {
+            SliderState::new()
+                .min(8.0)
+                .max(32.0)
+                .default_value(font_size(cx))
+                .step(1.0)
+        });
+
+        cx.subscribe(
+            &preamp_slider_state,
+            |this, _, event: &SliderEvent, cx| match event {
+                SliderEvent::Change(value) => {
+                    let preamp = value.start();
+                    this.slide(cx, |config| config.audio.preamp = preamp);
+                }
+            },
+        )
+        .detach();
+
+        cx.subscribe(
+            &crossfade_slider_state,
+            |this, _, event: &SliderEvent, cx| match event {
+                SliderEvent::Change(value) => {
+                    let crossfade = value.start();
+                    this.slide(cx, |config| config.audio.crossfade = crossfade);
+                }
+            },
+        )
+        .detach();
+
+        cx.subscribe(
+            &font_size_slider_state,
+            |this, _, event: &SliderEvent, cx| match event {
+                SliderEvent::Change(value) => {
+                    let font_size = value.start();
+                    this.slide(cx, |config| config.ui.font_size = Some(font_size));
+                }
+            },
+        )
+        .detach();
+
+        // Keep the sliders in step with edits made to the config file.
+        cx.observe_global::<Config>(|this, cx| {
+            let config = cx.global::<Config>().clone();
+            for (slider, value) in [
+                (&this.preamp_slider_state, config.audio.preamp),
+                (&this.crossfade_slider_state, config.audio.crossfade),
+                (&this.font_size_slider_state, font_size(cx)),
+            ] {
+                slider.update(cx, |this, cx| this.set_value(value, cx));
+            }
+            cx.notify();
+        })
+        .detach();
+
+        cx.observe_global::<Theme>(|this, cx| {
+            let value = font_size(cx);
+            this.font_size_slider_state
+                .update(cx, |this, cx| this.set_value(value, cx));
+            cx.notify();
+        })
+        .detach();
+        cx.observe_global::<ThemeRegistry>(|_, cx| cx.notify())
+            .detach();
+        cx.observe_global::<Library>(|_, cx| cx.notify()).detach();
+        cx.observe_global::<Keymap>(|_, cx| cx.notify()).detach();
+        cx.observe_global::<Controller>(|_, cx| cx.notify())
+            .detach();
+
+        SettingsPage {
+            preamp_slider_state,
+            crossfade_slider_state,
+            font_size_slider_state,
+            slider_save: None,
+        }
+    }
+
+    /// Applies a slider's value right away, but only writes the config once
+    /// the slider has come to rest, rather than on every step of a drag.
+    fn slide(&mut self, cx: &mut Context<Self>, f: impl FnOnce(&mut Config)) {
+        if let Err(err) = Config::apply(cx, f) {
+            report_error("Could not update config", err, cx);
+            return;
+        }
+
+        self.slider_save = Some(cx.spawn(async move |_, cx| {
+            cx.background_executor().timer(SLIDER_SAVE_DELAY).await;
+
+            let _ = cx.update(|cx| {
+                if let Err(err) = cx.global::<Config>().save() {
+                    report_error("Could not update config", err, cx);
+                }
+            });
+        }));
+    }
+
+    fn add_library_folders(cx: &mut App) {
+        let paths = cx.prompt_for_paths(PathPromptOptions {
+            files: false,
+            directories: true,
+            multiple: true,
+            prompt: Some("Add to library".into()),
+        });
+
+        cx.spawn(async move |cx| {
+            let Ok(Ok(Some(paths))) = paths.await else {
+                return;
+            };
+
+            let _ = cx.update(|cx| {
+                update_config(cx, |config| {
+                    for path in paths {
+                        if !config.library.roots.contains(&path) {
+                            config.library.roots.push(path);
+                        }
+                    }
+                });
+            });
+        })
+        .detach();
+    }
+
+    fn edit_keymap(cx: &mut App) {
+        match Keymap::ensure_file() {
+            Ok(path) => cx.open_with_system(&path),
+            Err(err) => report_error("Could not open the keymap", err, cx),
+        }
+    }
+
+    fn remove_library_folder(path: PathBuf, cx: &mut App) {
+        update_config(cx, |config| {
+            config.library.roots.retain(|root| root != &path)
+        });
+    }
+}
+
+impl Render for SettingsPage {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let config = cx.global::<Config>().clone();
+        let devices = cx.global::<Controller>().output_devices.clone();
+        let themes = cx.global::<ThemeRegistry>().themes.clone();
+        let keymap = cx.global::<Keymap>();
+        let follows_system = config.ui.theme.eq_ignore_ascii_case(SYSTEM_THEME);
+        let library = cx.global::<Library>();
+        let scanning = library.scanning;
+        let track_count = library.tracks.len();
+
+        div()
+            .id("settings_page")
+            .size_full()
+            .flex()
+            .flex_col()
+            .gap_8()
+            .p_8()
+            .overflow_y_scroll()
+            .text_color(theme.text)
+            .child(div().text_size(theme.font_size_lg).child("Settings"))
+            .child(
+                section("Library", theme)
+                    .child(
+                        div()
+                            .flex()
+                            .items_center()
+                            .justify_between()
+                            .child(
+                                div()
+                                    .text_size(theme.font_size_sm)
+                                    .text_color(theme.text_muted)
+                                    .child(if scanning {
+                                        String::from("Scanning…")
+                                    } else {
+                                        format!("{track_count} tracks")
+                                    }),
+                            )
+                            .child(
+                                div()
+                                    .flex()
+                                    .gap_2()
+                                    .child(
+                                        button("add_folder", "Add folder", theme)
+                                            .on_click(|_, _, cx| Self::add_library_folders(cx)),
+                                    )
+                                    .child(
+                                        button("rescan", "Rescan", theme)
+                                            .on_click(|_, _, cx| library::rescan(cx)),
+                                    ),
+                            ),
+                    )
+                    .children(config.library.roots.iter().enumerate().map(|(ix, root)| {
+                        let path = root.clone();
+
+                        row(theme)
+                            .child(div().truncate().child(root.display().to_string()))
+                            .child(button(("remove_folder", ix), "Remove", theme).on_click(
+                                move |_, _, cx| Self::remove_library_folder(path.clone(), cx),
+                            ))
+                    })),
+            )
+            .child(
+                section("Audio output", theme)
+                    .child(div().flex().justify_end().child(
+                        button("refresh_devices", "Refresh", theme).on_click(|_, _, cx| {
+                            cx.global::<Controller>().refresh_output_devices()
+                        }),
+                    ))
+                    .child(option(
+                        "device_default",
+                        "System default",
+                        config.audio.device.is_none(),
+                        theme,
+                        |cx| update_config(cx, |config| config.audio.device = None),
+                    ))
+                    .children(devices.into_iter().enumerate().map(|(ix, name)| {
+                        let active = config.audio.device.as_ref() == Some(&name);
+                        let device = name.clone();
+
+                        option(("device", ix), name, active, theme, move |cx| {
+                            let device = device.clone();
+                            update_config(cx, |config| config.audio.device = Some(device));
+                        })
+                    })),
+            )
+            .child(
+                section("Playback", theme)
+                    .child(
+                        row(theme).child("ReplayGain").child(
+                            div().flex().gap_2().children(
+                                [
+                                    (ReplayGainMode::Off, "Off"),
+                                    (ReplayGainMode::Track, "Track"),
+                                    (ReplayGainMode::Album, "Album"),
+                                ]
+                                .into_iter()
+                                .map(|(mode, label)| {
+                                    option(
+                                        ("replaygain", mode as usize),
+                                        label,
+                                        config.audio.replaygain == mode,
+                                        theme,
+                                        move |cx| {
+                                            update_config(cx, |config| {
+                                                config.audio.replaygain = mode
+                                            })
+                                        },
+                                    )
+                                }),
+                            ),
+                        ),
+                    )
+                    .child(slider_row(
+                        format!("Preamp ({:+.1} dB)", config.audio.preamp),
+                        &self.preamp_slider_state,
+                        theme,
+                    ))
+                    .child(slider_row(
+                        if config.audio.crossfade > 0.0 {
+                            format!("Crossfade ({:.1} s)", config.audio.crossfade)
+                        } else {
+                            String::from("Crossfade (off)")
+                        },
+                        &self.crossfade_slider_state,
+                        theme,
+                    ))
+                    .child(
+                        row(theme).child("Dropped files").child(
+                            div().flex().gap_2().children(
+                                [
+                                    (DropAction::Enqueue, "Add to queue"),
+                                    (DropAction::Play, "Play now"),
+                                ]
+                                .into_iter()
+                                .map(|(action, label)| {
+                                    option(
+                                        ("drop_action", action as usize),
+                                        label,
+                                        config.audio.drop_action == action,
+                                        theme,
+                                        move |cx| {
+                                            update_config(cx, |config| {
+                                                config.audio.drop_action = action
+                                            })
+                                        },
+                                    )
+                                }),
+                            ),
+                        ),
+                    ),
+            )
+            .child(
+                section("Appearance", theme)
+                    .child(
+                        row(theme).child("Theme").child(
+                            div()
+                                .flex()
+                                .flex_wrap()
+                                .justify_end()
+                                .gap_2()
+                                .child(theme_option(
+                                    "theme_system",
+                                    SYSTEM_THEME,
+                                    &config.ui.theme,
+                                    theme,
+                                    |config, name| config.ui.theme = name,
+                                ))
+                                .children(themes.iter().enumerate().map(|(ix, t)| {
+                                    theme_option(
+                                        ("theme", ix),
+                                        &t.name,
+                                        &config.ui.theme,
+                                        theme,
+                                        |config, name| config.ui.theme = name,
+                                    )
+                                })),
+                        ),
+                    )
+                    .when(follows_system, |this| {
+                        this.child(
+                            row(theme).child("Light theme").child(
+                                div().flex().flex_wrap().justify_end().gap_2().children(
+                                    themes
+                                        .iter()
+                                        .enumerate()
+                                        .filter(|(_, t)| t.appearance == Appearance::Light)
+                                        .map(|(ix, t)| {
+                                            theme_option(
+                                                ("light_theme", ix),
+                                                &t.name,
+                                                &config.ui.light_theme,
+                                                theme,
+                                                |config, name| config.ui.light_theme = name,
+                                            )
+                                        }),
+                                ),
+                            ),
+                        )
+                        .child(
+                            row(theme).child("Dark theme").child(
+                                div().flex().flex_wrap().justify_end().gap_2().children(
+                                    themes
+                                        .iter()
+                                        .enumerate()
+                                        .filter(|(_, t)| t.appearance == Appearance::Dark)
+                                        .map(|(ix, t)| {
+                                            theme_option(
+                                                ("dark_theme", ix),
+                                                &t.name,
+                                                &config.ui.dark_theme,
+                                                theme,
+                                                |config, name| config.ui.dark_theme = name,
+                                            )
+                                        }),
+                                ),
+                            ),
+                        )
+                    })
+                    .child(
+                        slider_row(
+                            match config.ui.font_size {
+                                Some(size) => format!("Font size ({size})"),
+                                None => format!("Font size ({}, theme default)", font_size(cx)),
+                            },
+                            &self.font_size_slider_state,
+                            theme,
+                        )
+                        .when(config.ui.font_size.is_some(), |this| {
+                            this.child(button("reset_font_size", "Reset", theme).on_click(
+                                |_, _, cx| update_config(cx, |config| config.ui.font_size = None),
+                            ))
+                        }),
+                    )
+                    .child(toggle(
+                        "dynamic_accent",
+                        "Accent color from album art",
+                        config.ui.dynamic_accent,
+                        theme,
+                        |cx| {
+                            update_config(cx, |config| {
+                                config.ui.dynamic_accent = !config.ui.dynamic_accent
+                            })
+                        },
+                    ))
+                    .child(
+                        row(theme).child("Visualizer").child(
+                            div().flex().gap_2().children(
+                                [
+                                    (VisualizerMode::Off, "Off"),
+                                    (VisualizerMode::Spectrum, "Spectrum"),
+                                    (VisualizerMode::Oscilloscope, "Oscilloscope"),
+                                    (VisualizerMode::Vectorscope, "Vectorscope"),
+                                ]
+                                .into_iter()
+                                .map(|(mode, label)| {
+                                    option(
+                                        ("visualizer", mode as usize),
+                                        label,
+                                        config.ui.visualizer == mode,
+                                        theme,
+                                        move |cx| {
+                                            update_config(cx, |config| config.ui.visualizer = mode)
+                                        },
+                                    )
+                                }),
+                            ),
+                        ),
+                    )
+                    .child(toggle(
+                        "mini_player_on_top",
+                        "Keep the mini player on top",
+                        config.ui.mini_player_on_top,
+                        theme,
+                        |cx| {
+                            update_config(cx, |config| {
+                                config.ui.mini_player_on_top = !config.ui.mini_player_on_top
+                            })
+                        },
+                    ))
+                    .child(toggle(
+                        "show_navbar",
+                        "Show navigation bar",
+                        config.ui.layout.navbar,
+                        theme,
+                        |cx| {
+                            update_config(cx, |config| {
+                                config.ui.layout.navbar = !config.ui.layout.navbar
+                            })
+                        },
+                    ))
+                    .child(toggle(
+                        "show_queue",
+                        "Show queue panel",
+                        config.ui.layout.queue,
+                        theme,
+                        |cx| {
+                            update_config(cx, |config| {
+                                config.ui.layout.queue = !config.ui.layout.queue
+                            })
+                        },
+                    ))
+                    .child(
+                        row(theme).child("Control bar").child(
+                            div().flex().gap_2().children(
+                                [
+                                    (ControlBarPosition::Top, "Top"),
+                                    (ControlBarPosition::Bottom, "Bottom"),
+                                ]
+                                .into_iter()
+                                .map(|(position, label)| {
+                                    option(
+                                        ("controlbar", position as usize),
+                                        label,
+                                        config.ui.layout.controlbar == position,
+                                        theme,
+                                        move |cx| {
+                                            update_config(cx, |config| {
+                                                config.ui.layout.controlbar = position
+                                            })
+                                        },
+                                    )
+                                }),
+                            ),
+                        ),
+                    ),
+            )
+            .child(
+                section("Integrations", theme)
+                    .child(toggle(
+                        "desktop_notifications",
+                        "Desktop notifications on track change",
+                        config.integrations.notifications,
+                        theme,
+                        |cx| {
+                            update_config(cx, |config| {
+                                config.integrations.notifications =
+                                    !config.integrations.notifications
+                            })
+                        },
+                    ))
+                    .child(toggle(
+                        "notification_actions",
+                        "Previous and next buttons on notifications",
+                        config.integrations.notification_actions,
+                        theme,
+                        |cx| {
+                            update_config(cx, |config| {
+                                config.integrations.notification_actions =
+                                    !config.integrations.notification_actions
+                            })
+                        },
+                    ))
+                    .child(toggle(
+                        "quiet_when_focused",
+                        "No notifications while the window is focused",
+                        config.integrations.quiet_when_focused,
+                        theme,
+                        |cx| {
+                            update_config(cx, |config| {
+                                config.integrations.quiet_when_focused =
+                                    !config.integrations.quiet_when_focused
+                            })
+                        },
+                    ))
+                    .child(toggle(
+                        "tray",
+                        "Show tray icon",
+                        config.integrations.tray,
+                        theme,
+                        |cx| {
+                            update_config(cx, |config| {
+                                config.integrations.tray = !config.integrations.tray
+                            })
+                        },
+                    ))
+                    .child(toggle(
+                        "close_to_tray",
+                        "Keep playing in the tray when the window is closed",
+                        config.integrations.close_to_tray,
+                        theme,
+                        |cx| {
+                            update_config(cx, |config| {
+                                config.integrations.close_to_tray =
+                                    !config.integrations.close_to_tray
+                            })
+                        },
+                    )),
+            )
+            .child(
+                section("Keyboard shortcuts", theme)
+                    .child(
+                        div()
+                            .flex()
+                            .items_center()
+                            .justify_between()
+                            .child(
+                                div()
+                                    .text_size(theme.font_size_sm)
+                                    .text_color(theme.text_muted)
+                                    .child("Changes to the keymap file apply right away."),
+                            )
+                            .child(
+                                button("edit_keymap", "Edit keymap", theme)
+                                    .on_click(|_, _, cx| Self::edit_keymap(cx)),
+                            ),
+                    )
+                    .child(
+                        div().flex().flex_col().px_3().children(
+                            ACTIONS.iter().zip(&keymap.keys).map(|(action, keys)| {
+                                shortcut_row(action.description, keys, theme)
+                            }),
+                        ),
+                    ),
+            )
+    }
+}
+
+fn section(title: &'static str, theme: &Theme) -> Div {
+    div()
+        .w_full()
+        .flex()
+        .flex_col()
+        .gap_2()
+        .child(div().text_color(theme.text_muted).child(title))
+}
+
+fn row(theme: &Theme) -> Div {
+    div()
+        .w_full()
+        .flex()
+        .gap_4()
+        .items_center()
+        .justify_between()
+        .px_3()
+        .py_2()
+        .rounded(theme.radius)
+        .bg(theme.panel)
+}
+
+fn button(id: impl Into<ElementId>, label: &'static str, theme: &Theme) -> Stateful<Div> {
+    div()
+        .id(id)
+        .flex_shrink_0()
+        .px_3()
+        .py_1()
+        .rounded(theme.radius)
+        .text_size(theme.font_size_sm)
+        .bg(theme.highlighted)
+        .hover(|this| this.bg(theme.border))
+        .child(label)
+}
+
+fn option(
+    id: impl Into<ElementId>,
+    label: impl Into<SharedString>,
+    active: bool,
+    theme: &Theme,
+    on_select: impl Fn(&mut App) + 'static,
+) -> Stateful<Div> {
+    div()
+        .id(id)
+        .px_3()
+        .py_2()
+        .rounded(theme.radius)
+        .bg(if active { theme.accent } else { theme.panel })
+        .hover(|this| {
+            if active {
+                this.bg(theme.accent)
+            } else {
+                this.bg(theme.highlighted)
+            }
+        })
+        .on_click(move |_, _, cx| on_select(cx))
+        .child(label.into())
+}
+
+fn toggle(
+    id: &'static str,
+    label: &'static str,
+    on: bool,
+    theme: &Theme,
+    on_toggle: impl Fn(&mut App) + 'static,
+) -> Stateful<Div> {
+    row(theme)
+        .id(id)
+        .hover(|this| this.bg(theme.highlighted))
+        .on_click(move |_, _, cx| on_toggle(cx))
+        .child(label)
+        .child(
+            div()
+                .w_10()
+                .h_5()
+                .flex_shrink_0()
+                .flex()
+                .items_center()
+                .p(px(2.0))
+                .rounded_full()
+                .bg(if on { theme.accent } else { theme.border })
+                .when(on, |this| this.justify_end())
+                .child(div().size_4().rounded_full().bg(theme.text)),
+        )
+}
+
+fn slider_row(label: String, state: &Entity<SliderState>, theme: &Theme) -> Div {
+    row(theme)
+        .child(div().flex_shrink_0().child(label))
+        .child(div().w_64().child(Slider::new(state)))
+}
+
+fn theme_option(
+    id: impl Into<ElementId>,
+    name: &str,
+    selected: &str,
+    theme: &Theme,
+    set: impl Fn(&mut Config, String) + 'static,
+) -> Stateful<Div> {
+    let value = name.to_string();
+
+    option(
+        id,
+        value.clone(),
+        name.eq_ignore_ascii_case(selected),
+        theme,
+        move |cx| {
+            let value = value.clone();
+            update_config(cx, |config| set(config, value))
+        },
+    )
+}
+
+/// Updates and saves the config, showing why when that fails.
+fn update_config(cx: &mut App, f: impl FnOnce(&mut Config)) {
+    if let Err(err) = Config::update(cx, f) {
+        report_error("Could not update config", err, cx);
+    }
+}
+
+/// The font size in effect, either set in the config or taken from the theme.
+fn font_size(cx: &App) -> f32 {
+    cx.global::<Config>()
+        .ui
+        .font_size
+        .unwrap_or_else(|| f32::from(cx.global::<Theme>().font_size))
+}
@@ -1,6 +1,30 @@
-use gpui::{rgb, Rgba};
+use crate::config::{self, Config};
+use crate::ui::accent::AlbumPalette;
+use anyhow::{Context, Result};
+use gpui::{App, Pixels, Rgba, SharedString, Window, WindowAppearance, px, rgb};
+use notify::RecommendedWatcher;
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
+/// Value of `ui.theme` that follows the system light/dark preference.
+pub const SYSTEM_THEME: &str = "system";
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Appearance {
+    Light,
+    #[default]
+    Dark,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Theme {
+    pub name: SharedString,
+    pub appearance: Appearance,
+
     pub bg: Rgba,
     pub panel: Rgba,
     pub highlighted: Rgba,
@@ -11,11 +35,27 @@ pub struct Theme {
     pub text_muted: Rgba,
 
     pub border: Rgba,
+    pub error: Rgba,
+
+    pub font_size: Pixels,
+    pub font_size_sm: Pixels,
+    pub font_size_lg: Pixels,
+
+    pub radius: Pixels,
+    pub radius_lg: Pixels,
 }
 
 impl Default for Theme {
     fn default() -> Self {
+        Theme::dark()
+    }
+}
+
+impl Theme {
+    pub fn dark() -> Self {
         Theme {
+            name: "dark".into(),
+            appearance: Appearance::Dark,
             bg: rgb(0x131319),
             panel: rgb(0x14141d),
             highlighted: rgb(0x202332),
@@ -23,8 +63,259 @@ impl Default for Theme {
             text: rgb(0xceced2),
             text_muted: rgb(0x5e6067),
             border: rgb(0x1b1d25),
+            error: rgb(0xe81123),
+            font_size: px(16.0),
+            font_size_sm: px(14.0),
+            font_size_lg: px(20.0),
+            radius: px(6.0),
+            radius_lg: px(10.0),
+        }
+    }
+
+    pub fn light() -> Self {
+        Theme {
+            name: "light".into(),
+            appearance: Appearance::Light,
+            bg: rgb(0xf4f4f6),
+            panel: rgb(0xfbfbfc),
+            highlighted: rgb(0xe4e4ea),
+            accent: rgb(0x6841f0),
+            text: rgb(0x1d1d22),
+            text_muted: rgb(0x6e707a),
+            border: rgb(0xdcdce2),
+            error: rgb(0xd0101f),
+            ..Theme::dark()
         }
     }
+
+    /// Picks the theme named in the config, or the light/dark theme matching
+    /// the window when following the system, tints it with the cover art's
+    /// palette if enabled, and makes it the global theme.
+    pub fn sync(window: &mut Window, cx: &mut App) {
+        let ui = &cx.global::<Config>().ui;
+        let appearance = match window.appearance() {
+            WindowAppearance::Light | WindowAppearance::VibrantLight => Appearance::Light,
+            WindowAppearance::Dark | WindowAppearance::VibrantDark => Appearance::Dark,
+        };
+        let name = if ui.theme.eq_ignore_ascii_case(SYSTEM_THEME) {
+            match appearance {
+                Appearance::Light => &ui.light_theme,
+                Appearance::Dark => &ui.dark_theme,
+            }
+        } else {
+            &ui.theme
+        };
+
+        let registry = cx.global::<ThemeRegistry>();
+        let mut theme = registry
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| match appearance {
+                Appearance::Light => Theme::light(),
+                Appearance::Dark => Theme::dark(),
+            });
+
+        if ui.dynamic_accent
+            && let Some(palette) = cx.global::<AlbumPalette>().palette
+        {
+            palette.apply(&mut theme);
+        }
+
+        if cx.has_global::<Theme>() && cx.global::<Theme>() == &theme {
+            return;
+        }
+
+        // Keep the component library's own widgets (sliders and the like)
+        // on the same side of light/dark as the rest of the UI.
+        gpui_component::Theme::change(
+            match theme.appearance {
+                Appearance::Light => gpui_component::ThemeMode::Light,
+                Appearance::Dark => gpui_component::ThemeMode::Dark,
+            },
+            Some(window),
+            cx,
+        );
+        cx.set_global(theme);
+    }
+
+    fn from_file(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("could not read {}", path.display()))?;
+        let file: ThemeFile = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&contents)
+                .with_context(|| format!("invalid theme in {}", path.display()))?,
+            _ => toml::from_str(&contents)
+                .with_context(|| format!("invalid theme in {}", path.display()))?,
+        };
+
+        let name = match file.name {
+            Some(name) => name,
+            None => path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .context("theme has no name")?,
+        };
+
+        // Anything left out is taken from the built-in theme of the same
+        // appearance, so a theme can be as small as a single accent color.
+        let mut theme = match file.appearance {
+            Appearance::Light => Theme::light(),
+            Appearance::Dark => Theme::dark(),
+        };
+        theme.name = name.into();
+
+        let colors = file.colors;
+        for (field, value, key) in [
+            (&mut theme.bg, colors.bg, "bg"),
+            (&mut theme.panel, colors.panel, "panel"),
+            (&mut theme.highlighted, colors.highlighted, "highlighted"),
+            (&mut theme.accent, colors.accent, "accent"),
+            (&mut theme.text, colors.text, "text"),
+            (&mut theme.text_muted, colors.text_muted, "text_muted"),
+            (&mut theme.border, colors.border, "border"),
+            (&mut theme.error, colors.error, "error"),
+        ] {
+            if let Some(value) = value {
+                *field = Rgba::try_from(value.as_str()).with_context(|| {
+                    format!("colors.{key} in {} is not a valid color", path.display())
+                })?;
+            }
+        }
+
+        let sizes = file.sizes;
+        for (field, value, key) in [
+            (&mut theme.font_size, sizes.font_size, "font_size"),
+            (&mut theme.font_size_sm, sizes.font_size_sm, "font_size_sm"),
+            (&mut theme.font_size_lg, sizes.font_size_lg, "font_size_lg"),
+            (&mut theme.radius, sizes.radius, "radius"),
+            (&mut theme.radius_lg, sizes.radius_lg, "radius_lg"),
+        ] {
+            if let Some(value) = value {
+                if !(0.0..=64.0).contains(&value) {
+                    anyhow::bail!(
+                        "sizes.{key} in {} must be between 0 and 64, got {value}",
+                        path.display()
+                    );
+                }
+                *field = px(value);
+            }
+        }
+
+        Ok(theme)
+    }
 }
 
 impl gpui::Global for Theme {}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    name: Option<String>,
+    #[serde(default)]
+    appearance: Appearance,
+    #[serde(default)]
+    colors: ThemeColors,
+    #[serde(default)]
+    sizes: ThemeSizes,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ThemeColors {
+    bg: Option<String>,
+    panel: Option<String>,
+    highlighted: Option<String>,
+    accent: Option<String>,
+    text: Option<String>,
+    text_muted: Option<String>,
+    border: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ThemeSizes {
+    font_size: Option<f32>,
+    font_size_sm: Option<f32>,
+    font_size_lg: Option<f32>,
+    radius: Option<f32>,
+    radius_lg: Option<f32>,
+}
+
+/// The built-in themes plus every theme found in the themes directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThemeRegistry {
+    pub themes: Vec<Theme>,
+}
+
+impl Default for ThemeRegistry {
+    fn default() -> Self {
+        ThemeRegistry {
+            themes: vec![Theme::dark(), Theme::light()],
+        }
+    }
+}
+
+impl ThemeRegistry {
+    pub fn dir() -> Option<PathBuf> {
+        Config::dir().map(|dir| dir.join("themes"))
+    }
+
+    /// Loads every `.toml` and `.json` theme in the themes directory. Themes
+    /// that fail to load are reported and skipped; one named after a
+    /// built-in theme replaces it.
+    pub fn load() -> (Self, Vec<anyhow::Error>) {
+        let mut registry = ThemeRegistry::default();
+        let mut errors = Vec::new();
+
+        let Some(entries) = Self::dir().and_then(|dir| fs::read_dir(dir).ok()) else {
+            return (registry, errors);
+        };
+
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| is_theme_file(path))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            match Theme::from_file(&path) {
+                Ok(theme) => {
+                    registry
+                        .themes
+                        .retain(|t| !t.name.eq_ignore_ascii_case(&theme.name));
+                    registry.themes.push(theme);
+                }
+                Err(err) => errors.push(err),
+            }
+        }
+
+        (registry, errors)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Theme> {
+        self.themes
+            .iter()
+            .find(|theme| theme.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Watches the themes directory and signals whenever a theme file
+    /// changes. The returned watcher stops watching when dropped.
+    pub fn watch(tx: async_channel::Sender<()>) -> Result<RecommendedWatcher> {
+        let dir = Self::dir().context("could not determine the config directory")?;
+
+        config::watch_dir(&dir, is_theme_file, move || {
+            let _ = tx.try_send(());
+        })
+    }
+}
+
+impl gpui::Global for ThemeRegistry {}
+
+fn is_theme_file(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("toml" | "json")
+    )
+}
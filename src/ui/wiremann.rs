@@ -1,32 +1,70 @@
 use super::{
-    components::{controlbar::ControlBar, navbar::NavBar, titlebar::Titlebar},
-    theme::Theme,
+    accent::AlbumPalette,
+    components::{
+        controlbar::ControlBar,
+        mini_player::{self, MiniPlayer},
+        navbar::NavBar,
+        notifications::Notifications,
+        palette::{CommandPalette, CommandPaletteEvent},
+        queue::QueuePanel,
+        shortcuts::shortcuts_overlay,
+        titlebar::Titlebar,
+    },
+    keymap::{
+        Dismiss, FocusSearch, KEY_CONTEXT, Keymap, TYPING_CONTEXT, ToggleCommandPalette,
+        ToggleMiniPlayer, ToggleShortcuts,
+    },
+    pages::{
+        library::LibraryPage, lyrics::LyricsPage, playlists::PlaylistsPage, settings::SettingsPage,
+    },
+    theme::{Theme, ThemeRegistry},
 };
-use crate::{audio::engine::PlaybackState, controller::player::Controller, ui::components::Page};
-use gpui::*;
-use gpui_component::slider::{SliderEvent, SliderState};
+use crate::{
+    config::{Config, ControlBarPosition, DropAction},
+    controller::player::Controller,
+    library,
+    ui::components::{Page, notifications::NotificationLevel},
+};
+use gpui::{prelude::FluentBuilder, *};
+use gpui_component::{
+    Root,
+    slider::{SliderEvent, SliderState},
+};
+use std::path::PathBuf;
 
 pub struct Wiremann {
     pub titlebar: Entity<Titlebar>,
     pub navbar: Entity<NavBar>,
     pub controlbar: Entity<ControlBar>,
+    pub queue_panel: Entity<QueuePanel>,
+    pub notifications: Entity<Notifications>,
+    pub library_page: Entity<LibraryPage>,
+    pub playlists_page: Entity<PlaylistsPage>,
+    pub lyrics_page: Entity<LyricsPage>,
+    pub settings_page: Entity<SettingsPage>,
+    focus_handle: FocusHandle,
+    shortcuts_open: bool,
+    palette: Option<Entity<CommandPalette>>,
+    mini_player: Option<MiniPlayerWindow>,
+    /// Where the mini player was when it was last closed, so it reopens
+    /// there.
+    mini_player_bounds: Option<Bounds<Pixels>>,
+}
+
+/// The open mini player, and the page to go back to from it.
+struct MiniPlayerWindow {
+    handle: WindowHandle<Root>,
+    page: Page,
 }
 
 impl Wiremann {
-    pub fn new(cx: &mut Context<Self>) -> Self {
+    pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let volume = cx.global::<Controller>().state.volume * 100.0;
         let vol_slider_state = cx.new(|_| {
             SliderState::new()
                 .min(0.0)
                 .max(100.0)
-                .default_value(100.0)
-                .step(1.0)
-        });
-
-        let playback_slider_state = cx.new(|_| {
-            SliderState::new()
-                .min(0.0)
-                .max(100.0)
-                .default_value(0.0)
+                .default_value(volume)
                 .step(1.0)
         });
 
@@ -41,69 +79,406 @@ impl Wiremann {
         )
         .detach();
 
-        cx.subscribe(
-            &playback_slider_state,
-            |_, _, event: &SliderEvent, cx| match event {
-                SliderEvent::Change(value) => {
-                    let controller = cx.global::<Controller>();
-                    if controller.state.state == PlaybackState::Playing {
-                        if let Some(meta) = controller.state.clone().meta {
-                            controller.seek(slider_to_secs(value.start(), meta.duration));
-                        }
-                    }
+        Theme::sync(window, cx);
+        cx.set_global(Page::Home);
+        cx.observe_global::<Page>(|_, cx| cx.notify()).detach();
+        cx.observe_global_in::<Config>(window, |_, window, cx| {
+            Theme::sync(window, cx);
+            cx.notify();
+        })
+        .detach();
+        cx.observe_global_in::<ThemeRegistry>(window, |_, window, cx| {
+            Theme::sync(window, cx);
+            cx.notify();
+        })
+        .detach();
+        cx.observe_global_in::<AlbumPalette>(window, |_, window, cx| {
+            Theme::sync(window, cx);
+            cx.notify();
+        })
+        .detach();
+        cx.observe_global::<Keymap>(|_, cx| cx.notify()).detach();
+        cx.observe_window_appearance(window, |_, window, cx| {
+            Theme::sync(window, cx);
+            cx.notify();
+        })
+        .detach();
 
-                    cx.notify();
-                }
+        let titlebar = cx.new(|cx| Titlebar::new(window, cx));
+        let search = titlebar.read(cx).search.clone();
+        cx.subscribe_in(&search, window, |this, _, _: &DismissEvent, window, _| {
+            window.focus(&this.focus_handle);
+        })
+        .detach();
+        let navbar = cx.new(|_| NavBar::new());
+        let controlbar = cx.new(|cx| ControlBar::new(vol_slider_state, cx));
+        let queue_panel = cx.new(|cx| QueuePanel::new(cx));
+        cx.subscribe_in(
+            &queue_panel,
+            window,
+            |this, _, _: &DismissEvent, window, _| {
+                window.focus(&this.focus_handle);
             },
         )
         .detach();
+        let notifications = cx.new(|_| Notifications::new());
+        Notifications::set_main(&notifications, cx);
+        let library_page = cx.new(|cx| LibraryPage::new(cx));
+        let playlists_page = cx.new(|cx| PlaylistsPage::new(cx));
+        let lyrics_page = cx.new(|cx| LyricsPage::new(cx));
+        let settings_page = cx.new(|cx| SettingsPage::new(cx));
 
-        cx.set_global(Theme::default());
-        cx.set_global(Page::Home);
-
-        let titlebar = cx.new(|_| Titlebar::new());
-        let navbar = cx.new(|_| NavBar::new());
-        let controlbar = cx.new(|_| ControlBar::new(playback_slider_state, vol_slider_state));
+        // Shortcuts are dispatched from the focused element up, so the root
+        // holds focus whenever nothing more specific does.
+        let focus_handle = cx.focus_handle();
+        window.focus(&focus_handle);
 
         Self {
             titlebar,
             navbar,
             controlbar,
+            queue_panel,
+            notifications,
+            library_page,
+            playlists_page,
+            lyrics_page,
+            settings_page,
+            focus_handle,
+            shortcuts_open: false,
+            palette: None,
+            mini_player: None,
+            mini_player_bounds: None,
+        }
+    }
+
+    fn toggle_command_palette(
+        &mut self,
+        _: &ToggleCommandPalette,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if self.palette.is_some() {
+            self.close_command_palette(window, cx);
+            return;
+        }
+
+        self.shortcuts_open = false;
+        let palette = cx.new(|cx| CommandPalette::new(window, cx));
+        cx.subscribe_in(
+            &palette,
+            window,
+            |this, _, event: &CommandPaletteEvent, window, cx| {
+                this.close_command_palette(window, cx);
+
+                if let CommandPaletteEvent::Run(command) = event {
+                    command.clone().run(window, cx);
+                }
+            },
+        )
+        .detach();
+
+        self.palette = Some(palette);
+        cx.notify();
+    }
+
+    /// Closes the palette and hands focus back to the root, so shortcuts and
+    /// actions run from the palette are dispatched as usual.
+    fn close_command_palette(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.palette = None;
+        window.focus(&self.focus_handle);
+        cx.notify();
+    }
+
+    fn toggle_mini_player(
+        &mut self,
+        _: &ToggleMiniPlayer,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if self.mini_player.is_some() {
+            self.close_mini_player(window, cx);
+        } else {
+            self.open_mini_player(window, cx);
+        }
+    }
+
+    /// Opens the mini player and minimizes the main window. The main window
+    /// keeps its page and bounds, so going back finds it as it was left.
+    fn open_mini_player(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let view = cx.new(|cx| MiniPlayer::new(cx));
+        let bounds = self
+            .mini_player_bounds
+            .unwrap_or_else(|| Bounds::centered(None, mini_player::SIZE, cx));
+        let on_top = cx.global::<Config>().ui.mini_player_on_top;
+
+        let options = WindowOptions {
+            window_bounds: Some(WindowBounds::Windowed(bounds)),
+            app_id: Some(String::from("wiremann")),
+            focus: true,
+            titlebar: None,
+            kind: if on_top {
+                WindowKind::PopUp
+            } else {
+                WindowKind::Normal
+            },
+            is_resizable: false,
+            window_decorations: Some(WindowDecorations::Client),
+            ..Default::default()
+        };
+
+        let this = cx.weak_entity();
+        let handle = cx.open_window(options, |mini_window, cx| {
+            view.read(cx).focus(mini_window);
+
+            // Closing it from the window manager goes back to the full
+            // layout too, rather than leaving the main window minimized.
+            // The window is gone by the time that's handled, so its bounds
+            // are kept here.
+            let closed = view.clone();
+            mini_window.on_window_should_close(cx, move |mini_window, cx| {
+                let bounds = mini_window.bounds();
+                let _ = this.update(cx, |this, _| this.mini_player_bounds = Some(bounds));
+                closed.update(cx, |_, cx| cx.emit(DismissEvent));
+                true
+            });
+
+            cx.new(|cx| Root::new(view.clone(), mini_window, cx))
+        });
+
+        let handle = match handle {
+            Ok(handle) => handle,
+            Err(err) => {
+                self.notifications.update(cx, |this, cx| {
+                    this.push(
+                        NotificationLevel::Error,
+                        "Could not open the mini player",
+                        format!("{err:#}"),
+                        cx,
+                    );
+                });
+                return;
+            }
+        };
+
+        cx.subscribe_in(&view, window, |this, _, _: &DismissEvent, window, cx| {
+            this.close_mini_player(window, cx);
+        })
+        .detach();
+
+        self.shortcuts_open = false;
+        self.mini_player = Some(MiniPlayerWindow {
+            handle,
+            page: *cx.global::<Page>(),
+        });
+        window.minimize_window();
+    }
+
+    /// Closes the mini player and brings the main window back on the page
+    /// it was on.
+    fn close_mini_player(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(mini_player) = self.mini_player.take() else {
+            return;
+        };
+
+        // Fails when the window manager already closed it.
+        let _ = mini_player.handle.update(cx, |_, mini_window, _| {
+            self.mini_player_bounds = Some(mini_window.bounds());
+            mini_window.remove_window();
+        });
+
+        cx.set_global(mini_player.page);
+        window.activate_window();
+        window.focus(&self.focus_handle);
+        cx.notify();
+    }
+
+    fn focus_search(&mut self, _: &FocusSearch, window: &mut Window, cx: &mut Context<Self>) {
+        if self.palette.is_some() {
+            self.close_command_palette(window, cx);
+        }
+
+        self.shortcuts_open = false;
+        self.titlebar.read(cx).search.read(cx).focus(window, cx);
+        cx.notify();
+    }
+
+    /// Whether a text field has focus, so plain keys type instead of
+    /// triggering shortcuts.
+    fn is_typing(&self, window: &Window, cx: &App) -> bool {
+        self.palette.is_some()
+            || self.library_page.read(cx).is_editing()
+            || self.queue_panel.read(cx).is_editing()
+            || self.lyrics_page.read(cx).is_editing(cx)
+            || self
+                .titlebar
+                .read(cx)
+                .search
+                .read(cx)
+                .is_focused(window, cx)
+    }
+
+    /// Queues or plays dropped files, folders and playlists, depending on
+    /// the configured drop action. Folders can be large, so they're walked
+    /// in the background.
+    fn open_paths(&mut self, paths: Vec<PathBuf>, cx: &mut Context<Self>) {
+        let expand = cx
+            .background_executor()
+            .spawn(async move { library::expand(&paths) });
+
+        cx.spawn(async move |this, cx| {
+            let (tracks, errors) = expand.await;
+            let _ = this.update(cx, |this, cx| {
+                for err in errors {
+                    this.notifications.update(cx, |this, cx| {
+                        this.push(
+                            NotificationLevel::Error,
+                            "Could not open playlist",
+                            format!("{err:#}"),
+                            cx,
+                        );
+                    });
+                }
+
+                if tracks.is_empty() {
+                    return;
+                }
+
+                let count = tracks.len();
+                let controller = cx.global::<Controller>();
+                let message = match cx.global::<Config>().audio.drop_action {
+                    DropAction::Enqueue => {
+                        controller.enqueue(tracks);
+                        "added to the queue"
+                    }
+                    DropAction::Play => {
+                        controller.play_tracks(tracks, 0);
+                        "playing"
+                    }
+                };
+
+                this.notifications.update(cx, |this, cx| {
+                    let title = match count {
+                        1 => String::from("1 track"),
+                        n => format!("{n} tracks"),
+                    };
+                    this.push(NotificationLevel::Info, title, message, cx);
+                });
+            });
+        })
+        .detach();
+    }
+
+    fn toggle_shortcuts(&mut self, _: &ToggleShortcuts, _: &mut Window, cx: &mut Context<Self>) {
+        self.shortcuts_open = !self.shortcuts_open;
+        cx.notify();
+    }
+
+    fn dismiss(&mut self, _: &Dismiss, _: &mut Window, cx: &mut Context<Self>) {
+        if self.shortcuts_open {
+            self.shortcuts_open = false;
+            cx.notify();
+        } else {
+            cx.propagate();
         }
     }
 }
 
 impl Render for Wiremann {
-    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let mut key_context = KeyContext::new_with_defaults();
+        key_context.add(KEY_CONTEXT);
+        if self.is_typing(window, cx) {
+            key_context.add(TYPING_CONTEXT);
+        }
+
         let theme = cx.global::<Theme>();
+        let page = *cx.global::<Page>();
+        let ui = &cx.global::<Config>().ui;
+        let controlbar_on_top = ui.layout.controlbar == ControlBarPosition::Top;
+
+        let content = match page {
+            Page::Home => div().size_full().min_h_0().child(self.library_page.clone()),
+            Page::Playlists => div()
+                .size_full()
+                .min_h_0()
+                .child(self.playlists_page.clone()),
+            Page::Lyrics => div().size_full().min_h_0().child(self.lyrics_page.clone()),
+            Page::Settings => div().size_full().child(self.settings_page.clone()),
+        };
+
         div()
             .id("main_container")
+            .key_context(key_context)
+            .track_focus(&self.focus_handle)
+            .on_action(cx.listener(Self::toggle_command_palette))
+            .on_action(cx.listener(Self::focus_search))
+            .on_action(cx.listener(Self::toggle_mini_player))
+            .drag_over::<ExternalPaths>(|style, _, _, cx| {
+                style.border_2().border_color(cx.global::<Theme>().accent)
+            })
+            .on_drop(cx.listener(|this, paths: &ExternalPaths, _, cx| {
+                this.open_paths(paths.paths().to_vec(), cx)
+            }))
+            .on_action(cx.listener(Self::toggle_shortcuts))
+            .on_action(cx.listener(Self::dismiss))
             .size_full()
+            .relative()
             .flex()
             .flex_col()
             .justify_center()
             .items_center()
             .bg(theme.bg)
+            .text_size(ui.font_size.map(px).unwrap_or(theme.font_size))
+            .when_some(ui.font.clone(), |this, font| this.font_family(font))
             .child(self.titlebar.clone())
             .child(
                 div()
                     .w_full()
                     .h_full()
                     .flex()
-                    .child(self.navbar.clone())
+                    .when(ui.layout.navbar, |this| this.child(self.navbar.clone()))
                     .child(
                         div()
                             .h_full()
                             .w_full()
                             .flex()
-                            .flex_col()
-                            .child(div().w_full().h_full().flex())
+                            .when(controlbar_on_top, |this| this.flex_col_reverse())
+                            .when(!controlbar_on_top, |this| this.flex_col())
+                            .child(
+                                div()
+                                    .w_full()
+                                    .flex_1()
+                                    .min_h_0()
+                                    .flex()
+                                    .child(content)
+                                    .when(ui.layout.queue, |this| {
+                                        this.child(self.queue_panel.clone())
+                                    }),
+                            )
                             .child(self.controlbar.clone()),
                     ),
             )
+            .child(self.notifications.clone())
+            .when(self.shortcuts_open, |this| {
+                this.child(shortcuts_overlay(cx.global::<Keymap>(), theme))
+            })
+            .when_some(self.palette.clone(), |this, palette| {
+                this.child(
+                    div()
+                        .id("command_palette_overlay")
+                        .absolute()
+                        .size_full()
+                        .flex()
+                        .justify_center()
+                        .pt_24()
+                        .bg(rgba(0x00000080))
+                        .on_click(
+                            cx.listener(|this, _, window, cx| {
+                                this.close_command_palette(window, cx)
+                            }),
+                        )
+                        .child(palette),
+                )
+            })
     }
 }
-
-fn slider_to_secs(slider: f32, duration_secs: u64) -> u64 {
-    ((slider.clamp(0.0, 100.0) / 100.0) * duration_secs as f32) as u64
-}